@@ -0,0 +1,14 @@
+#ifndef __STDARG_H
+#define __STDARG_H
+
+/* System V AMD64 ABIの可変長引数リスト。vprintfなどのva_listを受け取る関数にそのまま渡せる。 */
+typedef __builtin_va_list va_list;
+typedef __builtin_va_list __gnuc_va_list;
+
+#define va_start(ap, last) __builtin_va_start(ap, last)
+#define va_arg(ap, ty) __builtin_va_arg(ap, ty)
+#define va_end(ap) ((void)(ap))
+#define va_copy(dest, src) (*(dest) = *(src))
+#define __va_copy(dest, src) va_copy(dest, src)
+
+#endif
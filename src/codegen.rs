@@ -1,48 +1,931 @@
-use crate::parse::{Node, NodeKind};
+use crate::parse::{Function, GVar, LVar, Node, NodeKind};
+use crate::types::{align_to, Type, TypeKind};
 
 /// 与えられたノードが変数を指しているときに、その変数のアドレスを計算して、それをスタックにプッシュする。
-/// それ以外の場合にはエラーを表示する。
+/// 左辺値でないノードは意味解析でエラーにしているので、ここには来ない。
 fn gen_lval(node: &Node) -> String {
-    if node.kind == NodeKind::LVar {
-        let mut result = String::new();
-        result.push_str("  mov rax, rbp\n");
-        result.push_str(&format!("  sub rax, {}\n", node.offset));
-        result.push_str("  push rax\n");
-        result
+    let mut result = String::new();
+    match node.kind {
+        NodeKind::LVar => {
+            result.push_str("  mov rax, rbp\n");
+            result.push_str(&format!("  sub rax, {}\n", node.offset));
+            result.push_str("  push rax\n");
+        }
+        NodeKind::GVar => {
+            // 共有ライブラリにある変数や関数も指せるように、GOTを経由してアドレスを得る
+            result.push_str(&format!(
+                "  mov rax, QWORD PTR {}@GOTPCREL[rip]\n",
+                node.label
+            ));
+            result.push_str("  push rax\n");
+        }
+        NodeKind::Deref => {
+            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
+        }
+        // 複合リテラルは、初期化してから名前のない変数のアドレスを得る
+        NodeKind::Comma => {
+            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
+            result.push_str("  pop rax\n");
+            result.push_str(gen_lval(node.rhs.as_ref().unwrap()).as_str());
+        }
+        // 構造体・共用体を返す関数呼び出しは、戻り値を置いた変数のアドレスを積む
+        NodeKind::FuncCall => result.push_str(gen_funcall(node).as_str()),
+        NodeKind::Member => {
+            result.push_str(gen_lval(node.lhs.as_ref().unwrap()).as_str());
+            result.push_str("  pop rax\n");
+            result.push_str(&format!("  add rax, {}\n", node.offset));
+            result.push_str("  push rax\n");
+        }
+        _ => unreachable!("左辺値ではないノードです: {:?}", node.kind),
+    }
+    result
+}
+
+/// スタックトップのアドレスから型の大きさの値を読み出し、スタックトップと置き換える。
+/// 構造体・共用体・配列・関数はアドレスをそのまま値として扱うため、何もしない。
+/// 可変長配列を要素とする可変長配列の要素も、配列なのでアドレスを値とする。
+fn load(ty: &Type) -> String {
+    let mut result = String::new();
+    if ty.is_aggregate() || ty.is_array() || ty.is_vla() || ty.is_func() {
+        return result;
+    }
+
+    result.push_str("  pop rax\n");
+    match (ty.size, ty.is_unsigned) {
+        // floatのビット列はそのまま下位32ビットに読み込む
+        (4, _) if ty.is_flonum() => result.push_str("  mov eax, [rax]\n"),
+        (1, false) => result.push_str("  movsx rax, byte ptr [rax]\n"),
+        (1, true) => result.push_str("  movzx eax, byte ptr [rax]\n"),
+        (2, false) => result.push_str("  movsx rax, word ptr [rax]\n"),
+        (2, true) => result.push_str("  movzx eax, word ptr [rax]\n"),
+        (4, false) => result.push_str("  movsxd rax, dword ptr [rax]\n"),
+        // 32ビットレジスタへの書き込みで上位32ビットは0になる
+        (4, true) => result.push_str("  mov eax, [rax]\n"),
+        _ => result.push_str("  mov rax, [rax]\n"),
+    }
+    if ty.is_bitfield() {
+        // ビットフィールドを最上位のビットまで寄せてから戻し、符号付きの型なら符号拡張する
+        result.push_str(&format!(
+            "  shl rax, {}\n",
+            64 - ty.bit_width - ty.bit_offset
+        ));
+        result.push_str(&bitfield_extend("rax", ty));
+    }
+    result.push_str("  push rax\n");
+    result
+}
+
+/// 最上位のビットまで寄せたビットフィールドの値を、最下位のビットまで戻して64ビットに拡張する。
+fn bitfield_extend(reg: &str, ty: &Type) -> String {
+    let shift = if ty.is_unsigned { "shr" } else { "sar" };
+    format!("  {shift} {reg}, {}\n", 64 - ty.bit_width)
+}
+
+/// スタックトップの値を、その下にあるアドレスに型の大きさだけ書き込む。
+/// 書き込んだ値はスタックに残す。構造体・共用体の場合は値であるアドレスの指す先を1バイトずつコピーする。
+fn store(ty: &Type) -> String {
+    let mut result = String::new();
+    result.push_str("  pop rdi\n");
+    result.push_str("  pop rax\n");
+
+    if ty.is_bitfield() {
+        // 同じ領域にある他のビットフィールドを壊さないよう、読み出した値のビットを書き換えて書き戻す
+        let mask = (u64::MAX >> (64 - ty.bit_width)) as i64;
+        result.push_str("  mov r8, rdi\n");
+        result.push_str(&format!("  mov rdx, {mask}\n"));
+        result.push_str("  and rdi, rdx\n");
+        result.push_str(&format!("  shl rdi, {}\n", ty.bit_offset));
+        match ty.size {
+            1 => result.push_str("  movzx esi, byte ptr [rax]\n"),
+            2 => result.push_str("  movzx esi, word ptr [rax]\n"),
+            4 => result.push_str("  mov esi, [rax]\n"),
+            _ => result.push_str("  mov rsi, [rax]\n"),
+        }
+        result.push_str(&format!("  mov rdx, {}\n", !(mask << ty.bit_offset)));
+        result.push_str("  and rsi, rdx\n");
+        result.push_str("  or rdi, rsi\n");
+    }
+    if ty.is_aggregate() {
+        for i in 0..ty.size {
+            result.push_str(&format!("  mov r8b, [rdi+{i}]\n"));
+            result.push_str(&format!("  mov [rax+{i}], r8b\n"));
+        }
     } else {
-        panic!("代入の左辺値が変数ではありません");
+        match ty.size {
+            1 => result.push_str("  mov [rax], dil\n"),
+            2 => result.push_str("  mov [rax], di\n"),
+            4 => result.push_str("  mov [rax], edi\n"),
+            _ => result.push_str("  mov [rax], rdi\n"),
+        }
+    }
+    if ty.is_bitfield() {
+        // 代入式の値は、ビットフィールドの幅に切り詰めた値になる
+        result.push_str("  mov rdi, r8\n");
+        result.push_str(&format!("  shl rdi, {}\n", 64 - ty.bit_width));
+        result.push_str(&bitfield_extend("rdi", ty));
+    }
+    result.push_str("  push rdi\n");
+    result
+}
+
+/// raxの値をtyの大きさに切り詰め、64ビットに拡張する。
+/// 符号付きの型は符号拡張、符号なしの型はゼロ拡張する。8バイトの型と浮動小数点数型の場合は何もしない。
+fn extend(ty: &Type) -> String {
+    if ty.is_flonum() {
+        return String::new();
+    }
+    match (ty.size, ty.is_unsigned) {
+        (1, false) => "  movsx rax, al\n".to_string(),
+        (1, true) => "  movzx eax, al\n".to_string(),
+        (2, false) => "  movsx rax, ax\n".to_string(),
+        (2, true) => "  movzx eax, ax\n".to_string(),
+        (4, false) => "  movsxd rax, eax\n".to_string(),
+        (4, true) => "  mov eax, eax\n".to_string(),
+        _ => String::new(),
     }
 }
 
+/// 浮動小数点数型の命令の接尾辞。doubleはsd、floatはss。
+fn sse_suffix(ty: &Type) -> &'static str {
+    match ty.kind {
+        TypeKind::Float => "ss",
+        _ => "sd",
+    }
+}
+
+/// xmm0の浮動小数点数をビット列のままraxに移す。floatの場合は上位32ビットを0にする。
+fn from_xmm0(ty: &Type) -> &'static str {
+    match ty.kind {
+        TypeKind::Float => "  movd eax, xmm0\n",
+        _ => "  movq rax, xmm0\n",
+    }
+}
+
+/// raxの値をfrom型からto型に変換する。
+/// 浮動小数点数はビット列のままraxに置き、演算や変換のときだけxmmレジスタに移す。
+fn cast(from: &Type, to: &Type) -> String {
+    let mut result = String::new();
+    if to.kind == TypeKind::Bool {
+        // _Bool型に変換すると、0以外の値はすべて1になる。NaNも1になる。
+        result.push_str(cmp_zero(from).as_str());
+        result.push_str("  setne al\n");
+        if from.is_flonum() {
+            result.push_str("  setp dl\n");
+            result.push_str("  or al, dl\n");
+        }
+        result.push_str("  movzx eax, al\n");
+        return result;
+    }
+    match (from.is_flonum(), to.is_flonum()) {
+        (false, false) => result.push_str(extend(to).as_str()),
+        (false, true) => {
+            let s = sse_suffix(to);
+            if from.size == 8 && from.is_unsigned {
+                // 符号付きとして負になる値は、1ビット右シフトして変換してから2倍する。
+                // 最下位ビットは丸めのために残しておく。
+                result.push_str("  test rax, rax\n");
+                result.push_str("  js 1f\n");
+                result.push_str(&format!("  cvtsi2{s} xmm0, rax\n"));
+                result.push_str("  jmp 2f\n");
+                result.push_str("1:\n");
+                result.push_str("  mov rdi, rax\n");
+                result.push_str("  and eax, 1\n");
+                result.push_str("  shr rdi\n");
+                result.push_str("  or rdi, rax\n");
+                result.push_str(&format!("  cvtsi2{s} xmm0, rdi\n"));
+                result.push_str(&format!("  add{s} xmm0, xmm0\n"));
+                result.push_str("2:\n");
+            } else {
+                // 整数の値は64ビットに拡張されているので、そのまま変換できる
+                result.push_str(&format!("  cvtsi2{s} xmm0, rax\n"));
+            }
+            result.push_str(from_xmm0(to));
+        }
+        (true, false) => {
+            let s = sse_suffix(from);
+            result.push_str("  movq xmm0, rax\n");
+            if to.size == 8 && to.is_unsigned {
+                // 2^63以上の値は2^63を引いて変換してから、最上位ビットを立てる
+                if from.kind == TypeKind::Float {
+                    result.push_str("  mov eax, 0x5f000000\n");
+                } else {
+                    result.push_str("  mov rax, 0x43e0000000000000\n");
+                }
+                result.push_str("  movq xmm1, rax\n");
+                result.push_str(&format!("  ucomi{s} xmm0, xmm1\n"));
+                result.push_str("  jae 1f\n");
+                result.push_str(&format!("  cvtt{s}2si rax, xmm0\n"));
+                result.push_str("  jmp 2f\n");
+                result.push_str("1:\n");
+                result.push_str(&format!("  sub{s} xmm0, xmm1\n"));
+                result.push_str(&format!("  cvtt{s}2si rax, xmm0\n"));
+                result.push_str("  btc rax, 63\n");
+                result.push_str("2:\n");
+            } else {
+                result.push_str(&format!("  cvtt{s}2si rax, xmm0\n"));
+                result.push_str(extend(to).as_str());
+            }
+        }
+        (true, true) => {
+            if from.kind != to.kind {
+                let (f, t) = (sse_suffix(from), sse_suffix(to));
+                result.push_str("  movq xmm0, rax\n");
+                result.push_str(&format!("  cvt{f}2{t} xmm0, xmm0\n"));
+                result.push_str(from_xmm0(to));
+            }
+        }
+    }
+    result
+}
+
+/// raxの値が0と等しいかどうかでフラグを設定する。浮動小数点数の場合は-0.0も0とみなす。
+fn cmp_zero(ty: &Type) -> String {
+    let mut result = String::new();
+    if ty.is_flonum() {
+        result.push_str("  movq xmm0, rax\n");
+        result.push_str("  xorpd xmm1, xmm1\n");
+        result.push_str(&format!("  ucomi{} xmm0, xmm1\n", sse_suffix(ty)));
+    } else {
+        result.push_str("  cmp rax, 0\n");
+    }
+    result
+}
+
+/// 浮動小数点数の二項演算を行う。左辺の値はrax、右辺の値はrdiにビット列のまま入っている。
+fn gen_flonum_op(node: &Node, ty: &Type) -> String {
+    let mut result = String::new();
+    let s = sse_suffix(ty);
+    result.push_str("  movq xmm0, rax\n");
+    result.push_str("  movq xmm1, rdi\n");
+    match node.kind {
+        NodeKind::Add | NodeKind::Sub | NodeKind::Mul | NodeKind::Div => {
+            let op = match node.kind {
+                NodeKind::Add => "add",
+                NodeKind::Sub => "sub",
+                NodeKind::Mul => "mul",
+                _ => "div",
+            };
+            result.push_str(&format!("  {op}{s} xmm0, xmm1\n"));
+            result.push_str(from_xmm0(ty));
+        }
+        // 比較できない場合(NaN)はパリティフラグが立つので、それも考慮する
+        NodeKind::Eq => {
+            result.push_str(&format!("  ucomi{s} xmm0, xmm1\n"));
+            result.push_str("  sete al\n");
+            result.push_str("  setnp dl\n");
+            result.push_str("  and al, dl\n");
+            result.push_str("  movzb rax, al\n");
+        }
+        NodeKind::Ne => {
+            result.push_str(&format!("  ucomi{s} xmm0, xmm1\n"));
+            result.push_str("  setne al\n");
+            result.push_str("  setp dl\n");
+            result.push_str("  or al, dl\n");
+            result.push_str("  movzb rax, al\n");
+        }
+        // a < b を b > a として比較すると、NaNの場合に偽になる
+        NodeKind::Lt => {
+            result.push_str(&format!("  ucomi{s} xmm1, xmm0\n"));
+            result.push_str("  seta al\n");
+            result.push_str("  movzb rax, al\n");
+        }
+        NodeKind::Le => {
+            result.push_str(&format!("  ucomi{s} xmm1, xmm0\n"));
+            result.push_str("  setae al\n");
+            result.push_str("  movzb rax, al\n");
+        }
+        _ => panic!("浮動小数点数に使えない演算子です"),
+    }
+    result.push_str("  push rax\n");
+    result
+}
+
+/// 引数を渡す整数レジスタと、その下位8ビットの名前
+const ARG_REGS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+const ARG_REGS8: [&str; 6] = ["dil", "sil", "dl", "cl", "r8b", "r9b"];
+
+/// 引数を渡すxmmレジスタの個数
+const FP_ARG_REGS: usize = 8;
+
+/// 構造体・共用体の戻り値を返す整数レジスタと、その下位8ビットの名前
+const RET_REGS: [&str; 2] = ["rax", "rdx"];
+const RET_REGS8: [&str; 2] = ["al", "dl"];
+
+/// 引数を渡す場所
+enum ArgPlace {
+    Gp(usize),                // 整数レジスタ。番号はARG_REGSの位置
+    Fp(usize),                // xmmレジスタ
+    Regs(Vec<(bool, usize)>), // 構造体・共用体を8バイトずつ、xmmレジスタ(真)か整数レジスタ(偽)で渡す
+    Stack(i64),               // スタック。rspからのオフセット
+}
+
+/// System V ABIに従い、構造体・共用体を8バイトずつに区切った各部分をxmmレジスタで渡すかどうかを返す。
+/// 浮動小数点数のメンバだけからなる部分はxmmレジスタで、それ以外は整数レジスタで渡す。
+/// 16バイトより大きいものはメモリで渡すため、Noneを返す。
+fn classify(ty: &Type) -> Option<Vec<bool>> {
+    if ty.size > 16 {
+        return None;
+    }
+    let eightbytes = (ty.size + 7) / 8;
+    Some(
+        (0..eightbytes)
+            .map(|i| has_only_flonum(ty, i * 8, i * 8 + 8, 0))
+            .collect(),
+    )
+}
+
+/// offsetの位置にあるty型の値のうち、lo以上hi未満の範囲にある部分が浮動小数点数だけからなるかどうか
+fn has_only_flonum(ty: &Type, lo: i64, hi: i64, offset: i64) -> bool {
+    if ty.is_aggregate() {
+        return ty
+            .members
+            .iter()
+            .all(|member| has_only_flonum(&member.ty, lo, hi, offset + member.offset));
+    }
+    if ty.is_array() {
+        let base = ty.base.as_ref().unwrap();
+        return (0..ty.array_len).all(|i| has_only_flonum(base, lo, hi, offset + base.size * i));
+    }
+    offset < lo || hi <= offset || ty.is_flonum()
+}
+
+/// r11が指す構造体・共用体の、offsetから始まるsizeバイトの部分をreg番目のレジスタに読み込む。
+/// 整数レジスタは、regsとその下位8ビットの名前のregs8から選ぶ。
+fn load_eightbyte(
+    (sse, reg): (bool, usize),
+    (regs, regs8): (&[&str], &[&str]),
+    offset: i64,
+    size: i64,
+) -> String {
+    let mut result = String::new();
+    if sse {
+        let op = if size == 4 { "movss" } else { "movsd" };
+        result.push_str(&format!("  {op} xmm{reg}, [r11+{offset}]\n"));
+        return result;
+    }
+    if size == 8 {
+        result.push_str(&format!("  mov {}, [r11+{offset}]\n", regs[reg]));
+        return result;
+    }
+    // 構造体の範囲外を読まないよう、上位のバイトから1バイトずつ読み込む
+    result.push_str(&format!("  mov {}, 0\n", regs[reg]));
+    for i in (0..size).rev() {
+        result.push_str(&format!("  shl {}, 8\n", regs[reg]));
+        result.push_str(&format!("  mov {}, [r11+{}]\n", regs8[reg], offset + i));
+    }
+    result
+}
+
+/// reg番目のレジスタの値を、r11が指す構造体・共用体のoffsetから始まるsizeバイトの部分に書き込む。
+/// 整数レジスタは、regsとその下位8ビットの名前のregs8から選ぶ。
+fn store_eightbyte(
+    (sse, reg): (bool, usize),
+    (regs, regs8): (&[&str], &[&str]),
+    offset: i64,
+    size: i64,
+) -> String {
+    let mut result = String::new();
+    if sse {
+        let op = if size == 4 { "movss" } else { "movsd" };
+        result.push_str(&format!("  {op} [r11+{offset}], xmm{reg}\n"));
+        return result;
+    }
+    let (reg, reg8) = (regs[reg], regs8[reg]);
+    if size == 8 {
+        result.push_str(&format!("  mov [r11+{offset}], {reg}\n"));
+        return result;
+    }
+    for i in 0..size {
+        result.push_str(&format!("  mov [r11+{}], {reg8}\n", offset + i));
+        result.push_str(&format!("  shr {reg}, 8\n"));
+    }
+    result
+}
+
+/// レジスタで返す構造体・共用体の戻り値の、8バイトずつの各部分を返すレジスタ。
+/// 整数レジスタはrax, rdxの順に、xmmレジスタはxmm0, xmm1の順に使う。
+/// メモリで返す場合は空になる。
+fn ret_regs(classes: Option<Vec<bool>>) -> Vec<(bool, usize)> {
+    let (mut gp, mut fp) = (0, 0);
+    classes
+        .unwrap_or_default()
+        .into_iter()
+        .map(|sse| {
+            let next = if sse { &mut fp } else { &mut gp };
+            *next += 1;
+            (sse, *next - 1)
+        })
+        .collect()
+}
+
+/// System V ABIに従い、型の並びの各引数を渡す場所を決める。gpは最初に使う整数レジスタの番号。
+/// 各引数の場所と、使った整数レジスタとxmmレジスタの数、スタックで渡す引数の領域の大きさを返す。
+/// 関数を呼び出す側と、呼び出される関数の先頭で引数を受け取る側の両方で使う。
+fn arg_places<'a>(
+    tys: impl Iterator<Item = &'a Type>,
+    mut gp: usize,
+) -> (Vec<ArgPlace>, usize, usize, i64) {
+    let mut fp = 0;
+    let mut stack_size = 0;
+    let mut places = Vec::new();
+    for ty in tys {
+        let place = match classify(ty) {
+            _ if !ty.is_aggregate() && ty.is_flonum() && fp < FP_ARG_REGS => {
+                fp += 1;
+                ArgPlace::Fp(fp - 1)
+            }
+            _ if !ty.is_aggregate() && !ty.is_flonum() && gp < ARG_REGS.len() => {
+                gp += 1;
+                ArgPlace::Gp(gp - 1)
+            }
+            Some(classes)
+                if ty.is_aggregate()
+                    && gp + classes.iter().filter(|sse| !**sse).count() <= ARG_REGS.len()
+                    && fp + classes.iter().filter(|sse| **sse).count() <= FP_ARG_REGS =>
+            {
+                let regs = classes
+                    .into_iter()
+                    .map(|sse| {
+                        let next = if sse { &mut fp } else { &mut gp };
+                        *next += 1;
+                        (sse, *next - 1)
+                    })
+                    .collect();
+                ArgPlace::Regs(regs)
+            }
+            _ => {
+                let offset = align_to(stack_size, ty.align.max(8));
+                stack_size = offset + align_to(ty.size, 8);
+                ArgPlace::Stack(offset)
+            }
+        };
+        places.push(place);
+    }
+    (places, gp, fp, stack_size)
+}
+
+/// 関数を呼び出し、戻り値をスタックに積む。引数と戻り値はSystem V ABIに従って受け渡す。
+/// 整数の引数は整数レジスタに、浮動小数点数の引数はxmmレジスタに順に割り当て、足りなくなったらスタックで渡す。
+/// 16バイト以下の構造体・共用体は8バイトずつレジスタで、それより大きいものはスタックにコピーして渡す。
+/// 構造体・共用体の戻り値は、意味解析で割り当てた変数に置き、そのアドレスを積む。
+fn gen_funcall(node: &Node) -> String {
+    let mut result = String::new();
+    result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
+    // 構造体・共用体の引数は、そのアドレスを積む
+    for arg in node.args.iter() {
+        result.push_str(gen(arg).as_str());
+    }
+
+    // メモリで返す戻り値は、それを置くアドレスを隠れた最初の引数として渡す
+    let ret_ty = node.ty.as_ref().unwrap();
+    let ret_classes = classify(ret_ty).filter(|_| ret_ty.is_aggregate());
+    let ret_in_memory = ret_ty.is_aggregate() && ret_classes.is_none();
+    let arg_tys = node.args.iter().map(|arg| arg.ty.as_ref().unwrap());
+    let (places, _, fp, stack_size) = arg_places(arg_tys, ret_in_memory as usize);
+
+    // スタックに積んだ値を指すraxを、スタックで渡す引数の領域の後ろに保存してから、rspを16の倍数に揃える。
+    // n番目の引数の値は[rax+8*(引数の数-1-n)]に、呼び出す関数のアドレスはその上にある。
+    let nargs = node.args.len() as i64;
+    result.push_str("  mov rax, rsp\n");
+    result.push_str(&format!("  sub rsp, {}\n", stack_size + 8));
+    result.push_str("  and rsp, -16\n");
+    result.push_str(&format!("  mov [rsp+{stack_size}], rax\n"));
+    for (i, (arg, place)) in node.args.iter().zip(&places).enumerate() {
+        let ArgPlace::Stack(offset) = place else {
+            continue;
+        };
+        let ty = arg.ty.as_ref().unwrap();
+        result.push_str(&format!(
+            "  mov rdx, [rax+{}]\n",
+            8 * (nargs - 1 - i as i64)
+        ));
+        if ty.is_aggregate() {
+            for j in 0..ty.size {
+                result.push_str(&format!("  mov r11b, [rdx+{j}]\n"));
+                result.push_str(&format!("  mov [rsp+{}], r11b\n", offset + j));
+            }
+        } else {
+            result.push_str(&format!("  mov [rsp+{offset}], rdx\n"));
+        }
+    }
+    for (i, (arg, place)) in node.args.iter().zip(&places).enumerate() {
+        let slot = 8 * (nargs - 1 - i as i64);
+        match place {
+            ArgPlace::Gp(reg) => {
+                result.push_str(&format!("  mov {}, [rax+{slot}]\n", ARG_REGS[*reg]))
+            }
+            ArgPlace::Fp(reg) => result.push_str(&format!("  movq xmm{reg}, [rax+{slot}]\n")),
+            ArgPlace::Regs(regs) => {
+                let size = arg.ty.as_ref().unwrap().size;
+                result.push_str(&format!("  mov r11, [rax+{slot}]\n"));
+                for (j, place) in regs.iter().enumerate() {
+                    let offset = 8 * j as i64;
+                    result.push_str(&load_eightbyte(
+                        *place,
+                        (&ARG_REGS, &ARG_REGS8),
+                        offset,
+                        (size - offset).min(8),
+                    ));
+                }
+            }
+            ArgPlace::Stack(_) => {}
+        }
+    }
+    if ret_in_memory {
+        result.push_str(&format!("  lea rdi, [rbp-{}]\n", node.offset));
+    }
+    result.push_str(&format!("  mov r10, [rax+{}]\n", 8 * nargs));
+
+    // 可変長引数の関数のために、alにはxmmレジスタで渡す引数の数を入れる
+    result.push_str(&format!("  mov eax, {fp}\n"));
+    result.push_str("  call r10\n");
+    result.push_str(&format!("  mov rsp, [rsp+{stack_size}]\n"));
+    result.push_str(&format!("  add rsp, {}\n", 8 * (nargs + 1)));
+
+    if ret_ty.is_aggregate() {
+        result.push_str(&format!("  lea r11, [rbp-{}]\n", node.offset));
+        for (j, place) in ret_regs(ret_classes).into_iter().enumerate() {
+            let offset = 8 * j as i64;
+            result.push_str(&store_eightbyte(
+                place,
+                (&RET_REGS, &RET_REGS8),
+                offset,
+                (ret_ty.size - offset).min(8),
+            ));
+        }
+        result.push_str("  push r11\n");
+        return result;
+    }
+    // 戻り値の上位ビットは不定なので、型に合わせて拡張しておく
+    match ret_ty.kind {
+        TypeKind::Float | TypeKind::Double => result.push_str(from_xmm0(ret_ty)),
+        _ => result.push_str(extend(ret_ty).as_str()),
+    }
+    result.push_str("  push rax\n");
+    result
+}
+
+/// 関数の先頭で、レジスタやスタックで渡された引数を、varsの先頭に並んだ引数の変数に書き込む。
+/// 可変長引数の関数では、先にva_listの初期値と、引数を渡すレジスタの値を保存する領域に書き込む。
+pub fn gen_params(func: &Function, vars: &[LVar]) -> String {
+    let mut result = String::new();
+    // メモリで返す戻り値を書き込む先のアドレスは、隠れた最初の引数としてrdiで渡される
+    let ret_ty = func.ty.return_ty.as_ref().unwrap();
+    let ret_in_memory = ret_ty.is_aggregate() && classify(ret_ty).is_none();
+    if let (true, Some(offset)) = (ret_in_memory, func.ret_ptr) {
+        result.push_str(&format!("  mov [rbp-{offset}], rdi\n"));
+    }
+    let (places, gp, fp, stack_size) = arg_places(func.ty.params.iter(), ret_in_memory as usize);
+    if let Some(offset) = func.va_area {
+        result.push_str(&format!("  lea r11, [rbp-{offset}]\n"));
+        for (i, reg) in ARG_REGS.iter().enumerate() {
+            result.push_str(&format!("  mov [r11+{}], {reg}\n", 24 + 8 * i));
+        }
+        for i in 0..FP_ARG_REGS {
+            result.push_str(&format!("  movsd [r11+{}], xmm{i}\n", 72 + 16 * i));
+        }
+        // va_listの初期値は、名前のある引数の後の引数を指す。
+        // スタックで渡された引数は、[rbp]にある呼び出し元のrbpの保存位置から、戻り番地を挟んだ先にある
+        result.push_str(&format!("  mov DWORD PTR [r11], {}\n", 8 * gp));
+        result.push_str(&format!("  mov DWORD PTR [r11+4], {}\n", 48 + 16 * fp));
+        result.push_str("  mov rax, [rbp]\n");
+        result.push_str(&format!("  add rax, {}\n", 16 + stack_size));
+        result.push_str("  mov [r11+8], rax\n");
+        result.push_str("  lea rax, [r11+24]\n");
+        result.push_str("  mov [r11+16], rax\n");
+    }
+    for (var, place) in vars.iter().zip(places) {
+        let ty = var.ty.as_ref().unwrap();
+        match place {
+            ArgPlace::Gp(reg) => result.push_str(&format!("  mov rax, {}\n", ARG_REGS[reg])),
+            ArgPlace::Fp(reg) => {
+                let s = sse_suffix(ty);
+                result.push_str(&format!("  mov{s} [rbp-{}], xmm{reg}\n", var.offset));
+                continue;
+            }
+            ArgPlace::Stack(offset) if ty.is_aggregate() => {
+                result.push_str("  mov rax, [rbp]\n");
+                for j in 0..ty.size {
+                    result.push_str(&format!("  mov r11b, [rax+{}]\n", 16 + offset + j));
+                    result.push_str(&format!("  mov [rbp-{}], r11b\n", var.offset - j));
+                }
+                continue;
+            }
+            ArgPlace::Stack(offset) => {
+                result.push_str("  mov rax, [rbp]\n");
+                result.push_str(&format!("  mov rax, [rax+{}]\n", 16 + offset));
+            }
+            ArgPlace::Regs(regs) => {
+                result.push_str(&format!("  lea r11, [rbp-{}]\n", var.offset));
+                for (j, place) in regs.into_iter().enumerate() {
+                    let offset = 8 * j as i64;
+                    result.push_str(&store_eightbyte(
+                        place,
+                        (&ARG_REGS, &ARG_REGS8),
+                        offset,
+                        (ty.size - offset).min(8),
+                    ));
+                }
+                continue;
+            }
+        }
+        let reg = match ty.size {
+            1 => "al",
+            2 => "ax",
+            4 => "eax",
+            _ => "rax",
+        };
+        result.push_str(&format!("  mov [rbp-{}], {reg}\n", var.offset));
+    }
+    result
+}
+
+/// ポインタ(配列)の指す先の型の大きさを表すオペランド。ポインタでも配列でもなければNone。
+/// 指す先が可変長配列の場合は、実行時に大きさを格納した変数を参照する。
+fn pointee_size(node: &Node) -> Option<String> {
+    match node.ty.as_ref() {
+        Some(Type {
+            kind: TypeKind::Ptr | TypeKind::Array | TypeKind::Vla,
+            base: Some(base),
+            ..
+        }) if base.is_vla() => Some(format!("QWORD PTR [rbp-{}]", base.vla_size)),
+        Some(Type {
+            kind: TypeKind::Ptr | TypeKind::Array | TypeKind::Vla,
+            base: Some(base),
+            ..
+        }) => Some(base.size.to_string()),
+        _ => None,
+    }
+}
+
+/// 静的変数の領域をデータ領域に確保する。extern宣言された変数は他で定義されるため、何もしない。
+/// 外部から参照されないように、ラベルは.globlにしない。
+pub fn gen_data(gvars: &[GVar]) -> String {
+    let mut result = String::new();
+    for gvar in gvars {
+        let Some(data) = &gvar.data else {
+            continue;
+        };
+        result.push_str(".data\n");
+        result.push_str(&format!(".align {}\n", gvar.ty.align));
+        result.push_str(&format!("{}:\n", gvar.label));
+        if gvar.relocs.is_empty() && data.iter().all(|&b| b == 0) {
+            result.push_str(&format!("  .zero {}\n", data.len()));
+            continue;
+        }
+        // 他の変数のアドレスを書き込む位置では、アセンブラにアドレスを計算させる
+        let mut pos = 0;
+        while pos < data.len() {
+            match gvar.relocs.iter().find(|r| r.offset as usize == pos) {
+                Some(reloc) => {
+                    result.push_str(&format!("  .quad {}{:+}\n", reloc.label, reloc.addend));
+                    pos += 8;
+                }
+                None => {
+                    result.push_str(&format!("  .byte {}\n", data[pos]));
+                    pos += 1;
+                }
+            }
+        }
+    }
+    result
+}
+
 pub fn gen(node: &Node) -> String {
     let mut result = String::new();
     match node.kind {
         NodeKind::Return => {
-            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
-            result.push_str("  pop rax\n");
-            result.push_str("  mov rsp, rbp\n");
+            if let Some(lhs) = node.lhs.as_ref() {
+                result.push_str(gen(lhs).as_str());
+                let ty = lhs.ty.as_ref().unwrap();
+                match classify(ty) {
+                    // 16バイト以下の構造体・共用体は、8バイトずつレジスタで返す
+                    Some(classes) if ty.is_aggregate() => {
+                        result.push_str("  pop r11\n");
+                        for (j, place) in ret_regs(Some(classes)).into_iter().enumerate() {
+                            let offset = 8 * j as i64;
+                            result.push_str(&load_eightbyte(
+                                place,
+                                (&RET_REGS, &RET_REGS8),
+                                offset,
+                                (ty.size - offset).min(8),
+                            ));
+                        }
+                    }
+                    // それより大きいものは、呼び出し元から渡されたアドレスにコピーし、そのアドレスをraxで返す
+                    None => {
+                        result.push_str("  pop rdx\n");
+                        result.push_str(&format!("  mov rax, [rbp-{}]\n", node.offset));
+                        for j in 0..ty.size {
+                            result.push_str(&format!("  mov r11b, [rdx+{j}]\n"));
+                            result.push_str(&format!("  mov [rax+{j}], r11b\n"));
+                        }
+                    }
+                    _ => {
+                        result.push_str("  pop rax\n");
+                        // 浮動小数点数の戻り値はxmm0で返す
+                        if ty.is_flonum() {
+                            result.push_str("  movq xmm0, rax\n");
+                        }
+                    }
+                }
+            }
+            result.push_str("  mov rsp, [rbp]\n");
             result.push_str("  pop rbp\n");
             result.push_str("  ret\n");
             return result;
         }
         NodeKind::Num => {
-            result.push_str(&format!("  push {}\n", node.val));
+            // 浮動小数点数はビット列を整数としてスタックに積む
+            match node.ty.as_ref().map(|ty| ty.kind) {
+                Some(TypeKind::Float) => {
+                    let bits = (node.fval as f32).to_bits();
+                    result.push_str(&format!("  mov eax, {bits}\n"));
+                    result.push_str("  push rax\n");
+                }
+                Some(TypeKind::Double) => {
+                    result.push_str(&format!("  mov rax, {}\n", node.fval.to_bits()));
+                    result.push_str("  push rax\n");
+                }
+                // pushの即値は32ビットまでなので、それを超える値はraxを経由する
+                _ if i32::try_from(node.val).is_ok() => {
+                    result.push_str(&format!("  push {}\n", node.val));
+                }
+                _ => {
+                    result.push_str(&format!("  mov rax, {}\n", node.val));
+                    result.push_str("  push rax\n");
+                }
+            }
             return result;
         }
-        NodeKind::LVar => {
+        NodeKind::LVar | NodeKind::GVar | NodeKind::Member => {
             result.push_str(gen_lval(node).as_str());
+            result.push_str(load(node.ty.as_ref().unwrap()).as_str());
+            return result;
+        }
+        NodeKind::Deref => {
+            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
+            result.push_str(load(node.ty.as_ref().unwrap()).as_str());
+            return result;
+        }
+        NodeKind::Addr => {
+            result.push_str(gen_lval(node.lhs.as_ref().unwrap()).as_str());
+            return result;
+        }
+        NodeKind::Cast => {
+            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
             result.push_str("  pop rax\n");
-            result.push_str("  mov rax, [rax]\n");
+            let from = node.lhs.as_ref().unwrap().ty.as_ref().unwrap();
+            result.push_str(cast(from, node.ty.as_ref().unwrap()).as_str());
             result.push_str("  push rax\n");
             return result;
         }
         NodeKind::Assign => {
             result.push_str(gen_lval(node.lhs.as_ref().unwrap()).as_str());
             result.push_str(gen(node.rhs.as_ref().unwrap()).as_str());
-            result.push_str("  pop rdi\n");
+            result.push_str(store(node.ty.as_ref().unwrap()).as_str());
+            return result;
+        }
+        NodeKind::FuncCall => return gen_funcall(node),
+        NodeKind::Not => {
+            let lhs = node.lhs.as_ref().unwrap();
+            result.push_str(gen(lhs).as_str());
             result.push_str("  pop rax\n");
-            result.push_str("  mov [rax], rdi\n");
-            result.push_str("  push rdi\n");
+            result.push_str(cmp_zero(lhs.ty.as_ref().unwrap()).as_str());
+            result.push_str("  sete al\n");
+            result.push_str("  movzx eax, al\n");
+            result.push_str("  push rax\n");
+            return result;
+        }
+        NodeKind::LogAnd | NodeKind::LogOr => {
+            // 左辺だけで結果が決まる場合は右辺を評価しない
+            let (jump, short_circuit) = match node.kind {
+                NodeKind::LogAnd => ("je", 0),
+                _ => ("jne", 1),
+            };
+            for side in [&node.lhs, &node.rhs] {
+                let side = side.as_ref().unwrap();
+                result.push_str(gen(side).as_str());
+                result.push_str("  pop rax\n");
+                result.push_str(cmp_zero(side.ty.as_ref().unwrap()).as_str());
+                result.push_str(&format!("  {jump} .Lshort{}\n", node.val));
+            }
+            result.push_str(&format!("  push {}\n", 1 - short_circuit));
+            result.push_str(&format!("  jmp .Lend{}\n", node.val));
+            result.push_str(&format!(".Lshort{}:\n", node.val));
+            result.push_str(&format!("  push {short_circuit}\n"));
+            result.push_str(&format!(".Lend{}:\n", node.val));
+            return result;
+        }
+        NodeKind::Cond => {
+            let cond = node.cond.as_ref().unwrap();
+            result.push_str(gen(cond).as_str());
+            result.push_str("  pop rax\n");
+            result.push_str(cmp_zero(cond.ty.as_ref().unwrap()).as_str());
+            result.push_str(&format!("  je .Lelse{}\n", node.val));
+            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
+            result.push_str(&format!("  jmp .Lend{}\n", node.val));
+            result.push_str(&format!(".Lelse{}:\n", node.val));
+            result.push_str(gen(node.rhs.as_ref().unwrap()).as_str());
+            result.push_str(&format!(".Lend{}:\n", node.val));
+            return result;
+        }
+        NodeKind::Comma => {
+            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
+            result.push_str("  pop rax\n");
+            result.push_str(gen(node.rhs.as_ref().unwrap()).as_str());
+            return result;
+        }
+        NodeKind::MemZero => {
+            result.push_str(&format!("  lea rdi, [rbp-{}]\n", node.offset));
+            result.push_str(&format!("  mov rcx, {}\n", node.ty.as_ref().unwrap().size));
+            result.push_str("  mov al, 0\n");
+            result.push_str("  rep stosb\n");
+            result.push_str("  push 0\n");
+            return result;
+        }
+        NodeKind::Alloca => {
+            // スタックを伸ばして領域を確保する。スタックに積まれている計算途中の値は、
+            // 確保した領域の下へ移す。rbpからnode.offsetの位置には、これまでに確保した領域の底がある。
+            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
+            result.push_str("  pop rdx\n");
+            result.push_str("  add rdx, 15\n");
+            result.push_str("  and rdx, -16\n");
+            result.push_str(&format!("  mov rcx, [rbp-{}]\n", node.offset));
+            result.push_str("  sub rcx, rsp\n");
+            result.push_str("  mov rsi, rsp\n");
+            result.push_str("  sub rsp, rdx\n");
+            result.push_str("  mov rdi, rsp\n");
+            result.push_str("  rep movsb\n");
+            result.push_str(&format!("  mov rax, [rbp-{}]\n", node.offset));
+            result.push_str("  sub rax, rdx\n");
+            result.push_str(&format!("  mov [rbp-{}], rax\n", node.offset));
+            result.push_str("  push rax\n");
+            return result;
+        }
+        NodeKind::Nil => {
+            // 空の文も他の文と同様に、値を1つだけスタックに積んでおく
+            result.push_str("  push 0\n");
+            return result;
+        }
+        NodeKind::VaStart => {
+            // 関数の先頭で保存したva_listの初期値を、引数のva_listにコピーする
+            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
+            result.push_str("  pop rax\n");
+            result.push_str(&format!("  lea r11, [rbp-{}]\n", node.offset));
+            for i in (0..24).step_by(8) {
+                result.push_str(&format!("  mov rdx, [r11+{i}]\n"));
+                result.push_str(&format!("  mov [rax+{i}], rdx\n"));
+            }
+            result.push_str("  push rax\n");
+            return result;
+        }
+        NodeKind::VaArg => {
+            // 整数はgp_offset、浮動小数点数はfp_offsetが指すレジスタ保存領域の位置から取り出す。
+            // レジスタで渡された分を使い切っていれば、overflow_arg_areaが指すスタック上の位置から取り出す。
+            // どちらも取り出した後は、次の引数を指すように進める
+            let ty = node.ty.as_ref().unwrap();
+            let (field, end, step) = if ty.is_flonum() {
+                (4, 48 + 16 * FP_ARG_REGS, 16)
+            } else {
+                (0, 8 * ARG_REGS.len(), 8)
+            };
+            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
+            result.push_str("  pop rax\n");
+            result.push_str(&format!("  mov ecx, [rax+{field}]\n"));
+            result.push_str(&format!("  cmp ecx, {end}\n"));
+            result.push_str("  jae 1f\n");
+            result.push_str("  mov rdx, [rax+16]\n");
+            result.push_str("  add rdx, rcx\n");
+            result.push_str(&format!("  add ecx, {step}\n"));
+            result.push_str(&format!("  mov [rax+{field}], ecx\n"));
+            result.push_str("  jmp 2f\n");
+            result.push_str("1:\n");
+            result.push_str("  mov rdx, [rax+8]\n");
+            result.push_str("  lea rcx, [rdx+8]\n");
+            result.push_str("  mov [rax+8], rcx\n");
+            result.push_str("2:\n");
+            result.push_str("  push rdx\n");
+            result.push_str(load(ty).as_str());
+            return result;
+        }
+        NodeKind::DoWhile => {
+            // continue文は本体の先頭ではなく条件式の評価に飛ぶ
+            result.push_str(&format!(".Lbegin{}:\n", node.val));
+            result.push_str(gen(node.lhs.as_ref().unwrap()).as_str());
+            result.push_str("  pop rax\n");
+            result.push_str(&format!(".Lcontinue{}:\n", node.val));
+            let cond = node.rhs.as_ref().unwrap();
+            result.push_str(gen(cond).as_str());
+            result.push_str("  pop rax\n");
+            result.push_str(cmp_zero(cond.ty.as_ref().unwrap()).as_str());
+            result.push_str(&format!("  jne .Lbegin{}\n", node.val));
+            // 他の文と同様に、文の値として1つだけスタックに積んでおく
+            result.push_str("  push rax\n");
+            return result;
+        }
+        NodeKind::Continue => {
+            result.push_str(&format!("  jmp .Lcontinue{}\n", node.val));
             return result;
         }
         _ => {}
@@ -70,13 +953,61 @@ pub fn gen(node: &Node) -> String {
     result.push_str("  pop rdi\n");
     result.push_str("  pop rax\n");
 
+    let lhs = node.lhs.as_ref().unwrap();
+    let rhs = node.rhs.as_ref().unwrap();
+    // 通常の算術型変換により、浮動小数点数の演算では両辺が同じ型になっている
+    if let Some(ty) = lhs.ty.as_ref().filter(|ty| ty.is_flonum()) {
+        result.push_str(gen_flonum_op(node, ty).as_str());
+        return result;
+    }
+    // 比較と割り算は、左辺の型が符号なしかどうかで命令を使い分ける
+    let is_unsigned = lhs.ty.as_ref().unwrap().is_unsigned;
     match node.kind {
-        NodeKind::Add => result.push_str("  add rax, rdi\n"),
-        NodeKind::Sub => result.push_str("  sub rax, rdi\n"),
+        NodeKind::Add => {
+            // ポインタに整数を足すときは、整数にポインタの指す先の型の大きさを掛ける
+            if let Some(size) = pointee_size(lhs) {
+                result.push_str(&format!("  imul rdi, {size}\n"));
+            } else if let Some(size) = pointee_size(rhs) {
+                result.push_str(&format!("  imul rax, {size}\n"));
+            }
+            result.push_str("  add rax, rdi\n");
+        }
+        NodeKind::Sub => match (pointee_size(lhs), pointee_size(rhs)) {
+            (Some(size), Some(_)) => {
+                // ポインタ同士の引き算は要素数の差になる
+                result.push_str("  sub rax, rdi\n");
+                result.push_str("  cqo\n");
+                result.push_str(&format!("  mov rdi, {size}\n"));
+                result.push_str("  idiv rdi\n");
+            }
+            (Some(size), None) => {
+                result.push_str(&format!("  imul rdi, {size}\n"));
+                result.push_str("  sub rax, rdi\n");
+            }
+            _ => result.push_str("  sub rax, rdi\n"),
+        },
         NodeKind::Mul => result.push_str("  imul rax, rdi\n"),
         NodeKind::Div => {
-            result.push_str("  cqo\n");
-            result.push_str("  idiv rdi\n");
+            if is_unsigned {
+                result.push_str("  mov rdx, 0\n");
+                result.push_str("  div rdi\n");
+            } else {
+                result.push_str("  cqo\n");
+                result.push_str("  idiv rdi\n");
+            }
+        }
+        NodeKind::Shl => {
+            result.push_str("  mov rcx, rdi\n");
+            result.push_str("  shl rax, cl\n");
+        }
+        NodeKind::Shr => {
+            // 符号なしの値は論理シフト、符号付きの値は算術シフト
+            result.push_str("  mov rcx, rdi\n");
+            if is_unsigned {
+                result.push_str("  shr rax, cl\n");
+            } else {
+                result.push_str("  sar rax, cl\n");
+            }
         }
         NodeKind::Eq => {
             result.push_str("  cmp rax, rdi\n");
@@ -90,17 +1021,31 @@ pub fn gen(node: &Node) -> String {
         }
         NodeKind::Lt => {
             result.push_str("  cmp rax, rdi\n");
-            result.push_str("  setl al\n");
+            if is_unsigned {
+                result.push_str("  setb al\n");
+            } else {
+                result.push_str("  setl al\n");
+            }
             result.push_str("  movzb rax, al\n");
         }
         NodeKind::Le => {
             result.push_str("  cmp rax, rdi\n");
-            result.push_str("  setle al\n");
+            if is_unsigned {
+                result.push_str("  setbe al\n");
+            } else {
+                result.push_str("  setle al\n");
+            }
             result.push_str("  movzb rax, al\n");
         }
         _ => {}
     };
 
+    // 整数の演算結果を結果の型の大きさに切り詰める
+    if let NodeKind::Add | NodeKind::Sub | NodeKind::Mul | NodeKind::Div | NodeKind::Shl = node.kind
+    {
+        result.push_str(extend(node.ty.as_ref().unwrap()).as_str());
+    }
+
     result.push_str("  push rax\n");
     result
 }
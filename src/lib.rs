@@ -1,40 +1,136 @@
 mod codegen;
 mod parse;
+mod preprocess;
 mod result;
+mod sema;
 mod tokenize;
+mod types;
 
-use codegen::gen;
-use parse::program;
+use codegen::{gen, gen_data, gen_params};
+use parse::{program, LVars};
+use preprocess::{format_tokens, preprocess};
+use sema::analyze;
 use tokenize::tokenize;
+use types::align_to;
+
+/// コマンドライン引数。ソースコードはファイルではなく、引数の文字列として与える。
+struct Args {
+    input: String,              // ソースコード
+    include_paths: Vec<String>, // -Iで指定されたディレクトリ
+    preprocess_only: bool,      // -Eが指定された場合に真
+}
+
+/// コマンドライン引数を解釈する。-Iの後のディレクトリは続けて書いても、次の引数に書いてもよい。
+/// -Eを指定すると、前処理だけを行ってその結果を出力する。
+fn parse_args(args: &[String]) -> Option<Args> {
+    let mut input = None;
+    let mut include_paths = Vec::new();
+    let mut preprocess_only = false;
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-E" {
+            preprocess_only = true;
+        } else if arg == "-I" {
+            include_paths.push(args.next()?.clone());
+        } else if let Some(dir) = arg.strip_prefix("-I") {
+            include_paths.push(dir.to_string());
+        } else if input.is_none() {
+            input = Some(arg.clone());
+        } else {
+            return None;
+        }
+    }
+    Some(Args {
+        input: input?,
+        include_paths,
+        preprocess_only,
+    })
+}
 
 pub fn cli(args: Vec<String>) -> String {
-    if args.len() != 2 {
-        eprintln!("引数の個数が正しくありません");
+    let args = match parse_args(&args) {
+        Some(args) => args,
+        None => {
+            eprintln!("引数の個数が正しくありません");
+            std::process::exit(1);
+        }
+    };
+
+    let tokens = match tokenize(args.input) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    let mut tokens = preprocess(tokens, &args.include_paths);
+    if args.preprocess_only {
+        return format_tokens(&tokens);
+    }
+    let mut code = program(&mut tokens);
+    if let Err(errors) = analyze(&mut code, &mut tokens.lvars) {
+        for err in errors {
+            eprintln!("{err}");
+        }
         std::process::exit(1);
     }
-
-    let mut tokens = tokenize(args[1].to_string()).unwrap();
     let mut result = String::new();
 
     // アセンブリの前半部分
     result.push_str(".intel_syntax noprefix\n");
     result.push_str(".globl main\n");
     result.push_str("main:\n");
-
-    // 変数26個分の領域を確保する
-    result.push_str("  push rbp\n");
-    result.push_str("  mov rbp, rsp\n");
-    result.push_str("  sub rsp, 208\n");
-
-    let code = program(&mut tokens);
+    result.push_str(&prologue(&tokens.lvars));
     for node in code {
         let asm_code = gen(&node);
         result.push_str(&asm_code);
         result.push_str("  pop rax\n");
     }
+    result.push_str(EPILOGUE);
+
+    // 関数の定義。本体の文は関数の変数の領域に入れ替えてから生成する
+    for mut func in std::mem::take(&mut tokens.lvars.funcs) {
+        tokens.lvars.swap_frame(&mut func);
+        // staticな関数は、他の翻訳単位から参照されないようにラベルを.globlにしない
+        if !func.is_static {
+            result.push_str(&format!(".globl {}\n", func.label));
+        }
+        result.push_str(&format!("{}:\n", func.label));
+        result.push_str(&prologue(&tokens.lvars));
+        result.push_str(&gen_params(&func, &tokens.lvars.vec));
+        for node in &func.body {
+            result.push_str(&gen(node));
+            result.push_str("  pop rax\n");
+        }
+        result.push_str(EPILOGUE);
+        tokens.lvars.swap_frame(&mut func);
+    }
+
+    // 静的変数の領域
+    result.push_str(&gen_data(&tokens.lvars.gvars));
+    result
+}
 
-    result.push_str("  mov rsp, rbp\n");
-    result.push_str("  pop rbp\n");
-    result.push_str("  ret\n");
-    return result;
+/// 関数の先頭で変数の領域を確保する。
+/// rbpは変数のアライメントに揃えるため、元のフレームの位置を[rbp]に保存しておく
+fn prologue(lvars: &LVars) -> String {
+    let mut result = String::new();
+    result.push_str("  push rbp\n");
+    result.push_str("  mov rax, rsp\n");
+    result.push_str("  sub rsp, 8\n");
+    result.push_str(&format!("  and rsp, -{}\n", lvars.frame_align()));
+    result.push_str("  mov rbp, rsp\n");
+    result.push_str("  mov [rbp], rax\n");
+    result.push_str(&format!(
+        "  sub rsp, {}\n",
+        align_to(lvars.stack_size(), 16)
+    ));
+    // allocaで確保する領域は、変数の領域の下から積み上げていく
+    if let Some(offset) = lvars.alloca_bottom {
+        result.push_str(&format!("  mov [rbp-{offset}], rsp\n"));
+    }
+    result
 }
+
+/// 関数の末尾。本体の最後の文の値をraxに入れたまま、呼び出し元に戻る
+const EPILOGUE: &str = "  mov rsp, [rbp]\n  pop rbp\n  ret\n";
@@ -1,29 +1,83 @@
-use crate::tokenize::{Token, TokenKind, Tokens};
+use crate::tokenize::{Location, Token, TokenKind, Tokens};
+use crate::types::{align_to, common_type, promote, Type, TypeKind};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum NodeKind {
-    Add,    // +
-    Sub,    // -
-    Mul,    // *
-    Div,    // /
-    Lt,     // <
-    Le,     // <=
-    Eq,     // ==
-    Ne,     // !=
-    Assign, // =
-    Return, // return
-    LVar,   // local variable
-    Num,    // integer
-    Nil,    // empty node
+    Add,      // +
+    Sub,      // -
+    Mul,      // *
+    Div,      // /
+    Shl,      // <<
+    Shr,      // >>
+    Lt,       // <
+    Le,       // <=
+    Eq,       // ==
+    Ne,       // !=
+    Not,      // !
+    LogAnd,   // &&
+    LogOr,    // ||
+    Cond,     // ?:
+    Assign,   // =
+    Addr,     // 単項 &
+    Deref,    // 単項 *
+    Member,   // . (構造体のメンバ参照)
+    Sizeof,   // sizeof 式。意味解析でオペランドの型の大きさに置き換える
+    Cast,     // 型変換
+    Comma,    // 左辺、右辺の順に評価し、右辺の値を返す
+    MemZero,  // 変数の領域を0で埋める
+    Init,     // 宣言した変数の初期化子。意味解析で変数の型に合わせて展開する
+    Compound, // 複合リテラル。意味解析で、名前のない変数を初期化してその変数を参照する式にする
+    FuncCall, // 関数呼び出し
+    Alloca,   // スタック上の領域の確保 (alloca)
+    VaStart,  // va_listを最初の可変長引数を指すように初期化する (__builtin_va_start)
+    VaArg,    // va_listが指す次の可変長引数を取り出す (__builtin_va_arg)
+    Return,   // return
+    DoWhile,  // do ... while
+    Continue, // continue
+    Ident,    // 名前解決前の識別子。意味解析で変数を指すノードに置き換える
+    LVar,     // local variable
+    GVar,     // 静的記憶域期間を持つ変数 (静的ローカル変数とextern宣言された変数)
+    Num,      // integer
+    Nil,      // empty node
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug)]
 pub struct Node {
     pub kind: NodeKind,
-    pub val: i64,    // kindがNumの場合のみ使う
-    pub offset: i64, // kindがLVarの場合のみ使う
+    pub val: i64, // kindがNumの場合は値、InitかCompoundの場合は初期化する変数のLVarsでの位置
+    pub fval: f64, // kindがNumで、型が浮動小数点数の場合のみ使う
+    // kindがLVarかMemberかMemZeroかVaStart、または構造体・共用体を返すFuncCallかReturnの場合のみ使う
+    pub offset: i64,
+    // kindがGVarの場合はラベル、IdentとMemberの場合は識別子の名前、
+    // Initの場合は初期化する静的変数のラベル(自動変数の場合は空)
+    pub label: String,
     pub lhs: Option<Box<Node>>,
     pub rhs: Option<Box<Node>>,
+    pub args: Vec<Node>,         // kindがFuncCallの場合のみ使う
+    pub cond: Option<Box<Node>>, // kindがCondの場合のみ使う
+    pub ty: Option<Type>,        // 型が決まるまではNone
+    pub loc: Option<Location>,   // 意味解析のエラーを表示する位置。パーサが補ったノードはNone
+    pub scope: Scope,            // kindがIdentの場合のみ使う
+    // kindがInitかCompoundの場合のみ使う
+    pub init: Option<Box<Initializer>>,
+}
+
+/// 構文木の比較では、ソースコード上の位置は無視する
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.val == other.val
+            && self.fval == other.fval
+            && self.offset == other.offset
+            && self.label == other.label
+            && self.lhs == other.lhs
+            && self.rhs == other.rhs
+            && self.args == other.args
+            && self.cond == other.cond
+            && self.ty == other.ty
+            && self.scope == other.scope
+            && self.init == other.init
+    }
 }
 
 impl Node {
@@ -31,9 +85,17 @@ impl Node {
         Self {
             kind: NodeKind::Nil,
             val: 0,
+            fval: 0.0,
             offset: 0,
+            label: String::new(),
             lhs: None,
             rhs: None,
+            args: Vec::new(),
+            cond: None,
+            ty: None,
+            loc: None,
+            scope: Scope::default(),
+            init: None,
         }
     }
 
@@ -41,9 +103,27 @@ impl Node {
         Self {
             kind: NodeKind::Num,
             val,
+            fval: 0.0,
             offset: 0,
+            label: String::new(),
             lhs: None,
             rhs: None,
+            args: Vec::new(),
+            cond: None,
+            ty: None,
+            loc: None,
+            scope: Scope::default(),
+            init: None,
+        }
+    }
+
+    /// exprをtyに型変換するノードを作る
+    pub fn new_cast(expr: Node, ty: Type) -> Self {
+        Self {
+            kind: NodeKind::Cast,
+            lhs: Some(Box::new(expr)),
+            ty: Some(ty),
+            ..Self::default()
         }
     }
 
@@ -51,36 +131,224 @@ impl Node {
         Self {
             kind,
             val: 0,
+            fval: 0.0,
             offset: 0,
+            label: String::new(),
             lhs: Some(Box::new(lhs)),
             rhs: Some(Box::new(rhs)),
+            args: Vec::new(),
+            cond: None,
+            ty: None,
+            loc: None,
+            scope: Scope::default(),
+            init: None,
         }
     }
 }
 
+/// 識別子の位置から見える宣言の範囲。識別子を読んだ時点でLVarsに登録されていた変数と静的変数の個数。
+/// 意味解析では、これより後に登録された宣言を識別子に結びつけない。
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Scope {
+    pub lvars: usize,
+    pub gvars: usize,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct LVar {
     pub name: String,
     pub len: usize,
     pub offset: i64,
+    pub ty: Option<Type>, // 宣言なしで使われた変数の場合はNone
 }
 
+/// 静的記憶域期間を持つ変数。スタックではなくデータ領域に置かれる。
 #[derive(Debug, PartialEq, Eq)]
+pub struct GVar {
+    pub name: String,
+    pub label: String, // アセンブリ上のラベル
+    pub ty: Type,
+    pub data: Option<Vec<u8>>, // 初期値。extern宣言の場合は領域を確保しないためNone
+    pub relocs: Vec<Reloc>,    // 初期値のうち、他の変数のアドレスを書き込む位置
+}
+
+/// 静的変数の初期値に書き込む、他の変数のアドレス。アセンブラに.quad label+addendとして計算させる。
+#[derive(Debug, PartialEq, Eq)]
+pub struct Reloc {
+    pub offset: i64,   // 初期値の先頭からの位置
+    pub label: String, // アドレスを書き込む変数のラベル
+    pub addend: i64,   // アドレスに足すバイト数
+}
+
+/// 関数の定義。関数の本体は、トップレベルの文とは別の変数の領域を持つ。
+#[derive(Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub label: String, // アセンブリ上のラベル
+    pub ty: Type,
+    pub body: Vec<Node>,
+    pub vars: Vec<LVar>, // 引数と自動変数。引数は先頭から順に並ぶ
+    pub alloca_bottom: Option<i64>,
+    // 構造体・共用体を返す関数で、メモリで返す戻り値を書き込む先のアドレスを保存する変数のオフセット
+    pub ret_ptr: Option<i64>,
+    // 可変長引数の関数で、va_listの初期値とレジスタで渡された引数を保存する領域の変数のオフセット
+    pub va_area: Option<i64>,
+    pub is_static: bool, // staticな関数は他の翻訳単位から参照できない
+}
+
+/// 可変長引数の関数で、引数を保存する領域の大きさ。va_listの初期値の24バイトに続けて、
+/// System V ABIのレジスタ保存領域(整数レジスタ6個とxmmレジスタ8個)を置く。
+pub const VA_AREA_SIZE: i64 = 24 + 8 * 6 + 16 * 8;
+
+#[derive(Debug, PartialEq)]
 pub struct LVars {
     pub vec: Vec<LVar>,
+    pub gvars: Vec<GVar>,                // 静的ローカル変数とextern宣言
+    pub tags: Vec<Type>,                 // 構造体・共用体・列挙型のタグ
+    pub enum_consts: Vec<(String, i64)>, // 列挙定数の名前と値
+    pub typedefs: Vec<(String, Type)>,   // typedefで宣言された型名とその型
+    pub alloca_bottom: Option<i64>,      // allocaで確保した領域の底を持つ変数
+    pub implicit_funcs: Vec<usize>,      // 宣言なしで呼び出された関数の、gvarsでの位置
+    pub funcs: Vec<Function>,            // 関数の定義
+    pub return_ty: Option<Type>,         // 意味解析している関数の戻り値の型。main関数ではNone
 }
 
 impl LVars {
     pub fn new() -> Self {
-        Self { vec: Vec::new() }
+        Self {
+            vec: Vec::new(),
+            gvars: Vec::new(),
+            tags: Vec::new(),
+            enum_consts: Vec::new(),
+            typedefs: Vec::new(),
+            alloca_bottom: None,
+            implicit_funcs: Vec::new(),
+            funcs: Vec::new(),
+            return_ty: None,
+        }
+    }
+
+    /// 変数の領域を関数の変数の領域と入れ替える。関数の本体を読む前後と、解析する前後に呼ぶ。
+    pub fn swap_frame(&mut self, func: &mut Function) {
+        std::mem::swap(&mut self.vec, &mut func.vars);
+        std::mem::swap(&mut self.alloca_bottom, &mut func.alloca_bottom);
+    }
+
+    /// 現在の位置から見える宣言の範囲
+    pub fn scope(&self) -> Scope {
+        Scope {
+            lvars: self.vec.len(),
+            gvars: self.gvars.len(),
+        }
     }
 
     pub fn find(&self, name: &str) -> Option<&LVar> {
         self.vec.iter().find(|v| v.name == name)
     }
+
+    pub fn find_gvar(&self, name: &str) -> Option<&GVar> {
+        self.gvars.iter().find(|v| v.name == name)
+    }
+
+    /// 変数を追加し、そのサイズとアライメントに合わせてrbpからのオフセットを決める。
+    /// 宣言なしで使われた変数は8バイトの領域を確保する。
+    pub fn push(&mut self, name: String, ty: Option<Type>) -> &LVar {
+        let (size, align) = match &ty {
+            Some(ty) => (ty.size, ty.align),
+            None => (8, 8),
+        };
+        let offset = align_to(self.stack_size() + size, align);
+        self.vec.push(LVar {
+            len: name.len(),
+            name,
+            offset,
+            ty,
+        });
+        self.vec.last().unwrap()
+    }
+
+    /// allocaで確保した領域の底を持つ変数のオフセット。初めて使うときに変数を確保する。
+    /// 名前のない変数なので、名前で見つかることはない。
+    pub fn alloca_bottom(&mut self) -> i64 {
+        match self.alloca_bottom {
+            Some(offset) => offset,
+            None => {
+                let offset = self.push(String::new(), Some(Type::long())).offset;
+                self.alloca_bottom = Some(offset);
+                offset
+            }
+        }
+    }
+
+    /// 変数のために確保しているスタック領域の大きさ
+    pub fn stack_size(&self) -> i64 {
+        self.vec.iter().map(|v| v.offset).max().unwrap_or(0)
+    }
+
+    /// 変数のアドレスの基準となるrbpを揃えるアライメント。
+    /// オフセットは変数のアライメントの倍数なので、rbpを最大のアライメントに揃えれば変数のアドレスも揃う。
+    pub fn frame_align(&self) -> i64 {
+        self.vec
+            .iter()
+            .filter_map(|v| v.ty.as_ref())
+            .map(|ty| ty.align)
+            .fold(16, i64::max)
+    }
+
+    pub fn find_enum_const(&self, name: &str) -> Option<i64> {
+        self.enum_consts
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, val)| *val)
+    }
+
+    /// 識別子が型名(typedef名)であればその型を返す。変数や列挙定数の場合はNone。
+    pub fn find_typedef(&self, name: &str) -> Option<&Type> {
+        self.typedefs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, ty)| ty)
+    }
+
+    /// 変数・列挙定数・typedef名のいずれかとして宣言済みかどうか
+    pub fn is_declared(&self, name: &str) -> bool {
+        self.find(name).is_some()
+            || self.find_gvar(name).is_some()
+            || self.find_enum_const(name).is_some()
+            || self.find_typedef(name).is_some()
+    }
+
+    pub fn find_tag(&self, tag: &str) -> Option<&Type> {
+        self.tags
+            .iter()
+            .rev()
+            .find(|ty| ty.tag.as_deref() == Some(tag))
+    }
+
+    /// 不完全な構造体型・共用体型(へのポインタ)を、同じタグを持つ定義済みの型で置き換える。
+    /// 自分自身へのポインタをメンバに持つ構造体では、メンバの型が不完全型のままになっているため。
+    pub fn complete(&self, ty: Type) -> Type {
+        let completed = match ty.kind {
+            TypeKind::Struct | TypeKind::Union if ty.members.is_empty() => match &ty.tag {
+                Some(tag) => match self.find_tag(tag) {
+                    Some(found) if found.kind == ty.kind => found.clone(),
+                    Some(_) => panic!("タグの種類が宣言と一致しません: {tag}"),
+                    None => return ty,
+                },
+                None => return ty,
+            },
+            TypeKind::Ptr => Type::pointer_to(self.complete(*ty.base.clone().unwrap())),
+            TypeKind::Array => {
+                Type::array_of(self.complete(*ty.base.clone().unwrap()), ty.array_len)
+            }
+            _ => return ty,
+        };
+        completed.qualified(&ty)
+    }
 }
 
-/// program = stmt*
+/// program = (stmt | function-definition)*
+/// 関数の定義以外の文は、main関数の本体になる。
 pub fn program(tokens: &mut Tokens) -> Vec<Node> {
     let mut code = Vec::with_capacity(1);
     while tokens.len() > 0 {
@@ -89,12 +357,38 @@ pub fn program(tokens: &mut Tokens) -> Vec<Node> {
     code
 }
 
-/// stmt = (expr | "return" expr) ";"
+/// stmt = "__extension__"* stmt
+///      | (expr | "return" expr?) ";"
+///      | "do" stmt "while" "(" expr ")" ";"
+///      | "continue" ";"
+///      | declaration
+/// GNU拡張の "__extension__" は警告を抑えるためのものなので、読み飛ばす。
 pub fn stmt(tokens: &mut Tokens) -> Node {
+    while tokens.consume(TokenKind::Extension) {}
+    if tokens.consume(TokenKind::Do) {
+        return do_while(tokens);
+    }
+    if is_typename(tokens, tokens.front()) {
+        return declaration(tokens);
+    }
+
+    let loc = tokens.loc();
     let node = if tokens.consume_return() {
+        // void型の関数では戻り値を省略する
+        let value = tokens.front().is_some_and(|tk| tk.str != ";");
         Node {
             kind: NodeKind::Return,
-            lhs: Some(Box::new(expr(tokens))),
+            offset: tokens.func.as_ref().and_then(|f| f.ret_ptr).unwrap_or(0),
+            lhs: value.then(|| Box::new(expr(tokens))),
+            loc,
+            ..Node::default()
+        }
+    } else if tokens.consume(TokenKind::Continue) {
+        // ループの外のcontinue文は、飛び先を-1にしておき意味解析でエラーにする
+        Node {
+            kind: NodeKind::Continue,
+            val: tokens.continue_label.unwrap_or(-1),
+            loc,
             ..Node::default()
         }
     } else {
@@ -107,20 +401,1263 @@ pub fn stmt(tokens: &mut Tokens) -> Node {
     node
 }
 
+/// "do" の直後から do-while 文を読み取る。
+/// valにはループのラベル番号を格納し、本体の中のcontinue文はこの番号を飛び先とする。
+fn do_while(tokens: &mut Tokens) -> Node {
+    let label = new_label(tokens);
+
+    let outer_label = tokens.continue_label.replace(label);
+    let body = stmt(tokens);
+    tokens.continue_label = outer_label;
+
+    if !tokens.consume(TokenKind::While) {
+        panic!("do文の本体の後には'while'が必要です");
+    }
+    if !tokens.consume_op("(") {
+        panic!("'(' is not found");
+    }
+    let cond = expr(tokens);
+    if !tokens.consume_op(")") {
+        panic!("')' is not found");
+    }
+    if !tokens.consume_op(";") {
+        eprintln!("文末には';'が必要です。");
+        std::process::exit(1);
+    }
+
+    Node {
+        kind: NodeKind::DoWhile,
+        val: label,
+        lhs: Some(Box::new(body)),
+        rhs: Some(Box::new(cond)),
+        ..Node::default()
+    }
+}
+
+/// トークンが型名の始まりかどうか。
+/// 識別子の場合は、typedefで宣言された型名であれば型名とみなす。
+fn is_typename(tokens: &Tokens, tk: Option<&Token>) -> bool {
+    match tk {
+        Some(tk) => match tk.kind {
+            TokenKind::Char
+            | TokenKind::Short
+            | TokenKind::Int
+            | TokenKind::Long
+            | TokenKind::Signed
+            | TokenKind::Unsigned
+            | TokenKind::FloatType
+            | TokenKind::Double
+            | TokenKind::Struct
+            | TokenKind::Union
+            | TokenKind::Enum
+            | TokenKind::Typedef
+            | TokenKind::Static
+            | TokenKind::Extern
+            | TokenKind::Const
+            | TokenKind::Volatile
+            | TokenKind::Void
+            | TokenKind::Bool
+            | TokenKind::Alignas
+            | TokenKind::Restrict
+            | TokenKind::Inline
+            | TokenKind::Attribute
+            | TokenKind::VaList => true,
+            TokenKind::Ident => tokens.lvars.find_typedef(&tk.str).is_some(),
+            _ => false,
+        },
+        None => false,
+    }
+}
+
+/// declaration = storage-class? declspec (init-declarator ("," init-declarator)*)? ";"
+/// storage-class = "typedef" | "static" | "extern"
+/// init-declarator = declarator asm-label? attribute* ("=" initializer)?
+/// 変数をLVarsに登録する。"typedef" がある場合は変数ではなく型名として登録する。
+/// "static" と "extern" の変数はスタックではなくデータ領域に置く。
+/// 初期化式がある場合はその代入を順に行うノードを、ない場合は空の文のノードを返す。
+fn declaration(tokens: &mut Tokens) -> Node {
+    let storage = tokens.front().map(|tk| tk.kind).filter(|kind| {
+        matches!(
+            kind,
+            TokenKind::Typedef | TokenKind::Static | TokenKind::Extern
+        )
+    });
+    if storage.is_some() {
+        tokens.pop_front();
+    }
+    tokens.is_inline = false;
+    let base = declspec(tokens);
+    let is_inline = tokens.is_inline;
+    let mut node = Node::default();
+    let mut first = true;
+    while !tokens.consume_op(";") {
+        if !first && !tokens.consume_op(",") {
+            panic!("宣言の区切りには','が必要です");
+        }
+        first = false;
+
+        let (name, ty) = declarator(tokens, base.clone());
+        let label = asm_label(tokens).unwrap_or_else(|| name.clone());
+        attributes(tokens);
+        if tokens.lvars.is_declared(&name) && !is_redeclaration(&tokens.lvars, &name, &ty, storage)
+        {
+            panic!("識別子が再定義されています: {name}");
+        }
+        if !tokens.vla_sizes.is_empty() {
+            node = vla_declaration(tokens, node, name, ty, storage);
+            continue;
+        }
+        match storage {
+            Some(TokenKind::Typedef) => {
+                tokens.lvars.typedefs.push((name, ty));
+                continue;
+            }
+            // 関数は定義の前に宣言として登録するため、本体の中から再帰呼び出しできる
+            _ if ty.is_func() => {
+                tokens.lvars.gvars.push(GVar {
+                    label: label.clone(),
+                    name: name.clone(),
+                    ty: ty.clone(),
+                    data: None,
+                    relocs: Vec::new(),
+                });
+                if tokens.consume_op("{") {
+                    // inline関数は、gccで最適化しない場合と同様に、後で使われる場合だけ定義する。
+                    // ヘッダファイルには、使われなければ定義しなくてよいinline関数が多くある
+                    let body = take_block(tokens);
+                    let is_used = tokens
+                        .tokens
+                        .iter()
+                        .any(|tk| tk.kind == TokenKind::Ident && tk.str == name);
+                    if is_inline && !is_used {
+                        return node;
+                    }
+                    for tk in body.into_iter().rev() {
+                        tokens.push_front(tk);
+                    }
+                    let is_static = storage == Some(TokenKind::Static);
+                    function_definition(tokens, name, label, ty, is_static);
+                    return node;
+                }
+                continue;
+            }
+            Some(TokenKind::Extern) => {
+                if tokens.consume_op("=") {
+                    panic!("extern宣言に初期化子は使えません: {name}");
+                }
+                // 定義は他の翻訳単位にあるため、変数名をそのままラベルとして参照する
+                tokens.lvars.gvars.push(GVar {
+                    label,
+                    name,
+                    ty,
+                    data: None,
+                    relocs: Vec::new(),
+                });
+                continue;
+            }
+            _ => {}
+        }
+
+        // 初期化子は変数を登録する前に読むため、初期化子の中の識別子がその変数を指すことはない。
+        // 要素数を省略した配列の型は、意味解析で初期化子を展開するときに決まる
+        let init = tokens
+            .consume_op("=")
+            .then(|| Box::new(initializer(tokens)));
+        if ty.is_aggregate() && ty.members.is_empty()
+            || ty.is_array() && ty.array_len < 0 && init.is_none()
+            || ty.kind == TypeKind::Void
+        {
+            panic!("不完全な型の変数は宣言できません: {name}");
+        }
+        let Some(init) = init else {
+            if storage == Some(TokenKind::Static) {
+                tokens.lvars.gvars.push(GVar {
+                    label: format!("{name}.{}", tokens.lvars.gvars.len()),
+                    name,
+                    data: Some(vec![0; ty.size as usize]),
+                    ty,
+                    relocs: Vec::new(),
+                });
+            } else {
+                tokens.lvars.push(name, Some(ty));
+            }
+            continue;
+        };
+        let init = if storage == Some(TokenKind::Static) {
+            let label = format!("{name}.{}", tokens.lvars.gvars.len());
+            // 初期値は意味解析で初期化子を展開して求める
+            tokens.lvars.gvars.push(GVar {
+                label: label.clone(),
+                name,
+                ty,
+                data: Some(Vec::new()),
+                relocs: Vec::new(),
+            });
+            Node {
+                kind: NodeKind::Init,
+                label,
+                init: Some(init),
+                ..Node::default()
+            }
+        } else {
+            tokens.lvars.push(name, Some(ty));
+            Node {
+                kind: NodeKind::Init,
+                val: tokens.lvars.vec.len() as i64 - 1,
+                init: Some(init),
+                ..Node::default()
+            }
+        };
+        node = Node::new_op(NodeKind::Comma, node, init);
+    }
+    node
+}
+
+/// function-definition = declspec declarator "{" stmt* "}"
+/// "{" の直後から関数の本体を読み取り、関数の定義として登録する。
+/// 本体の変数は関数ごとの領域に確保し、引数はその先頭から順に登録する。
+/// 構造体・共用体を返す関数では戻り値を書き込む先のアドレスを、可変長引数の関数では引数を保存する領域も確保する。
+fn function_definition(
+    tokens: &mut Tokens,
+    name: String,
+    label: String,
+    ty: Type,
+    is_static: bool,
+) {
+    if tokens.func.is_some() {
+        panic!("関数の中で関数は定義できません: {name}");
+    }
+    if name == "main" || tokens.lvars.funcs.iter().any(|f| f.name == name) {
+        panic!("関数が再定義されています: {name}");
+    }
+    let mut func = Function {
+        name,
+        label,
+        ty,
+        body: Vec::new(),
+        vars: Vec::new(),
+        alloca_bottom: None,
+        ret_ptr: None,
+        va_area: None,
+        is_static,
+    };
+    tokens.lvars.swap_frame(&mut func);
+    let names = std::mem::take(&mut tokens.param_names);
+    for (name, ty) in names.into_iter().zip(func.ty.params.clone()) {
+        let Some(name) = name else {
+            panic!("関数の定義では引数の名前を省略できません: {}", func.name);
+        };
+        if tokens.lvars.find(&name).is_some() {
+            panic!("識別子が再定義されています: {name}");
+        }
+        tokens.lvars.push(name, Some(ty));
+    }
+    if func.ty.return_ty.as_ref().unwrap().is_aggregate() {
+        let ty = Type::pointer_to(Type::void());
+        func.ret_ptr = Some(tokens.lvars.push(String::new(), Some(ty)).offset);
+    }
+    // 引数の並びが空の関数は引数を取らず、可変長引数の関数にはならない
+    if func.ty.is_variadic && !func.ty.params.is_empty() {
+        let ty = Type::array_of(Type::long(), VA_AREA_SIZE / 8);
+        func.va_area = Some(tokens.lvars.push(String::new(), Some(ty)).offset);
+    }
+
+    let outer_label = tokens.continue_label.take();
+    tokens.func = Some(func);
+    let mut body = Vec::new();
+    while !tokens.consume_op("}") {
+        if tokens.len() == 0 {
+            panic!("'}}' is not found");
+        }
+        body.push(stmt(tokens));
+    }
+    let mut func = tokens.func.take().unwrap();
+    tokens.continue_label = outer_label;
+    tokens.lvars.swap_frame(&mut func);
+    func.body = body;
+    tokens.lvars.funcs.push(func);
+}
+
+/// 宣言済みの識別子の再宣言として許されるかどうか。
+/// 関数とextern変数は何度宣言してもよく、typedefは同じ型であれば再定義してもよい。
+fn is_redeclaration(lvars: &LVars, name: &str, ty: &Type, storage: Option<TokenKind>) -> bool {
+    match storage {
+        Some(TokenKind::Typedef) => lvars.find_typedef(name) == Some(ty),
+        _ if ty.is_func() => lvars.find_gvar(name).is_some_and(|v| v.ty.is_func()),
+        Some(TokenKind::Extern) => lvars
+            .find_gvar(name)
+            .is_some_and(|v| v.data.is_none() && !v.ty.is_func()),
+        _ => false,
+    }
+}
+
+/// 可変長配列の変数を宣言する。配列の大きさを計算してから、その大きさの領域をスタックに確保し、
+/// 領域へのポインタを変数に格納する式を、nodeに続けてコンマ演算子でつなげる。
+fn vla_declaration(
+    tokens: &mut Tokens,
+    mut node: Node,
+    name: String,
+    ty: Type,
+    storage: Option<TokenKind>,
+) -> Node {
+    if storage.is_some() || !ty.is_vla() {
+        panic!("可変長配列は自動変数としてのみ宣言できます: {name}");
+    }
+    if tokens.consume_op("=") {
+        panic!("可変長配列は初期化できません: {name}");
+    }
+    for size in std::mem::take(&mut tokens.vla_sizes) {
+        node = Node::new_op(NodeKind::Comma, node, size);
+    }
+    let alloca = Node {
+        kind: NodeKind::Alloca,
+        offset: tokens.lvars.alloca_bottom(),
+        lhs: Some(Box::new(vla_size(&ty))),
+        ..Node::default()
+    };
+    let ptr_ty = ty.clone().decay();
+    let var = Node {
+        kind: NodeKind::LVar,
+        offset: tokens.lvars.push(name, Some(ty)).offset,
+        ty: Some(ptr_ty),
+        ..Node::default()
+    };
+    Node::new_op(
+        NodeKind::Comma,
+        node,
+        Node::new_op(NodeKind::Assign, var, alloca),
+    )
+}
+
+/// 可変長配列の大きさを格納している変数
+pub fn vla_size(ty: &Type) -> Node {
+    Node {
+        kind: NodeKind::LVar,
+        offset: ty.vla_size,
+        ty: Some(Type::long()),
+        ..Node::default()
+    }
+}
+
+/// 型と照らし合わせる前の初期化子。{}で囲まれた並びは、エラーを表示する位置として"{"の位置を持つ。
+#[derive(Debug, PartialEq)]
+pub enum Initializer {
+    Expr(Box<Node>),
+    Str(String),
+    List(Location, Vec<(Vec<Designator>, Initializer)>),
+}
+
+/// 指示付き初期化子の指示子
+#[derive(Debug, PartialEq)]
+pub enum Designator {
+    Index(i64),     // [n]
+    Member(String), // .name
+}
+
+/// initializer = "{" (init-item ("," init-item)* ","?)? "}" | string | assign
+/// init-item   = (("[" const-expr "]" | "." ident)+ "=")? initializer
+fn initializer(tokens: &mut Tokens) -> Initializer {
+    if let Some(s) = string_literal(tokens) {
+        return Initializer::Str(s);
+    }
+    let loc = tokens.loc().unwrap_or_default();
+    if !tokens.consume_op("{") {
+        return Initializer::Expr(Box::new(assign(tokens)));
+    }
+
+    let mut items = Vec::new();
+    while !tokens.consume_op("}") {
+        if !items.is_empty() && !tokens.consume_op(",") {
+            panic!("初期化子の区切りには','が必要です");
+        }
+        if tokens.consume_op("}") {
+            break;
+        }
+
+        let mut designators = Vec::new();
+        loop {
+            if tokens.consume_op("[") {
+                designators.push(Designator::Index(const_expr(tokens)));
+                if !tokens.consume_op("]") {
+                    panic!("']' is not found");
+                }
+            } else if tokens.consume_op(".") {
+                designators.push(Designator::Member(expect_ident(&tokens.pop_front())));
+            } else {
+                break;
+            }
+        }
+        if !designators.is_empty() && !tokens.consume_op("=") {
+            panic!("指示子の後には'='が必要です");
+        }
+        items.push((designators, initializer(tokens)));
+    }
+    Initializer::List(loc, items)
+}
+
+/// 読み取った宣言に誤りがあり、型が決まらないまま解析を続けられない場合に、位置とともにエラーを表示して終了する
+fn error_at(loc: &Location, msg: &str) -> ! {
+    eprintln!("{loc}: {msg}");
+    std::process::exit(1)
+}
+
+/// declspec = declspec-qualifier* type-specifier declspec-qualifier*
+/// type-specifier = ("_Bool" | "char" | "short" | "int" | "long" | "signed" | "unsigned"
+///                   | "float" | "double" | declspec-qualifier)+
+///                | "struct" struct-decl | "union" struct-decl | "enum" enum-decl | "void"
+///                | "__builtin_va_list" | typedef-name
+fn declspec(tokens: &mut Tokens) -> Type {
+    let loc = tokens.front().map(|tk| tk.loc.clone()).unwrap_or_default();
+    let mut quals = declspec_qualifiers(tokens, no_qualifiers());
+    let ty = if let Some(ty) = tokens
+        .front()
+        .and_then(|tk| tokens.lvars.find_typedef(&tk.str))
+    {
+        // typedefの時点で不完全だった構造体型は、その後の定義で完全になっている場合がある
+        let ty = tokens.lvars.complete(ty.clone());
+        tokens.pop_front();
+        ty
+    } else if tokens.consume(TokenKind::Struct) {
+        struct_decl(tokens, TypeKind::Struct)
+    } else if tokens.consume(TokenKind::Union) {
+        struct_decl(tokens, TypeKind::Union)
+    } else if tokens.consume(TokenKind::Enum) {
+        enum_decl(tokens)
+    } else if tokens.consume(TokenKind::Void) {
+        Type::void()
+    } else if tokens.consume(TokenKind::VaList) {
+        Type::va_list()
+    } else {
+        arith_type(tokens, &mut quals)
+    };
+    let quals = declspec_qualifiers(tokens, quals);
+    // _Alignasで型の本来のアラインメントより緩いアラインメントは指定できない
+    if quals.align > 0 && quals.align < ty.align {
+        error_at(
+            &loc,
+            &format!(
+                "アラインメントを型のアラインメント({})より小さくすることはできません: {}",
+                ty.align, quals.align
+            ),
+        );
+    }
+    ty.qualified(&quals).aligned(quals.align)
+}
+
+/// 型修飾子とアラインメント指定子を集めるための仮の型。アラインメントの指定がないことを0で表す。
+fn no_qualifiers() -> Type {
+    Type {
+        align: 0,
+        ..Type::void()
+    }
+}
+
+/// declspec-qualifier = qualifier | "_Alignas" "(" (type-name | const-expr) ")"
+///                    | attribute | "__extension__" | "inline"
+/// 型修飾子とアラインメント指定子の並びを読み取り、それをtyに付けた型を返す。
+/// アラインメント指定子は型のアラインメントを引き上げることで、変数やメンバの配置に反映させる。
+fn declspec_qualifiers(tokens: &mut Tokens, mut ty: Type) -> Type {
+    loop {
+        ty = qualifiers(tokens, ty);
+        attributes(tokens);
+        // inlineは関数の呼び出し方に影響しないので、関数を定義するかどうかを決めるために記録するだけにする
+        if tokens.consume(TokenKind::Inline) {
+            tokens.is_inline = true;
+            continue;
+        }
+        if tokens.consume(TokenKind::Extension) {
+            continue;
+        }
+        let Some(loc) = tokens.front().map(|tk| tk.loc.clone()) else {
+            return ty;
+        };
+        if !tokens.consume(TokenKind::Alignas) {
+            return ty;
+        }
+        if !tokens.consume_op("(") {
+            panic!("'(' is not found");
+        }
+        let align = if is_typename(tokens, tokens.front()) {
+            type_name(tokens).align
+        } else {
+            const_expr(tokens)
+        };
+        if align <= 0 || align & (align - 1) != 0 {
+            error_at(
+                &loc,
+                &format!("アラインメントは2の累乗でなければなりません: {align}"),
+            );
+        }
+        if !tokens.consume_op(")") {
+            panic!("')' is not found");
+        }
+        ty = ty.aligned(align);
+    }
+}
+
+/// qualifier = "const" | "volatile" | "restrict"
+/// 型修飾子の並びを読み取り、それをtyに付けた型を返す。restrictは最適化のためのものなので無視する。
+fn qualifiers(tokens: &mut Tokens, mut ty: Type) -> Type {
+    loop {
+        if tokens.consume(TokenKind::Const) {
+            ty.is_const = true;
+        } else if tokens.consume(TokenKind::Volatile) {
+            ty.is_volatile = true;
+        } else if tokens.consume(TokenKind::Restrict) {
+            continue;
+        } else {
+            return ty;
+        }
+    }
+}
+
+/// attribute = "__attribute__" "(" "(" ... ")" ")"
+/// GNU拡張の属性を読み飛ばす。属性はどれもコード生成に影響しないものとして扱う。
+fn attributes(tokens: &mut Tokens) {
+    while tokens.consume(TokenKind::Attribute) {
+        if !tokens.consume_op("(") {
+            panic!("'(' is not found");
+        }
+        take_until_close_paren(tokens);
+    }
+}
+
+/// asm-label = "__asm__" "(" string ")"
+/// GNU拡張のアセンブリラベル。関数や変数を、名前ではなく指定されたラベルで参照する。
+fn asm_label(tokens: &mut Tokens) -> Option<String> {
+    if !tokens.consume(TokenKind::Asm) {
+        return None;
+    }
+    if !tokens.consume_op("(") {
+        panic!("'(' is not found");
+    }
+    let Some(label) = string_literal(tokens) else {
+        panic!("アセンブリラベルには文字列が必要です");
+    };
+    if !tokens.consume_op(")") {
+        panic!("')' is not found");
+    }
+    Some(label)
+}
+
+/// 文字列リテラルを読み取る。隣り合う文字列リテラルはつなげる。
+fn string_literal(tokens: &mut Tokens) -> Option<String> {
+    let mut s: Option<String> = None;
+    while tokens.front().is_some_and(|tk| tk.kind == TokenKind::Str) {
+        let tk = tokens.pop_front().unwrap();
+        s.get_or_insert_default().push_str(&tk.str);
+    }
+    s
+}
+
+/// 文字列リテラルの中身を、終端の'\0'まで含めた名前のないchar型の配列として静的領域に置き、
+/// その配列を指すノードを返す。
+pub fn string_node(lvars: &mut LVars, s: String) -> Node {
+    let mut data = s.into_bytes();
+    data.push(0);
+    let ty = Type::array_of(Type::char(), data.len() as i64);
+    let label = format!(".L.str.{}", lvars.gvars.len());
+    lvars.gvars.push(GVar {
+        name: String::new(),
+        label: label.clone(),
+        ty: ty.clone(),
+        data: Some(data),
+        relocs: Vec::new(),
+    });
+    Node {
+        kind: NodeKind::GVar,
+        label,
+        ty: Some(ty),
+        ..Node::default()
+    }
+}
+
+/// 算術型のキーワードの並びを読み取る。キーワードは型修飾子と混ざっていてもよく、任意の順序で書ける。
+/// "long long" はlongと同じ型として扱う。
+/// キーワードの間にある型修飾子とアラインメント指定子はqualsに集める。
+fn arith_type(tokens: &mut Tokens, quals: &mut Type) -> Type {
+    let (mut chars, mut shorts, mut ints, mut longs, mut signeds, mut unsigneds) =
+        (0, 0, 0, 0, 0, 0);
+    let (mut floats, mut doubles, mut bools) = (0, 0, 0);
+    loop {
+        *quals = declspec_qualifiers(tokens, quals.clone());
+        match tokens.front().map(|tk| tk.kind) {
+            Some(TokenKind::Char) => chars += 1,
+            Some(TokenKind::Short) => shorts += 1,
+            Some(TokenKind::Int) => ints += 1,
+            Some(TokenKind::Long) => longs += 1,
+            Some(TokenKind::Signed) => signeds += 1,
+            Some(TokenKind::Unsigned) => unsigneds += 1,
+            Some(TokenKind::FloatType) => floats += 1,
+            Some(TokenKind::Double) => doubles += 1,
+            Some(TokenKind::Bool) => bools += 1,
+            _ => break,
+        }
+        tokens.pop_front();
+    }
+
+    let ty = if bools > 0 {
+        if bools + chars + shorts + ints + longs + signeds + unsigneds + floats + doubles > 1 {
+            panic!("不正な型の指定です");
+        }
+        Type::bool()
+    } else if floats + doubles > 0 {
+        match (floats, doubles, longs) {
+            _ if chars + shorts + ints + signeds + unsigneds > 0 => {
+                panic!("不正な型の指定です")
+            }
+            (1, 0, 0) => Type::float(),
+            (0, 1, 0) => Type::double(),
+            (0, 1, 1) => Type::ldouble(),
+            _ => panic!("不正な型の指定です"),
+        }
+    } else {
+        if signeds + unsigneds > 1 || ints > 1 {
+            panic!("不正な型の指定です");
+        }
+        let ty = match (chars, shorts, longs) {
+            (1, 0, 0) if ints == 0 => Type::char(),
+            (0, 1, 0) => Type::short(),
+            (0, 0, 1 | 2) => Type::long(),
+            (0, 0, 0) if ints + signeds + unsigneds > 0 => Type::int(),
+            (0, 0, 0) => panic!("型名ではありません"),
+            _ => panic!("不正な型の指定です"),
+        };
+        if unsigneds > 0 {
+            ty.unsigned()
+        } else {
+            ty
+        }
+    };
+    ty
+}
+
+/// declarator = pointers ("(" declarator ")" | ident) type-suffix
+fn declarator(tokens: &mut Tokens, ty: Type) -> (String, Type) {
+    match declarator_opt(tokens, ty, false) {
+        (Some(name), ty) => (name, ty),
+        (None, _) => panic!("宣言子に識別子がありません"),
+    }
+}
+
+/// 識別子を省略できる宣言子を読み取る。
+/// is_abstractが真の場合は、型名や引数の宣言のように識別子を持たなくてもよい。
+/// int (*fp)(int) のように括弧で囲まれた宣言子は、その外側の型を先に読んでから内側を読む。
+fn declarator_opt(tokens: &mut Tokens, ty: Type, is_abstract: bool) -> (Option<String>, Type) {
+    let ty = pointers(tokens, ty);
+    if is_nested_declarator(tokens, is_abstract) {
+        tokens.consume_op("(");
+        let inner = take_until_close_paren(tokens);
+        let ty = type_suffix(tokens, ty);
+        let rest = tokens.len();
+        for tk in inner.into_iter().rev() {
+            tokens.push_front(tk);
+        }
+        let result = declarator_opt(tokens, ty, is_abstract);
+        if tokens.len() != rest {
+            panic!("括弧で囲まれた宣言子が不正です");
+        }
+        return result;
+    }
+
+    let name = match tokens.front() {
+        Some(tk) if tk.kind == TokenKind::Ident => Some(expect_ident(&tokens.pop_front())),
+        _ if is_abstract => None,
+        _ => panic!("宣言子に識別子がありません"),
+    };
+    (name, type_suffix(tokens, ty))
+}
+
+/// 次の "(" が、括弧で囲まれた宣言子の始まりかどうか。
+/// 識別子を省略できる場合は、int (int) のような関数の引数の並びと区別する。
+fn is_nested_declarator(tokens: &Tokens, is_abstract: bool) -> bool {
+    let is_op = |tk: Option<&Token>, op: &str| {
+        tk.is_some_and(|tk| tk.kind == TokenKind::Reserved && tk.str == op)
+    };
+    if !is_op(tokens.front(), "(") {
+        return false;
+    }
+    let next = tokens.get(1);
+    !is_abstract || is_op(next, "*") || is_op(next, "(") || is_op(next, "[")
+}
+
+/// "{" の直後から、対応する "}" までのトークンを "}" も含めて取り出す
+fn take_block(tokens: &mut Tokens) -> Vec<Token> {
+    let mut body = Vec::new();
+    let mut depth = 0;
+    loop {
+        let tk = match tokens.pop_front() {
+            Some(tk) => tk,
+            None => panic!("'}}' is not found"),
+        };
+        let is_op = |op: &str| tk.kind == TokenKind::Reserved && tk.str == op;
+        if is_op("{") {
+            depth += 1;
+        } else if is_op("}") && depth == 0 {
+            body.push(tk);
+            return body;
+        } else if is_op("}") {
+            depth -= 1;
+        }
+        body.push(tk);
+    }
+}
+
+/// 対応する ")" までのトークンを取り出す。")" 自体は読み捨てる。
+fn take_until_close_paren(tokens: &mut Tokens) -> Vec<Token> {
+    let mut inner = Vec::new();
+    let mut depth = 0;
+    loop {
+        let tk = match tokens.pop_front() {
+            Some(tk) => tk,
+            None => panic!("')' is not found"),
+        };
+        if tk.kind == TokenKind::Reserved {
+            match tk.str.as_str() {
+                "(" => depth += 1,
+                ")" if depth == 0 => return inner,
+                ")" => depth -= 1,
+                _ => {}
+            }
+        }
+        inner.push(tk);
+    }
+}
+
+/// pointers = ("*" qualifier*)*
+fn pointers(tokens: &mut Tokens, mut ty: Type) -> Type {
+    while tokens.consume_op("*") {
+        if ty.is_vla() {
+            panic!("可変長配列へのポインタはサポートしていません");
+        }
+        ty = qualifiers(tokens, Type::pointer_to(ty));
+    }
+    ty
+}
+
+/// type-suffix = "(" func-params | ("[" assign? "]" type-suffix)?
+/// 要素数を省略できるのは最も外側の配列のみ。要素数が定数式でなければ可変長配列になる。
+fn type_suffix(tokens: &mut Tokens, ty: Type) -> Type {
+    if tokens.consume_op("(") {
+        return func_params(tokens, ty);
+    }
+    if !tokens.consume_op("[") {
+        return ty;
+    }
+    if tokens.consume_op("]") {
+        let base = type_suffix(tokens, ty);
+        if base.is_array() && base.array_len < 0 {
+            panic!("要素数を省略できるのは最も外側の配列のみです");
+        }
+        return Type::array_of(base, -1);
+    }
+    let len = assign(tokens);
+    if !tokens.consume_op("]") {
+        panic!("']' is not found");
+    }
+    let base = type_suffix(tokens, ty);
+    if base.is_array() && base.array_len < 0 {
+        panic!("要素数を省略できるのは最も外側の配列のみです");
+    }
+    match const_value(&tokens.lvars, &len) {
+        Some((len, _)) if !base.is_vla() => {
+            if len < 0 {
+                panic!("配列の要素数が負です");
+            }
+            Type::array_of(base, len)
+        }
+        _ => vla_of(tokens, base, len),
+    }
+}
+
+/// 要素数が定数式でない配列は可変長配列になる。配列全体の大きさを格納する変数を確保し、
+/// 大きさを計算する式はtokens.vla_sizesに積んでおく。式は配列を宣言するときに評価する。
+/// 要素も可変長配列の場合は、先に積まれた要素の大きさの変数を使って大きさを計算する。
+fn vla_of(tokens: &mut Tokens, base: Type, len: Node) -> Type {
+    if base.is_func() || base.kind == TypeKind::Void {
+        panic!("配列の要素の型が不正です");
+    }
+    let offset = tokens.lvars.push(String::new(), Some(Type::long())).offset;
+    let var = Node {
+        kind: NodeKind::LVar,
+        offset,
+        ty: Some(Type::long()),
+        ..Node::default()
+    };
+    let base_size = if base.is_vla() {
+        vla_size(&base)
+    } else {
+        Node::new_num(base.size)
+    };
+    let size = Node::new_op(NodeKind::Mul, Node::new_cast(len, Type::long()), base_size);
+    tokens
+        .vla_sizes
+        .push(Node::new_op(NodeKind::Assign, var, size));
+    Type::vla_of(base, offset)
+}
+
+/// func-params = ("void" | param ("," param)* ("," "...")?)? ")"
+/// param = declspec declarator
+/// "(" の直後から読み取る。引数の宣言子は識別子を省略でき、配列と関数はポインタとして扱う。
+/// 引数の並びが空の場合は、引数が不明な関数として可変長引数の関数と同様に扱う。
+/// 引数の名前は、関数の定義で引数の変数を登録するためにtokens.param_namesに記録する。
+fn func_params(tokens: &mut Tokens, return_ty: Type) -> Type {
+    if return_ty.is_array() || return_ty.is_func() {
+        panic!("配列や関数を返す関数は宣言できません");
+    }
+    tokens.param_names.clear();
+    if tokens.consume_op(")") {
+        return Type::func(return_ty, Vec::new(), true);
+    }
+    let is_void = |tk: Option<&Token>| tk.is_some_and(|tk| tk.kind == TokenKind::Void);
+    let is_close = |tk: Option<&Token>| tk.is_some_and(|tk| tk.str == ")");
+    if is_void(tokens.front()) && is_close(tokens.get(1)) {
+        tokens.pop_front();
+        tokens.pop_front();
+        return Type::func(return_ty, Vec::new(), false);
+    }
+
+    let mut params = Vec::new();
+    let mut names = Vec::new();
+    let mut is_variadic = false;
+    while !tokens.consume_op(")") {
+        if !params.is_empty() && !tokens.consume_op(",") {
+            panic!("引数の区切りには','が必要です");
+        }
+        if tokens.consume_op("...") {
+            is_variadic = true;
+            if !tokens.consume_op(")") {
+                panic!("'...'は最後の引数でなければなりません");
+            }
+            break;
+        }
+        // 引数の可変長配列はポインタになるため、大きさを計算する必要はない
+        let vla_sizes = tokens.vla_sizes.len();
+        let base = declspec(tokens);
+        let (name, ty) = declarator_opt(tokens, base, true);
+        attributes(tokens);
+        tokens.vla_sizes.truncate(vla_sizes);
+        if ty.kind == TypeKind::Void {
+            panic!("void型の引数は宣言できません");
+        }
+        params.push(ty.decay());
+        names.push(name);
+    }
+    // 引数の中の関数の宣言子より後に記録するので、関数の定義では自身の引数の名前が残る
+    tokens.param_names = names;
+    Type::func(return_ty, params, is_variadic)
+}
+
+/// type-name = declspec abstract-declarator
+/// キャストやsizeofの中に書かれる、変数名を持たない型を読み取る。可変長配列の型は書けない。
+fn type_name(tokens: &mut Tokens) -> Type {
+    let ty = vla_type_name(tokens);
+    if !tokens.vla_sizes.is_empty() {
+        panic!("可変長配列の型はここでは使えません");
+    }
+    ty
+}
+
+/// 可変長配列の型も書けるtype-name。大きさを計算する式はtokens.vla_sizesに積まれる。
+fn vla_type_name(tokens: &mut Tokens) -> Type {
+    let ty = declspec(tokens);
+    match declarator_opt(tokens, ty, true) {
+        (None, ty) => ty,
+        (Some(name), _) => panic!("型名に識別子があります: {name}"),
+    }
+}
+
+/// struct-decl = attribute* ident?
+///               ("{" (declspec member-declarator ("," member-declarator)* ";")* "}" attribute*)?
+/// "struct" か "union" の直後から読み取る。kindで構造体と共用体のどちらを作るかを指定する。
+fn struct_decl(tokens: &mut Tokens, kind: TypeKind) -> Type {
+    let new_type = match kind {
+        TypeKind::Union => Type::new_union,
+        _ => Type::new_struct,
+    };
+    attributes(tokens);
+
+    let tag = match tokens.front() {
+        Some(tk) if tk.kind == TokenKind::Ident => Some(expect_ident(&tokens.pop_front())),
+        _ => None,
+    };
+
+    if !tokens.consume_op("{") {
+        // 既存の型の参照。まだ定義されていなければ不完全型として扱う。
+        let tag = match tag {
+            Some(tag) => tag,
+            None => panic!("構造体・共用体のタグも本体もありません"),
+        };
+        return tokens.lvars.complete(new_type(Some(tag), Vec::new()));
+    }
+
+    let mut members = Vec::new();
+    while !tokens.consume_op("}") {
+        let base = declspec(tokens);
+        let mut first = true;
+        while !tokens.consume_op(";") {
+            if !first && !tokens.consume_op(",") {
+                panic!("メンバの区切りには','が必要です");
+            }
+            first = false;
+            let (name, ty) = member_declarator(tokens, base.clone());
+            if ty.is_array() && ty.array_len < 0 || ty.is_func() || ty.kind == TypeKind::Void {
+                panic!("不完全な型や関数型はメンバにできません: {name}");
+            }
+            if ty.is_vla() {
+                panic!("可変長配列はメンバにできません: {name}");
+            }
+            attributes(tokens);
+            members.push((name, ty));
+        }
+    }
+    attributes(tokens);
+    if members.is_empty() {
+        panic!("メンバのない構造体・共用体は定義できません");
+    }
+
+    let ty = new_type(tag, members);
+    if ty.tag.is_some() {
+        tokens.lvars.tags.push(ty.clone());
+    }
+    ty
+}
+
+/// member-declarator = declarator (":" const-expr)? | ":" const-expr
+/// ":" の後に幅を書いたメンバはビットフィールドになる。名前のないビットフィールドの名前は空にする。
+fn member_declarator(tokens: &mut Tokens, ty: Type) -> (String, Type) {
+    let (name, ty) = declarator_opt(tokens, ty, true);
+    if !tokens.consume_op(":") {
+        return match name {
+            Some(name) => (name, ty),
+            None => panic!("宣言子に識別子がありません"),
+        };
+    }
+    if !ty.is_integer() {
+        panic!("ビットフィールドの型は整数型でなければなりません");
+    }
+    let width = const_expr(tokens);
+    if width < 0 || width > ty.size * 8 {
+        panic!("ビットフィールドの幅が不正です: {width}");
+    }
+    match name {
+        Some(name) if width == 0 => {
+            panic!("幅が0のビットフィールドには名前を付けられません: {name}")
+        }
+        Some(name) => (name, ty.bitfield(width)),
+        None => (String::new(), ty.bitfield(width)),
+    }
+}
+
+/// enum-decl = ident? ("{" enum-list? "}")?
+/// enum-list = ident ("=" const-expr)? ("," ident ("=" const-expr)?)* ","?
+/// "enum" の直後から読み取る。値を省略した列挙定数は直前の値に1を足したものになる。
+fn enum_decl(tokens: &mut Tokens) -> Type {
+    let tag = match tokens.front() {
+        Some(tk) if tk.kind == TokenKind::Ident => Some(expect_ident(&tokens.pop_front())),
+        _ => None,
+    };
+
+    if !tokens.consume_op("{") {
+        let tag = match tag {
+            Some(tag) => tag,
+            None => panic!("列挙型のタグも本体もありません"),
+        };
+        return match tokens.lvars.find_tag(&tag) {
+            Some(ty) if ty.kind == TypeKind::Enum => ty.clone(),
+            Some(_) => panic!("タグの種類が宣言と一致しません: {tag}"),
+            None => panic!("列挙型が定義されていません: {tag}"),
+        };
+    }
+
+    let mut val = 0;
+    let mut first = true;
+    while !tokens.consume_op("}") {
+        if !first && !tokens.consume_op(",") {
+            panic!("列挙定数の区切りには','が必要です");
+        }
+        first = false;
+        // 末尾のカンマを許す
+        if tokens.consume_op("}") {
+            break;
+        }
+
+        let name = expect_ident(&tokens.pop_front());
+        if tokens.consume_op("=") {
+            val = const_expr(tokens);
+        }
+        if tokens.lvars.is_declared(&name) {
+            panic!("識別子が再定義されています: {name}");
+        }
+        tokens.lvars.enum_consts.push((name, val));
+        val += 1;
+    }
+
+    let ty = Type::enum_type(tag);
+    if ty.tag.is_some() {
+        tokens.lvars.tags.push(ty.clone());
+    }
+    ty
+}
+
+/// const-expr = conditional
+/// 整数定数式を読み取り、その値をコンパイル時に計算する。
+/// 定数式でなければ宣言の型が決まらないため、位置とともにエラーを表示して終了する。
+fn const_expr(tokens: &mut Tokens) -> i64 {
+    let loc = tokens.loc().unwrap_or_default();
+    let node = conditional(tokens);
+    match const_value(&tokens.lvars, &node) {
+        Some((val, _)) => val,
+        None => error_at(&loc, "定数式ではありません"),
+    }
+}
+
+/// 配列の要素数や列挙定数の値のように、パーサが宣言の型を決めるために必要とする整数定数式を評価し、
+/// その値と型を返す。名前解決と型付けは意味解析で行うので、ここでは定数と型名だけから型を求め、
+/// 変数を参照する式など、そうして値を決められない式はNoneにする。
+fn const_value(lvars: &LVars, node: &Node) -> Option<(i64, Type)> {
+    let lhs = || const_value(lvars, node.lhs.as_ref()?);
+    let rhs = || const_value(lvars, node.rhs.as_ref()?);
+    let is_int = |ty: &Type| ty.is_integer();
+    match node.kind {
+        NodeKind::Num => {
+            let ty = match &node.ty {
+                Some(ty) => ty.clone(),
+                None if i32::try_from(node.val).is_ok() => Type::int(),
+                None => Type::long(),
+            };
+            is_int(&ty).then_some((node.val, ty))
+        }
+        NodeKind::Sizeof => {
+            let ty = const_type(lvars, node.lhs.as_ref()?)?;
+            let is_incomplete = ty.is_array() && ty.array_len < 0;
+            (!ty.is_vla() && !is_incomplete).then_some((ty.size, Type::int()))
+        }
+        // ポインタに整数を足すときは、整数にポインタの指す先の型の大きさを掛ける
+        NodeKind::Add | NodeKind::Sub => {
+            let ((l, lty), (r, rty)) = (lhs()?, rhs()?);
+            let size = |ty: &Type| ty.base.as_ref().map_or(1, |base| base.size);
+            match (lty.is_pointer(), rty.is_pointer()) {
+                (true, true) if node.kind == NodeKind::Sub => {
+                    Some((l.wrapping_sub(r) / size(&lty), Type::long()))
+                }
+                (true, false) if node.kind == NodeKind::Sub => {
+                    Some((l.wrapping_sub(r.wrapping_mul(size(&lty))), lty.decay()))
+                }
+                (true, false) => Some((l.wrapping_add(r.wrapping_mul(size(&lty))), lty.decay())),
+                (false, true) if node.kind == NodeKind::Add => {
+                    Some((l.wrapping_mul(size(&rty)).wrapping_add(r), rty.decay()))
+                }
+                (false, false) => {
+                    let ty = common_type(&lty, &rty);
+                    let val = eval_binary(&node.kind, l, r, ty.is_unsigned)?;
+                    Some((ty.truncate(val), ty))
+                }
+                _ => None,
+            }
+        }
+        NodeKind::Mul | NodeKind::Div => {
+            let ((l, lty), (r, rty)) = (lhs()?, rhs()?);
+            if !is_int(&lty) || !is_int(&rty) {
+                return None;
+            }
+            let ty = common_type(&lty, &rty);
+            let val = eval_binary(&node.kind, l, r, ty.is_unsigned)?;
+            Some((ty.truncate(val), ty))
+        }
+        NodeKind::Shl | NodeKind::Shr => {
+            let ((l, lty), (r, rty)) = (lhs()?, rhs()?);
+            if !is_int(&lty) || !is_int(&rty) {
+                return None;
+            }
+            let ty = promote(&lty);
+            let val = eval_binary(&node.kind, l, r, ty.is_unsigned)?;
+            Some((ty.truncate(val), ty))
+        }
+        NodeKind::Eq | NodeKind::Ne | NodeKind::Lt | NodeKind::Le => {
+            let ((l, lty), (r, rty)) = (lhs()?, rhs()?);
+            let is_unsigned = match (is_int(&lty), is_int(&rty)) {
+                (true, true) => common_type(&lty, &rty).is_unsigned,
+                _ => true,
+            };
+            Some((eval_binary(&node.kind, l, r, is_unsigned)?, Type::int()))
+        }
+        NodeKind::Not => Some(((lhs()?.0 == 0) as i64, Type::int())),
+        NodeKind::LogAnd => Some(((lhs()?.0 != 0 && rhs()?.0 != 0) as i64, Type::int())),
+        NodeKind::LogOr => Some(((lhs()?.0 != 0 || rhs()?.0 != 0) as i64, Type::int())),
+        NodeKind::Cond => {
+            let (cond, _) = const_value(lvars, node.cond.as_ref()?)?;
+            let ((l, lty), (r, rty)) = (lhs()?, rhs()?);
+            let ty = match (is_int(&lty), is_int(&rty)) {
+                (true, true) => common_type(&lty, &rty),
+                _ => lty.decay(),
+            };
+            Some((ty.truncate(if cond != 0 { l } else { r }), ty))
+        }
+        NodeKind::Cast => {
+            let ty = node.ty.clone()?;
+            if !is_int(&ty) && !ty.is_pointer() {
+                return None;
+            }
+            // (int)1.5 のような浮動小数点数のリテラルの型変換も整数定数式になる
+            let inner = node.lhs.as_ref()?;
+            let val = match &inner.ty {
+                Some(inner_ty) if inner_ty.is_flonum() => {
+                    if inner.kind != NodeKind::Num {
+                        return None;
+                    }
+                    match ty.kind {
+                        TypeKind::Bool => (inner.fval != 0.0) as i64,
+                        _ => inner.fval as i64,
+                    }
+                }
+                _ => lhs()?.0,
+            };
+            Some((ty.truncate(val), ty))
+        }
+        // offsetofの &((T *)0)->member のような、定数のアドレスから求めたメンバのアドレス。
+        // 配列のメンバは、そのアドレスを値として扱う
+        NodeKind::Addr => {
+            let (addr, ty) = const_lval(lvars, node.lhs.as_ref()?)?;
+            Some((addr, Type::pointer_to(ty)))
+        }
+        NodeKind::Member | NodeKind::Deref => {
+            let (addr, ty) = const_lval(lvars, node)?;
+            ty.is_array().then_some((addr, ty))
+        }
+        _ => None,
+    }
+}
+
+/// 定数のアドレスを参照外しした左辺値の、アドレスと型
+fn const_lval(lvars: &LVars, node: &Node) -> Option<(i64, Type)> {
+    match node.kind {
+        NodeKind::Member => {
+            let (addr, ty) = const_lval(lvars, node.lhs.as_ref()?)?;
+            let ty = lvars.complete(ty);
+            let member = ty.find_member(&node.label)?;
+            let member_ty = lvars.complete(member.ty.clone()).qualified(&ty);
+            Some((addr + member.offset, member_ty))
+        }
+        NodeKind::Deref => {
+            let (addr, ty) = const_value(lvars, node.lhs.as_ref()?)?;
+            if !ty.is_pointer() {
+                return None;
+            }
+            Some((addr, *ty.base?))
+        }
+        _ => None,
+    }
+}
+
+/// 定数式の中のsizeofのオペランドの型。型が決まっている定数と、宣言済みの変数の型のみを扱う。
+fn const_type(lvars: &LVars, node: &Node) -> Option<Type> {
+    match node.kind {
+        NodeKind::Ident => match lvars.find(&node.label) {
+            Some(lvar) => lvar.ty.clone(),
+            None => lvars.find_gvar(&node.label).map(|gvar| gvar.ty.clone()),
+        },
+        _ if node.ty.is_some() => node.ty.clone(),
+        _ => const_value(lvars, node)
+            .or_else(|| const_lval(lvars, node))
+            .map(|(_, ty)| ty),
+    }
+}
+
+/// 整数定数式の二項演算を、両辺を揃えた型の値lhsとrhsについて計算する。
+/// is_unsignedが真の場合は、値をu64として割り算や比較、右シフトをする。0で割る場合はNoneを返す。
+/// 結果は演算の型に切り詰める前の値で、パーサと意味解析のどちらの定数式の評価にも使う。
+pub fn eval_binary(kind: &NodeKind, lhs: i64, rhs: i64, is_unsigned: bool) -> Option<i64> {
+    let val = match kind {
+        NodeKind::Add => lhs.wrapping_add(rhs),
+        NodeKind::Sub => lhs.wrapping_sub(rhs),
+        NodeKind::Mul => lhs.wrapping_mul(rhs),
+        NodeKind::Div if rhs == 0 => return None,
+        NodeKind::Div if is_unsigned => (lhs as u64 / rhs as u64) as i64,
+        NodeKind::Div => lhs.wrapping_div(rhs),
+        NodeKind::Shl => lhs.wrapping_shl(rhs as u32),
+        NodeKind::Shr if is_unsigned => (lhs as u64).wrapping_shr(rhs as u32) as i64,
+        NodeKind::Shr => lhs.wrapping_shr(rhs as u32),
+        NodeKind::Eq => (lhs == rhs) as i64,
+        NodeKind::Ne => (lhs != rhs) as i64,
+        NodeKind::Lt if is_unsigned => ((lhs as u64) < rhs as u64) as i64,
+        NodeKind::Le if is_unsigned => (lhs as u64 <= rhs as u64) as i64,
+        NodeKind::Lt => (lhs < rhs) as i64,
+        NodeKind::Le => (lhs <= rhs) as i64,
+        _ => return None,
+    };
+    Some(val)
+}
+
 /// expr = assign
 pub fn expr(tokens: &mut Tokens) -> Node {
     assign(tokens)
 }
 
-/// assign = equality ("=" assign)?
+/// assign = conditional ("=" assign)?
 fn assign(tokens: &mut Tokens) -> Node {
-    let mut node = equality(tokens);
+    let mut node = conditional(tokens);
+    let loc = tokens.loc();
     if tokens.consume_op("=") {
-        node = Node::new_op(NodeKind::Assign, node, assign(tokens));
+        node = Node {
+            loc,
+            ..Node::new_op(NodeKind::Assign, node, assign(tokens))
+        };
+    }
+    node
+}
+
+/// conditional = logor ("?" expr ":" conditional)?
+/// 条件はcondに、真の場合の値はlhsに、偽の場合の値はrhsに格納し、valには分岐先のラベル番号を格納する。
+fn conditional(tokens: &mut Tokens) -> Node {
+    let cond = logor(tokens);
+    if !tokens.consume_op("?") {
+        return cond;
+    }
+    let then = expr(tokens);
+    if !tokens.consume_op(":") {
+        panic!("':' is not found");
+    }
+    let els = conditional(tokens);
+    Node {
+        val: new_label(tokens),
+        cond: Some(Box::new(cond)),
+        ..Node::new_op(NodeKind::Cond, then, els)
+    }
+}
+
+/// logor = logand ("||" logand)*
+/// 短絡評価のため、valには分岐先のラベル番号を格納する。
+fn logor(tokens: &mut Tokens) -> Node {
+    let mut node = logand(tokens);
+    while tokens.consume_op("||") {
+        node = Node {
+            val: new_label(tokens),
+            ..Node::new_op(NodeKind::LogOr, node, logand(tokens))
+        };
+    }
+    node
+}
+
+/// logand = equality ("&&" equality)*
+fn logand(tokens: &mut Tokens) -> Node {
+    let mut node = equality(tokens);
+    while tokens.consume_op("&&") {
+        node = Node {
+            val: new_label(tokens),
+            ..Node::new_op(NodeKind::LogAnd, node, equality(tokens))
+        };
     }
     node
 }
 
+/// 新しいラベル番号を割り当てる
+fn new_label(tokens: &mut Tokens) -> i64 {
+    let label = tokens.label_count;
+    tokens.label_count += 1;
+    label
+}
+
 /// equality = relational ("==" relational | "!=" relational)*
 fn equality(tokens: &mut Tokens) -> Node {
     let mut node = relational(tokens);
@@ -135,18 +1672,32 @@ fn equality(tokens: &mut Tokens) -> Node {
     }
 }
 
-/// relational = add ("<" add | "<=" add | ">" add | ">=" add)*
+/// relational = shift ("<" shift | "<=" shift | ">" shift | ">=" shift)*
 fn relational(tokens: &mut Tokens) -> Node {
-    let mut node = add(tokens);
+    let mut node = shift(tokens);
     loop {
         if tokens.consume_op("<") {
-            node = Node::new_op(NodeKind::Lt, node, add(tokens));
+            node = Node::new_op(NodeKind::Lt, node, shift(tokens));
         } else if tokens.consume_op("<=") {
-            node = Node::new_op(NodeKind::Le, node, add(tokens));
+            node = Node::new_op(NodeKind::Le, node, shift(tokens));
         } else if tokens.consume_op(">") {
-            node = Node::new_op(NodeKind::Lt, add(tokens), node);
+            node = Node::new_op(NodeKind::Lt, shift(tokens), node);
         } else if tokens.consume_op(">=") {
-            node = Node::new_op(NodeKind::Le, add(tokens), node);
+            node = Node::new_op(NodeKind::Le, shift(tokens), node);
+        } else {
+            return node;
+        }
+    }
+}
+
+/// shift = add ("<<" add | ">>" add)*
+fn shift(tokens: &mut Tokens) -> Node {
+    let mut node = add(tokens);
+    loop {
+        if tokens.consume_op("<<") {
+            node = Node::new_op(NodeKind::Shl, node, add(tokens));
+        } else if tokens.consume_op(">>") {
+            node = Node::new_op(NodeKind::Shr, node, add(tokens));
         } else {
             return node;
         }
@@ -167,69 +1718,299 @@ fn add(tokens: &mut Tokens) -> Node {
     }
 }
 
-/// mul = unary ("*" unary | "/" unary)*
+/// mul = cast ("*" cast | "/" cast)*
 fn mul(tokens: &mut Tokens) -> Node {
-    let mut node = unary(tokens);
+    let mut node = cast(tokens);
     loop {
         if tokens.consume_op("*") {
-            node = Node::new_op(NodeKind::Mul, node, unary(tokens));
+            node = Node::new_op(NodeKind::Mul, node, cast(tokens));
         } else if tokens.consume_op("/") {
-            node = Node::new_op(NodeKind::Div, node, unary(tokens));
+            node = Node::new_op(NodeKind::Div, node, cast(tokens));
         } else {
             return node;
         }
     }
 }
 
-/// unary = ("+" | "-")? primary
+/// 次のトークンが "(" で、その次が型名であるかどうか
+fn is_paren_typename(tokens: &Tokens) -> bool {
+    matches!(tokens.front(), Some(tk) if tk.kind == TokenKind::Reserved && tk.str == "(")
+        && is_typename(tokens, tokens.get(1))
+}
+
+/// cast = "(" type-name ")" cast | "(" type-name ")" compound-literal | unary
+fn cast(tokens: &mut Tokens) -> Node {
+    if !is_paren_typename(tokens) {
+        return unary(tokens);
+    }
+
+    let loc = tokens.loc();
+    tokens.consume_op("(");
+    let ty = type_name(tokens);
+    if !tokens.consume_op(")") {
+        panic!("')' is not found");
+    }
+    if matches!(tokens.front(), Some(tk) if tk.kind == TokenKind::Reserved && tk.str == "{") {
+        let node = compound_literal(tokens, ty);
+        return postfix_ops(tokens, node);
+    }
+    Node {
+        loc,
+        ..Node::new_cast(cast(tokens), ty)
+    }
+}
+
+/// compound-literal = initializer
+/// 複合リテラルは、現在の関数のフレームに確保した名前のない変数として扱う。
+/// 値は初期化した変数そのものなので、左辺値として代入やアドレスの取得ができる。
+/// 要素数を省略した配列の型は、意味解析で初期化子を展開するときに決まる。
+fn compound_literal(tokens: &mut Tokens, ty: Type) -> Node {
+    if !tokens.vla_sizes.is_empty() {
+        panic!("可変長配列の複合リテラルは作れません");
+    }
+    let loc = tokens.loc();
+    let init = initializer(tokens);
+    if ty.is_aggregate() && ty.members.is_empty() || ty.kind == TypeKind::Void || ty.is_func() {
+        panic!("複合リテラルの型が不完全です");
+    }
+    // 識別子は空にならないため、名前のない変数が名前で見つかることはない
+    tokens.lvars.push(String::new(), Some(ty));
+    Node {
+        kind: NodeKind::Compound,
+        val: tokens.lvars.vec.len() as i64 - 1,
+        init: Some(Box::new(init)),
+        loc,
+        ..Node::default()
+    }
+}
+
+/// unary = ("+" | "-" | "*" | "&" | "!" | "__extension__") cast
+///       | "sizeof" "(" type-name ")"
+///       | "sizeof" unary
+///       | "_Alignof" "(" type-name ")"
+///       | postfix
 fn unary(tokens: &mut Tokens) -> Node {
-    if tokens.consume_op("+") {
-        primary(tokens)
+    let loc = tokens.loc();
+    if tokens.consume_op("+") || tokens.consume(TokenKind::Extension) {
+        cast(tokens)
     } else if tokens.consume_op("-") {
-        Node::new_op(NodeKind::Sub, Node::new_num(0), primary(tokens))
+        Node::new_op(NodeKind::Sub, Node::new_num(0), cast(tokens))
+    } else if tokens.consume_op("*") {
+        Node {
+            kind: NodeKind::Deref,
+            lhs: Some(Box::new(cast(tokens))),
+            loc,
+            ..Node::default()
+        }
+    } else if tokens.consume_op("&") {
+        Node {
+            kind: NodeKind::Addr,
+            lhs: Some(Box::new(cast(tokens))),
+            loc,
+            ..Node::default()
+        }
+    } else if tokens.consume_op("!") {
+        Node {
+            kind: NodeKind::Not,
+            lhs: Some(Box::new(cast(tokens))),
+            ..Node::default()
+        }
+    } else if tokens.consume(TokenKind::Sizeof) {
+        if is_paren_typename(tokens) {
+            tokens.consume_op("(");
+            let ty = vla_type_name(tokens);
+            if !tokens.consume_op(")") {
+                panic!("')' is not found");
+            }
+            if matches!(tokens.front(), Some(tk) if tk.kind == TokenKind::Reserved && tk.str == "{")
+            {
+                // sizeof (型名){...} は複合リテラルの大きさ
+                let node = compound_literal(tokens, ty);
+                return sizeof(postfix_ops(tokens, node), loc);
+            }
+            if !ty.is_vla() {
+                return Node::new_num(ty.size);
+            }
+            // 可変長配列の型の大きさは、実行時に計算する
+            let mut node = Node::default();
+            for size in std::mem::take(&mut tokens.vla_sizes) {
+                node = Node::new_op(NodeKind::Comma, node, size);
+            }
+            return Node::new_op(NodeKind::Comma, node, vla_size(&ty));
+        }
+        sizeof(unary(tokens), loc)
+    } else if tokens.consume(TokenKind::Alignof) {
+        if !tokens.consume_op("(") {
+            panic!("'(' is not found");
+        }
+        let ty = type_name(tokens);
+        if !tokens.consume_op(")") {
+            panic!("')' is not found");
+        }
+        Node::new_num(ty.align)
     } else {
-        primary(tokens)
+        postfix(tokens)
+    }
+}
+
+/// 式の型の大きさ。式の型は意味解析で決まるため、sizeofのノードを作っておく。
+fn sizeof(expr: Node, loc: Option<Location>) -> Node {
+    Node {
+        kind: NodeKind::Sizeof,
+        lhs: Some(Box::new(expr)),
+        loc,
+        ..Node::default()
+    }
+}
+
+/// postfix = primary postfix-op*
+fn postfix(tokens: &mut Tokens) -> Node {
+    let node = primary(tokens);
+    postfix_ops(tokens, node)
+}
+
+/// postfix-op = "(" func-args | "[" expr "]" | "." ident | "->" ident
+/// nodeに続く後置演算子を読み取る。
+fn postfix_ops(tokens: &mut Tokens, mut node: Node) -> Node {
+    loop {
+        let loc = tokens.loc();
+        if tokens.consume_op("(") {
+            node = Node {
+                kind: NodeKind::FuncCall,
+                lhs: Some(Box::new(node)),
+                args: func_args(tokens),
+                loc,
+                ..Node::default()
+            };
+        } else if tokens.consume_op("[") {
+            // x[y] は *(x+y) と同じ
+            let index = expr(tokens);
+            if !tokens.consume_op("]") {
+                panic!("']' is not found");
+            }
+            node = Node {
+                kind: NodeKind::Deref,
+                lhs: Some(Box::new(Node::new_op(NodeKind::Add, node, index))),
+                loc,
+                ..Node::default()
+            };
+        } else if tokens.consume_op(".") {
+            node = struct_ref(tokens, node);
+        } else if tokens.consume_op("->") {
+            // x->y は (*x).y と同じ
+            let deref = Node {
+                kind: NodeKind::Deref,
+                lhs: Some(Box::new(node)),
+                loc,
+                ..Node::default()
+            };
+            node = struct_ref(tokens, deref);
+        } else {
+            return node;
+        }
+    }
+}
+
+/// func-args = (assign ("," assign)*)? ")"
+fn func_args(tokens: &mut Tokens) -> Vec<Node> {
+    let mut args = Vec::new();
+    while !tokens.consume_op(")") {
+        if !args.is_empty() && !tokens.consume_op(",") {
+            panic!("引数の区切りには','が必要です");
+        }
+        args.push(assign(tokens));
+    }
+    args
+}
+
+/// 構造体・共用体lhsのメンバを参照するノードを作る。lhsの型は意味解析で決まるため、
+/// メンバのオフセットと型は意味解析で求める。
+fn struct_ref(tokens: &mut Tokens, lhs: Node) -> Node {
+    let loc = tokens.loc();
+    Node {
+        kind: NodeKind::Member,
+        label: expect_ident(&tokens.pop_front()),
+        lhs: Some(Box::new(lhs)),
+        loc,
+        ..Node::default()
     }
 }
 
-/// primary = "(" expr ")" | ident | num
+/// primary = "(" expr ")" | ident | num | string+ | va-builtin
+/// 列挙定数を指す識別子は、定数式で使えるようにその値の数値ノードになる。
+/// alloca(size)と可変長引数の__builtin_va_start, __builtin_va_argは組み込み関数として扱う。
+/// それ以外の識別子は、意味解析で変数を指すノードに置き換える。文字列リテラルは名前のない静的な配列を指す。
 fn primary(tokens: &mut Tokens) -> Node {
     if tokens.consume_op("(") {
         let node = expr(tokens);
         if !tokens.consume_op(")") {
             panic!("')' is not found");
         };
-        return node;
+        node
     } else if let Some(tk) = tokens.front() {
         match tk.kind {
             TokenKind::Ident => {
-                if let Some(lvar) = tokens.lvars.find(&tk.str) {
-                    let node = Node {
-                        kind: NodeKind::LVar,
-                        offset: lvar.offset,
-                        ..Node::default()
-                    };
+                if let Some(val) = tokens.lvars.find_enum_const(&tk.str) {
                     tokens.pop_front();
-                    return node;
+                    Node::new_num(val)
+                } else if tk.str == "__func__" && tokens.lvars.find(&tk.str).is_none() {
+                    // __func__は関数名の文字列として定義済みの識別子。トップレベルの文はmain関数の本体になる。
+                    let tk = tokens.pop_front().unwrap();
+                    let name = tokens.func.as_ref().map_or("main", |f| &f.name).to_string();
+                    Node {
+                        loc: Some(tk.loc),
+                        ..string_node(&mut tokens.lvars, name)
+                    }
+                } else if tk.str == "__builtin_va_start" || tk.str == "__builtin_va_arg" {
+                    va_builtin(tokens)
+                } else if tk.str == "alloca"
+                    && tokens.lvars.find(&tk.str).is_none()
+                    && tokens.get(1).is_some_and(|tk| tk.str == "(")
+                {
+                    // allocaはライブラリの関数ではなく、スタックを伸ばす組み込み関数として扱う
+                    tokens.pop_front();
+                    tokens.pop_front();
+                    let mut args = func_args(tokens);
+                    if args.len() != 1 {
+                        panic!("allocaの引数は1つでなければなりません");
+                    }
+                    let size = Node::new_cast(args.pop().unwrap(), Type::long().unsigned());
+                    Node {
+                        kind: NodeKind::Alloca,
+                        offset: tokens.lvars.alloca_bottom(),
+                        lhs: Some(Box::new(size)),
+                        ..Node::default()
+                    }
                 } else {
-                    let lvar = LVar {
-                        name: tk.str.clone(),
-                        offset: tokens.lvars.vec.len() as i64 * 8 + 8,
-                        len: tk.len,
-                    };
-                    let node = Node {
-                        kind: NodeKind::LVar,
-                        offset: lvar.offset,
+                    // 変数や関数の名前解決は意味解析で行う
+                    let tk = tokens.pop_front().unwrap();
+                    Node {
+                        kind: NodeKind::Ident,
+                        label: tk.str,
+                        loc: Some(tk.loc),
+                        scope: tokens.lvars.scope(),
                         ..Node::default()
-                    };
-                    tokens.lvars.vec.push(lvar);
-                    tokens.pop_front();
-                    return node;
+                    }
                 }
             }
-            TokenKind::Num => {
-                let node = Node::new_num(expect_number(&tokens.pop_front()));
-                return node;
+            TokenKind::Num | TokenKind::Float => {
+                let tk = tokens.pop_front().unwrap();
+                let node = match tk.kind {
+                    TokenKind::Num => int_literal(&tk),
+                    _ => float_literal(&tk),
+                };
+                Node {
+                    loc: Some(tk.loc),
+                    ..node
+                }
+            }
+            TokenKind::Str => {
+                let loc = tk.loc.clone();
+                let s = string_literal(tokens).unwrap();
+                Node {
+                    loc: Some(loc),
+                    ..string_node(&mut tokens.lvars, s)
+                }
             }
             _ => panic!("数でも識別子でもないトークンです: {}", tk.str),
         }
@@ -238,11 +2019,91 @@ fn primary(tokens: &mut Tokens) -> Node {
     }
 }
 
-fn expect_number(tk: &Option<Token>) -> i64 {
+/// va-builtin = "__builtin_va_start" "(" assign "," assign ")"
+///            | "__builtin_va_arg" "(" assign "," type-name ")"
+/// __builtin_va_startはva_listに、定義している関数の引数を保存した領域からva_listの初期値をコピーする。
+/// 可変長引数の関数の外では領域がないため、offsetを0にしておき意味解析でエラーにする。
+/// 2番目の引数の最後の引数の名前は、保存した領域から位置が分かるので使わない。
+fn va_builtin(tokens: &mut Tokens) -> Node {
+    let tk = tokens.pop_front().unwrap();
+    if !tokens.consume_op("(") {
+        panic!("'(' is not found");
+    }
+    let ap = assign(tokens);
+    if !tokens.consume_op(",") {
+        panic!("{}の引数は2つでなければなりません", tk.str);
+    }
+    let node = if tk.str == "__builtin_va_start" {
+        assign(tokens);
+        Node {
+            kind: NodeKind::VaStart,
+            offset: tokens.func.as_ref().and_then(|f| f.va_area).unwrap_or(0),
+            lhs: Some(Box::new(ap)),
+            loc: Some(tk.loc),
+            ..Node::default()
+        }
+    } else {
+        Node {
+            kind: NodeKind::VaArg,
+            lhs: Some(Box::new(ap)),
+            ty: Some(type_name(tokens)),
+            loc: Some(tk.loc),
+            ..Node::default()
+        }
+    };
+    if !tokens.consume_op(")") {
+        panic!("')' is not found");
+    }
+    node
+}
+
+/// 浮動小数点数のリテラルの数値ノードを作る。接尾辞がf/Fならfloat型、それ以外はdouble型になる。
+fn float_literal(tk: &Token) -> Node {
+    let (literal, ty) = match tk.str.strip_suffix(['f', 'F']) {
+        Some(literal) => (literal, Type::float()),
+        None => (tk.str.trim_end_matches(['l', 'L']), Type::double()),
+    };
+    let fval = match literal.parse() {
+        Ok(fval) => fval,
+        Err(_) => panic!("浮動小数点数ではありません: {}", tk.str),
+    };
+    Node {
+        kind: NodeKind::Num,
+        fval,
+        ty: Some(ty),
+        ..Node::default()
+    }
+}
+
+/// 整数リテラルのノードを作る。接尾辞がある場合は、接尾辞と値から型を決める。
+/// 8進数と16進数のリテラルも、10進数と同じ規則で型を決める。
+fn int_literal(tk: &Token) -> Node {
+    let suffix = tk.str.trim();
+    let node = Node::new_num(tk.val);
+    if suffix.is_empty() {
+        return node;
+    }
+    let ty = if suffix.contains('l') || u32::try_from(tk.val).is_err() {
+        Type::long()
+    } else {
+        Type::int()
+    };
+    let ty = if suffix.contains('u') {
+        ty.unsigned()
+    } else {
+        ty
+    };
+    Node {
+        ty: Some(ty),
+        ..node
+    }
+}
+
+fn expect_ident(tk: &Option<Token>) -> String {
     match tk {
         Some(v) => match v.kind {
-            TokenKind::Num => v.val,
-            _ => panic!("数ではありません: {}", v.str),
+            TokenKind::Ident => v.str.clone(),
+            _ => panic!("識別子ではありません: {}", v.str),
         },
         None => panic!("解析エラー"),
     }
@@ -250,9 +2111,11 @@ fn expect_number(tk: &Option<Token>) -> i64 {
 
 #[cfg(test)]
 mod tests {
-    use super::program;
-    use crate::parse::{Node, NodeKind};
+    use super::{const_expr, program};
+    use crate::parse::{Node, NodeKind, Reloc};
+    use crate::sema::analyze;
     use crate::tokenize::tokenize;
+    use crate::types::Type;
 
     #[test]
     fn check_ast_with_add() {
@@ -487,8 +2350,8 @@ mod tests {
             &Node {
                 kind: NodeKind::Assign,
                 lhs: Some(Box::new(Node {
-                    kind: NodeKind::LVar,
-                    offset: ('a' as i64 - 'a' as i64 + 1) * 8,
+                    kind: NodeKind::Ident,
+                    label: "a".to_string(),
                     ..Node::default()
                 })),
                 rhs: Some(Box::new(Node {
@@ -507,8 +2370,8 @@ mod tests {
             &Node {
                 kind: NodeKind::Assign,
                 lhs: Some(Box::new(Node {
-                    kind: NodeKind::LVar,
-                    offset: ('b' as i64 - 'a' as i64 + 1) * 8,
+                    kind: NodeKind::Ident,
+                    label: "b".to_string(),
                     ..Node::default()
                 })),
                 rhs: Some(Box::new(Node {
@@ -532,13 +2395,13 @@ mod tests {
             &Node {
                 kind: NodeKind::Mul,
                 lhs: Some(Box::new(Node {
-                    kind: NodeKind::LVar,
-                    offset: 8,
+                    kind: NodeKind::Ident,
+                    label: "a".to_string(),
                     ..Node::default()
                 })),
                 rhs: Some(Box::new(Node {
-                    kind: NodeKind::LVar,
-                    offset: 16,
+                    kind: NodeKind::Ident,
+                    label: "b".to_string(),
                     ..Node::default()
                 })),
                 ..Node::default()
@@ -598,8 +2461,8 @@ mod tests {
         let expected_1 = Node {
             kind: NodeKind::Assign,
             lhs: Some(Box::new(Node {
-                kind: NodeKind::LVar,
-                offset: 8,
+                kind: NodeKind::Ident,
+                label: "foo".to_string(),
                 ..Node::default()
             })),
             rhs: Some(Box::new(Node::new_num(3))),
@@ -608,8 +2471,8 @@ mod tests {
         let expected_2 = Node {
             kind: NodeKind::Assign,
             lhs: Some(Box::new(Node {
-                kind: NodeKind::LVar,
-                offset: 16,
+                kind: NodeKind::Ident,
+                label: "bar".to_string(),
                 ..Node::default()
             })),
             rhs: Some(Box::new(Node::new_num(1))),
@@ -620,13 +2483,13 @@ mod tests {
             lhs: Some(Box::new(Node {
                 kind: NodeKind::Add,
                 lhs: Some(Box::new(Node {
-                    kind: NodeKind::LVar,
-                    offset: 8,
+                    kind: NodeKind::Ident,
+                    label: "foo".to_string(),
                     ..Node::default()
                 })),
                 rhs: Some(Box::new(Node {
-                    kind: NodeKind::LVar,
-                    offset: 16,
+                    kind: NodeKind::Ident,
+                    label: "bar".to_string(),
                     ..Node::default()
                 })),
                 ..Node::default()
@@ -640,4 +2503,463 @@ mod tests {
             "`return foo + bar;` の得られたAST:\n{node_3:?}"
         );
     }
+
+    #[test]
+    fn check_ast_with_do_while() {
+        let mut tokens = tokenize("do continue; while (1); do 2; while (3);".to_string()).unwrap();
+        let nodes = program(&mut tokens);
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(
+            &nodes[0],
+            &Node {
+                kind: NodeKind::DoWhile,
+                val: 0,
+                lhs: Some(Box::new(Node {
+                    kind: NodeKind::Continue,
+                    val: 0,
+                    ..Node::default()
+                })),
+                rhs: Some(Box::new(Node::new_num(1))),
+                ..Node::default()
+            },
+            "`do continue; while (1);` の得られたAST:\n{:?}",
+            &nodes[0]
+        );
+        assert_eq!(
+            &nodes[1],
+            &Node {
+                kind: NodeKind::DoWhile,
+                val: 1,
+                lhs: Some(Box::new(Node::new_num(2))),
+                rhs: Some(Box::new(Node::new_num(3))),
+                ..Node::default()
+            },
+            "`do 2; while (3);` の得られたAST:\n{:?}",
+            &nodes[1]
+        );
+    }
+
+    #[test]
+    fn check_ast_with_struct_member() {
+        let mut tokens =
+            tokenize("struct P {char a; int b;}; struct P x, *p; x.b; p->a;".to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        // メンバのオフセットと型は、意味解析で構造体の型から求める
+        assert_eq!(nodes[2].kind, NodeKind::Member);
+        assert_eq!(nodes[2].label, "b");
+        assert_eq!(nodes[2].ty, None);
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(nodes[0], Node::default());
+        assert_eq!(nodes[1], Node::default());
+
+        let member = &nodes[2];
+        assert_eq!(member.kind, NodeKind::Member);
+        assert_eq!(member.offset, 4);
+        assert_eq!(member.ty, Some(Type::int()));
+        let lhs = member.lhs.as_ref().unwrap();
+        assert_eq!(lhs.kind, NodeKind::LVar);
+        assert_eq!(lhs.offset, 8);
+
+        let member = &nodes[3];
+        assert_eq!(member.kind, NodeKind::Member);
+        assert_eq!(member.offset, 0);
+        assert_eq!(member.ty, Some(Type::char()));
+        let deref = member.lhs.as_ref().unwrap();
+        assert_eq!(deref.kind, NodeKind::Deref);
+        assert_eq!(deref.lhs.as_ref().unwrap().offset, 16);
+    }
+
+    #[test]
+    fn check_ast_with_enum_constants() {
+        let mut tokens = tokenize("enum {A, B=5, C}; C - A;".to_string()).unwrap();
+        let nodes = program(&mut tokens);
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(
+            &nodes[1],
+            &Node::new_op(NodeKind::Sub, Node::new_num(6), Node::new_num(0)),
+            "`C - A;` の得られたAST:\n{:?}",
+            &nodes[1]
+        );
+    }
+
+    #[test]
+    fn check_ast_with_typedef_declaration() {
+        // `T * x;` はTが型名なので掛け算ではなく宣言になる
+        let mut tokens = tokenize("typedef int T; T * x; x;".to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[0], Node::default());
+        assert_eq!(nodes[1], Node::default());
+        assert_eq!(
+            &nodes[2],
+            &Node {
+                kind: NodeKind::LVar,
+                offset: 8,
+                ty: Some(Type::pointer_to(Type::int())),
+                ..Node::default()
+            },
+            "`x;` の得られたAST:\n{:?}",
+            &nodes[2]
+        );
+    }
+
+    #[test]
+    fn check_ast_without_typedef_declaration() {
+        // Tが型名でなければ掛け算になる
+        let mut tokens = tokenize("T * x;".to_string()).unwrap();
+        let nodes = program(&mut tokens);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].kind, NodeKind::Mul);
+    }
+
+    #[test]
+    fn check_ast_with_cast() {
+        let mut tokens = tokenize("(char)1; (int *)2; sizeof(int *);".to_string()).unwrap();
+        let nodes = program(&mut tokens);
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes[0], Node::new_cast(Node::new_num(1), Type::char()));
+        assert_eq!(
+            nodes[1],
+            Node::new_cast(Node::new_num(2), Type::pointer_to(Type::int()))
+        );
+        assert_eq!(nodes[2], Node::new_num(8));
+    }
+
+    #[test]
+    fn check_ast_with_float_literals() {
+        let mut tokens =
+            tokenize("1.5; 2.5f; (double)1; sizeof(long double);".to_string()).unwrap();
+        let nodes = program(&mut tokens);
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(
+            nodes[0],
+            Node {
+                kind: NodeKind::Num,
+                fval: 1.5,
+                ty: Some(Type::double()),
+                ..Node::default()
+            }
+        );
+        assert_eq!(
+            nodes[1],
+            Node {
+                kind: NodeKind::Num,
+                fval: 2.5,
+                ty: Some(Type::float()),
+                ..Node::default()
+            }
+        );
+        assert_eq!(nodes[2], Node::new_cast(Node::new_num(1), Type::double()));
+        assert_eq!(nodes[3], Node::new_num(16));
+    }
+
+    #[test]
+    fn check_ast_with_array_initializer() {
+        let mut tokens = tokenize("int a[2] = {[1] = 5};".to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        assert_eq!(nodes.len(), 1);
+        // 初期化子は、意味解析で変数の型に合わせて展開する
+        let init = nodes[0].rhs.as_ref().unwrap();
+        assert_eq!(init.kind, NodeKind::Init);
+        assert_eq!(init.val, 0);
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        let ty = Type::array_of(Type::int(), 2);
+        let zero = Node {
+            kind: NodeKind::MemZero,
+            offset: 8,
+            ty: Some(ty),
+            ..Node::default()
+        };
+        let elem = Node {
+            kind: NodeKind::LVar,
+            offset: 4,
+            ty: Some(Type::int()),
+            ..Node::default()
+        };
+        let Node { lhs, rhs, .. } = nodes[0].rhs.as_deref().unwrap();
+        assert_eq!(lhs.as_ref().unwrap().rhs.as_deref(), Some(&zero));
+        let assign = rhs.as_ref().unwrap();
+        assert_eq!(assign.kind, NodeKind::Assign);
+        assert_eq!(**assign.lhs.as_ref().unwrap(), elem);
+        assert_eq!(assign.rhs.as_ref().unwrap().lhs.as_ref().unwrap().val, 5);
+
+        // 要素数を省略した配列は、初期化子から決まった大きさの領域を確保する
+        let mut tokens = tokenize("char c; int a[] = {1, 2, 3};".to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        let a = tokens.lvars.find("a").unwrap();
+        assert_eq!(a.ty, Some(Type::array_of(Type::int(), 3)));
+        assert_eq!(a.offset, 16);
+    }
+
+    #[test]
+    fn check_ast_with_static_variables() {
+        let mut tokens =
+            tokenize("static short a[] = {1, 258}; extern int b; a; b;".to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(nodes[0].rhs.as_deref(), Some(&Node::default()));
+        assert_eq!(nodes[1], Node::default());
+        let ty = Type::array_of(Type::short(), 2);
+        assert_eq!(
+            nodes[2],
+            Node {
+                kind: NodeKind::GVar,
+                label: "a.0".to_string(),
+                ty: Some(ty.clone()),
+                ..Node::default()
+            }
+        );
+        assert_eq!(nodes[3].label, "b");
+
+        let gvars = &tokens.lvars.gvars;
+        assert_eq!(gvars[0].ty, ty);
+        assert_eq!(gvars[0].data, Some(vec![1, 0, 2, 1]));
+        assert_eq!(gvars[1].data, None);
+        assert!(tokens.lvars.vec.is_empty());
+
+        // 文字列リテラルは名前のない静的な配列になり、隣り合うものはつなげる
+        let mut tokens = tokenize("\"ab\" \"c\";".to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        let ty = Type::array_of(Type::char(), 4);
+        assert_eq!(nodes[0].kind, NodeKind::GVar);
+        assert_eq!(nodes[0].ty, Some(ty.clone()));
+        assert_eq!(tokens.lvars.gvars[0].label, nodes[0].label);
+        assert_eq!(tokens.lvars.gvars[0].ty, ty);
+        assert_eq!(tokens.lvars.gvars[0].data, Some(b"abc\0".to_vec()));
+
+        // 静的変数のアドレスは、ラベルと足すバイト数として記録する
+        let input =
+            "static int a[3]; static struct { char c; int *p[2]; } s = {1, {a + 2, &a[1]}};";
+        let mut tokens = tokenize(input.to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        let gvars = &tokens.lvars.gvars;
+        assert_eq!(gvars[1].data.as_ref().unwrap()[0], 1);
+        assert_eq!(
+            gvars[1].relocs,
+            vec![
+                Reloc {
+                    offset: 8,
+                    label: "a.0".to_string(),
+                    addend: 8
+                },
+                Reloc {
+                    offset: 16,
+                    label: "a.0".to_string(),
+                    addend: 4
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn check_ast_with_qualifiers() {
+        let mut tokens = tokenize("const char * volatile p; int const x;".to_string()).unwrap();
+        program(&mut tokens);
+        let base = Type {
+            is_const: true,
+            ..Type::char()
+        };
+        let p = Type {
+            is_volatile: true,
+            ..Type::pointer_to(base)
+        };
+        assert_eq!(tokens.lvars.find("p").unwrap().ty, Some(p));
+        let x = Type {
+            is_const: true,
+            ..Type::int()
+        };
+        assert_eq!(tokens.lvars.find("x").unwrap().ty, Some(x));
+    }
+
+    #[test]
+    fn check_ast_with_function_pointer() {
+        let mut tokens =
+            tokenize("int (*fp[2])(int, char *, ...); fp[1](1, 2);".to_string()).unwrap();
+        let nodes = program(&mut tokens);
+        let func = Type::func(
+            Type::int(),
+            vec![Type::int(), Type::pointer_to(Type::char())],
+            true,
+        );
+        assert_eq!(
+            tokens.lvars.find("fp").unwrap().ty,
+            Some(Type::array_of(Type::pointer_to(func), 2))
+        );
+        assert_eq!(nodes[1].kind, NodeKind::FuncCall);
+        assert_eq!(nodes[1].args, vec![Node::new_num(1), Node::new_num(2)]);
+        assert_eq!(nodes[1].lhs.as_ref().unwrap().kind, NodeKind::Deref);
+    }
+
+    #[test]
+    fn check_ast_with_function_definition() {
+        let input = "int x; int f(int a, char *b, ...) { int c; return a; }\n\
+                     inline int g(void) { return 1; } inline int h(void) { return 2; } h();";
+        let mut tokens = tokenize(input.to_string()).unwrap();
+        let nodes = program(&mut tokens);
+        assert_eq!(nodes.len(), 5);
+        assert_eq!(tokens.lvars.vec.len(), 1);
+        let f = &tokens.lvars.funcs[0];
+        let names: Vec<_> = f.vars.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "", "c"]);
+        assert_eq!(f.va_area, Some(f.vars[2].offset));
+        assert_eq!(f.body[1].kind, NodeKind::Return);
+        // 後で使われないinline関数は定義しない
+        assert_eq!(tokens.lvars.funcs.len(), 2);
+        assert_eq!(tokens.lvars.funcs[1].name, "h");
+        assert!(tokens.lvars.find_gvar("g").unwrap().ty.is_func());
+
+        // 構造体を返す関数では、戻り値を書き込む先のアドレスを保存する変数を引数の後に確保する
+        let input = "struct P { long a, b, c; }; struct P f(struct P p) { return p; }";
+        let mut tokens = tokenize(input.to_string()).unwrap();
+        program(&mut tokens);
+        let f = &tokens.lvars.funcs[0];
+        assert_eq!(f.ret_ptr, Some(f.vars[1].offset));
+        assert_eq!(f.body[0].offset, f.vars[1].offset);
+        assert!(!f.is_static);
+
+        let mut tokens = tokenize("static int f(void) { return 1; }".to_string()).unwrap();
+        program(&mut tokens);
+        assert!(tokens.lvars.funcs[0].is_static);
+    }
+
+    #[test]
+    fn check_ast_with_logical_operators() {
+        let mut tokens = tokenize("1 || !2 && 3;".to_string()).unwrap();
+        let nodes = program(&mut tokens);
+        let not = Node {
+            kind: NodeKind::Not,
+            lhs: Some(Box::new(Node::new_num(2))),
+            ..Node::default()
+        };
+        let and = Node {
+            val: 1,
+            ..Node::new_op(NodeKind::LogAnd, not, Node::new_num(3))
+        };
+        let or = Node {
+            val: 0,
+            ..Node::new_op(NodeKind::LogOr, Node::new_num(1), and)
+        };
+        assert_eq!(nodes, vec![or]);
+    }
+
+    #[test]
+    fn check_ast_with_compound_literal() {
+        let mut tokens = tokenize("(int){3}; _Alignof(long);".to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        assert_eq!(nodes[0].kind, NodeKind::Compound);
+        assert_eq!(nodes[1], Node::new_num(8));
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        let var = Node {
+            kind: NodeKind::LVar,
+            offset: 4,
+            ty: Some(Type::int()),
+            ..Node::default()
+        };
+        let Node { kind, lhs, rhs, .. } = &nodes[0];
+        assert_eq!(*kind, NodeKind::Comma);
+        assert_eq!(**rhs.as_ref().unwrap(), var);
+        let assign = lhs.as_ref().unwrap().rhs.as_ref().unwrap();
+        assert_eq!(assign.kind, NodeKind::Assign);
+        assert_eq!(**assign.lhs.as_ref().unwrap(), var);
+    }
+
+    #[test]
+    fn check_ast_with_alignas() {
+        let mut tokens = tokenize("char a; _Alignas(8) char b; b;".to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        assert_eq!(nodes[2].offset, 8);
+        assert_eq!(nodes[2].ty.as_ref().unwrap().align, 8);
+        assert_eq!(tokens.lvars.frame_align(), 16);
+
+        let mut tokens = tokenize("char a; _Alignas(64) int b;".to_string()).unwrap();
+        program(&mut tokens);
+        assert_eq!(tokens.lvars.find("b").unwrap().offset, 64);
+        assert_eq!(tokens.lvars.frame_align(), 64);
+    }
+
+    #[test]
+    fn check_ast_with_vla() {
+        let mut tokens = tokenize("long n; char a[n]; sizeof a;".to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        // n, 大きさを持つ変数, allocaの領域の底, a の順に確保される
+        let size = || Node {
+            kind: NodeKind::LVar,
+            offset: 16,
+            ty: Some(Type::long()),
+            ..Node::default()
+        };
+        assert_eq!(tokens.lvars.alloca_bottom, Some(24));
+        assert_eq!(tokens.lvars.find("a").unwrap().offset, 32);
+        let Node { lhs, rhs, .. } = &nodes[1];
+        let alloca = rhs.as_ref().unwrap().rhs.as_ref().unwrap();
+        assert_eq!(alloca.kind, NodeKind::Alloca);
+        assert_eq!(alloca.offset, 24);
+        assert_eq!(**alloca.lhs.as_ref().unwrap(), size());
+        assert_eq!(
+            lhs.as_ref().unwrap().rhs.as_ref().unwrap().kind,
+            NodeKind::Assign
+        );
+        // 可変長配列の大きさは、大きさを持つ変数から読む
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        assert_eq!(nodes[2], size());
+
+        // offsetofと同じ形の、定数のアドレスから求めたメンバの位置は定数式になる
+        let input = "struct S { char c; int a[3]; }; char b[(long)&((struct S *)0)->a[2]];";
+        let mut tokens = tokenize(input.to_string()).unwrap();
+        program(&mut tokens);
+        assert_eq!(
+            tokens.lvars.find("b").unwrap().ty,
+            Some(Type::array_of(Type::char(), 12))
+        );
+    }
+
+    #[test]
+    fn check_ast_with_conditional() {
+        let mut tokens = tokenize("1 ? 2 : 3 ? 4 : 5;".to_string()).unwrap();
+        let nodes = program(&mut tokens);
+        let Node {
+            kind, cond, rhs, ..
+        } = &nodes[0];
+        assert_eq!(*kind, NodeKind::Cond);
+        assert_eq!(**cond.as_ref().unwrap(), Node::new_num(1));
+        assert_eq!(rhs.as_ref().unwrap().kind, NodeKind::Cond);
+
+        let mut tokens = tokenize("2 * 3 > 5 ? 1 << 4 : 0".to_string()).unwrap();
+        assert_eq!(const_expr(&mut tokens), 16);
+
+        // 符号なし整数型の割り算と比較
+        let mut tokens = tokenize("(unsigned long)-1 / 2".to_string()).unwrap();
+        assert_eq!(const_expr(&mut tokens), i64::MAX);
+        let mut tokens = tokenize("(unsigned long)-1 / 2 > 0".to_string()).unwrap();
+        assert_eq!(const_expr(&mut tokens), 1);
+        let mut tokens = tokenize("-1 < 0u".to_string()).unwrap();
+        assert_eq!(const_expr(&mut tokens), 0);
+
+        // 符号なし整数型の右シフトは論理シフトになる
+        let mut tokens = tokenize("1UL << 63 >> 63".to_string()).unwrap();
+        assert_eq!(const_expr(&mut tokens), 1);
+        let mut tokens = tokenize("(1L << 63) >> 63".to_string()).unwrap();
+        assert_eq!(const_expr(&mut tokens), -1);
+    }
+
+    #[test]
+    fn check_ast_with_gnu_extensions() {
+        let input = "__extension__ typedef long __attribute__((aligned(8))) L;\n\
+                     extern int f(int *__restrict p) __asm__(\"g\" \"1\") __attribute__((pure));\n\
+                     static inline int f(int *p) { return *p; }\n\
+                     typedef long L; __builtin_va_list ap; __extension__ sizeof(ap);";
+        let mut tokens = tokenize(input.to_string()).unwrap();
+        let mut nodes = program(&mut tokens);
+        analyze(&mut nodes, &mut tokens.lvars).unwrap();
+        assert_eq!(tokens.lvars.find_gvar("f").unwrap().label, "g1");
+        assert_eq!(tokens.lvars.find_typedef("L"), Some(&Type::long()));
+        assert_eq!(nodes.last().unwrap().val, 24);
+    }
 }
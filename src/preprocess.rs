@@ -0,0 +1,1167 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::tokenize::{tokenize, tokenize_file, Token, TokenKind, Tokens, INPUT_NAME};
+
+/// インクルードの入れ子の深さの上限。自分自身をインクルードし続けるファイルを検出するため。
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// システムのヘッダファイルを探すディレクトリ。組み込みのヘッダファイルの後に探す。
+const SYSTEM_INCLUDE_PATHS: [&str; 3] = [
+    "/usr/local/include",
+    "/usr/include/x86_64-linux-gnu",
+    "/usr/include",
+];
+
+/// コンパイラに組み込んだヘッダファイルの名前と中身。-Iで指定されたディレクトリの後に探す。
+const BUILTIN_HEADERS: [(&str, &str); 4] = [
+    ("stddef.h", include_str!("../include/stddef.h")),
+    ("stdarg.h", include_str!("../include/stdarg.h")),
+    ("stdbool.h", include_str!("../include/stdbool.h")),
+    ("stdint.h", include_str!("../include/stdint.h")),
+];
+
+/// 組み込みのヘッダファイルを置いているものとして扱うディレクトリの名前
+const BUILTIN_DIR: &str = "<built-in>";
+
+/// 定義済みのオブジェクト形式マクロの名前と置換リスト。x86-64のLinuxを対象とする。
+const PREDEFINED_MACROS: [(&str, &str); 32] = [
+    ("__STDC__", "1"),
+    ("__STDC_VERSION__", "201112L"),
+    ("__STDC_HOSTED__", "1"),
+    ("__STDC_NO_ATOMICS__", "1"),
+    ("__STDC_NO_COMPLEX__", "1"),
+    ("__STDC_NO_THREADS__", "1"),
+    ("__x86_64", "1"),
+    ("__x86_64__", "1"),
+    ("__amd64", "1"),
+    ("__amd64__", "1"),
+    ("__linux", "1"),
+    ("__linux__", "1"),
+    ("__gnu_linux__", "1"),
+    ("__unix", "1"),
+    ("__unix__", "1"),
+    ("__ELF__", "1"),
+    ("_LP64", "1"),
+    ("__LP64__", "1"),
+    ("__CHAR_BIT__", "8"),
+    ("__SIZEOF_SHORT__", "2"),
+    ("__SIZEOF_INT__", "4"),
+    ("__SIZEOF_LONG__", "8"),
+    ("__SIZEOF_LONG_LONG__", "8"),
+    ("__SIZEOF_POINTER__", "8"),
+    ("__SIZEOF_FLOAT__", "4"),
+    ("__SIZEOF_DOUBLE__", "8"),
+    ("__SIZEOF_LONG_DOUBLE__", "8"),
+    ("__SIZE_TYPE__", "unsigned long"),
+    ("__PTRDIFF_TYPE__", "long"),
+    ("__WCHAR_TYPE__", "int"),
+    ("__ORDER_LITTLE_ENDIAN__", "1234"),
+    ("__BYTE_ORDER__", "__ORDER_LITTLE_ENDIAN__"),
+];
+
+/// 条件付き取り込み(#if ... #endif)の状態
+struct CondIncl {
+    in_else: bool,  // #elseの後にいる場合に真
+    included: bool, // いずれかの分岐をすでに取り込んだ場合に真
+}
+
+/// マクロの定義
+enum Macro {
+    /// オブジェクト形式マクロ。置換リストを持つ。
+    Object(Vec<Token>),
+    /// 関数形式マクロ。仮引数の名前と、可変長引数(...)を取るかどうかと、置換リストを持つ。
+    Function {
+        params: Vec<String>,
+        is_variadic: bool,
+        body: Vec<Token>,
+    },
+    /// __FILE__や__LINE__のように、使った位置によって置き換える結果が変わる組み込みマクロ
+    Builtin(fn(&Token) -> Token),
+}
+
+struct Preprocessor {
+    macros: HashMap<String, Macro>, // マクロの名前と定義
+    include_paths: Vec<PathBuf>,    // #includeでファイルを探すディレクトリ
+    pragma_once: HashSet<PathBuf>,  // #pragma onceが書かれた、2度目以降はインクルードしないファイル
+}
+
+/// トークン列を前処理する。ディレクティブを処理し、マクロを展開したトークン列を返す。
+/// include_pathsは-Iで指定された、インクルードファイルを探すディレクトリ。
+pub fn preprocess(mut tokens: Tokens, include_paths: &[String]) -> Tokens {
+    let mut pp = Preprocessor {
+        macros: HashMap::new(),
+        include_paths: include_paths.iter().map(PathBuf::from).collect(),
+        pragma_once: HashSet::new(),
+    };
+    pp.define_builtin_macros();
+    let mut out = VecDeque::new();
+    pp.run(std::mem::take(&mut tokens.tokens), &mut out, 0);
+    tokens.tokens = out;
+    tokens
+}
+
+/// -Eの出力として、前処理したトークン列をソースコードのテキストに戻す。
+/// 行番号はできるだけ元のソースコードに合わせ、ファイルが変わる位置と行が大きく飛ぶ位置には
+/// 行マーカー(# 行番号 "ファイル名")を入れる。
+pub fn format_tokens(tokens: &Tokens) -> String {
+    let mut out = String::new();
+    let mut prev: Option<&Token> = None;
+    for tk in &tokens.tokens {
+        let loc = &tk.loc;
+        match prev.map(|prev| &prev.loc) {
+            Some(prev_loc) if prev_loc.file == loc.file && prev_loc.line == loc.line => {
+                // 空白を挟まないと1つのトークンとして読まれてしまう場合にも空白を入れる
+                if loc.has_space || prev.is_some_and(|prev| is_word(prev) && is_word(tk)) {
+                    out.push(' ');
+                }
+            }
+            Some(prev_loc)
+                if prev_loc.file == loc.file
+                    && prev_loc.line < loc.line
+                    && loc.line - prev_loc.line <= 8 =>
+            {
+                out.push_str(&"\n".repeat(loc.line - prev_loc.line));
+            }
+            _ => {
+                if prev.is_some() {
+                    out.push('\n');
+                }
+                let file = Token::new_str(loc.file.to_string()).unwrap();
+                out.push_str(&format!("# {} {}\n", loc.line, file.text()));
+            }
+        }
+        out.push_str(&tk.text());
+        prev = Some(tk);
+    }
+    out
+}
+
+/// 識別子か予約語か数値かどうか。続けて書くと1つのトークンになってしまう。
+fn is_word(tk: &Token) -> bool {
+    tk.kind != TokenKind::Reserved && tk.kind != TokenKind::Str
+}
+
+/// 識別子か予約語かどうか。プリプロセッサは予約語も識別子として扱う。
+fn is_ident(tk: &Token) -> bool {
+    !matches!(
+        tk.kind,
+        TokenKind::Reserved | TokenKind::Num | TokenKind::Float | TokenKind::Str | TokenKind::Stray
+    )
+}
+
+/// 記号opのトークンかどうか
+fn is_op(tk: &Token, op: &str) -> bool {
+    tk.kind == TokenKind::Reserved && tk.str == op
+}
+
+/// 行頭の '#' はディレクティブの始まり。マクロを展開してできた '#' は除く。
+fn is_hash(tk: Option<&Token>) -> bool {
+    tk.is_some_and(|tk| tk.loc.at_bol && tk.hideset.is_empty() && is_op(tk, "#"))
+}
+
+/// 現在の行の残りのトークンを読み取る
+fn read_line(tokens: &mut VecDeque<Token>) -> Vec<Token> {
+    let mut line = Vec::new();
+    while tokens.front().is_some_and(|tk| !tk.loc.at_bol) {
+        line.push(tokens.pop_front().unwrap());
+    }
+    line
+}
+
+/// ディレクティブの名前の後に、識別子が1つだけ書かれていることを確かめてその名前を返す。
+fn expect_macro_name(directive: &str, line: Vec<Token>) -> String {
+    match &line[..] {
+        [tk] if is_ident(tk) => tk.str.clone(),
+        [tk, ..] if is_ident(tk) => panic!("#{directive}の後に余分なトークンがあります"),
+        _ => panic!("#{directive}にはマクロ名が必要です"),
+    }
+}
+
+/// #define F(a, b, ...) の '(' より後ろの仮引数リストを読み取り、仮引数の名前と
+/// 可変長引数を取るかどうかを返す。
+fn read_macro_params(line: &mut VecDeque<Token>, name: &str) -> (Vec<String>, bool) {
+    let mut params = Vec::new();
+    loop {
+        let tk = match line.pop_front() {
+            Some(tk) => tk,
+            None => panic!("マクロ{name}の仮引数リストが閉じられていません"),
+        };
+        if params.is_empty() && is_op(&tk, ")") {
+            return (params, false);
+        }
+        if is_op(&tk, "...") {
+            if line.pop_front().is_none_or(|tk| !is_op(&tk, ")")) {
+                panic!("マクロ{name}の'...'の後には')'が必要です");
+            }
+            return (params, true);
+        }
+        if !is_ident(&tk) || tk.str == "__VA_ARGS__" {
+            panic!("マクロ{name}の仮引数には識別子が必要です");
+        }
+        if params.contains(&tk.str) {
+            panic!("マクロ{name}の仮引数{}が重複しています", tk.str);
+        }
+        params.push(tk.str);
+        match line.pop_front() {
+            Some(tk) if is_op(&tk, ")") => return (params, false),
+            Some(tk) if is_op(&tk, ",") => {}
+            _ => panic!("マクロ{name}の仮引数リストが閉じられていません"),
+        }
+    }
+}
+
+/// 関数形式マクロの呼び出しの '(' より後ろの実引数を読み取り、実引数ごとのトークン列と
+/// 閉じる ')' のトークンを返す。可変長引数の部分はカンマを含めて1つの実引数にまとめる。
+fn read_macro_args(
+    tokens: &mut VecDeque<Token>,
+    name: &str,
+    nparams: usize,
+    is_variadic: bool,
+) -> (Vec<Vec<Token>>, Token) {
+    let mut args = Vec::new();
+    let mut arg = Vec::new();
+    let mut depth = 0;
+    let rparen = loop {
+        let tk = match tokens.pop_front() {
+            Some(tk) => tk,
+            None => panic!("マクロ{name}の実引数が閉じられていません"),
+        };
+        if is_op(&tk, ")") && depth == 0 {
+            args.push(arg);
+            break tk;
+        }
+        if is_op(&tk, ",") && depth == 0 && !(is_variadic && args.len() == nparams) {
+            args.push(std::mem::take(&mut arg));
+            continue;
+        }
+        if is_op(&tk, "(") {
+            depth += 1;
+        } else if is_op(&tk, ")") {
+            depth -= 1;
+        }
+        arg.push(tk);
+    };
+
+    // 仮引数のないマクロは F() と呼び出す
+    if nparams == 0 && !is_variadic && args.len() == 1 && args[0].is_empty() {
+        args.clear();
+    }
+    // 可変長引数は省略できる
+    if is_variadic && args.len() == nparams {
+        args.push(Vec::new());
+    }
+    if args.len() != nparams + is_variadic as usize {
+        panic!("マクロ{name}の実引数の数が正しくありません");
+    }
+    (args, rparen)
+}
+
+/// トークン列の綴りを、間の空白を1つに詰めてつなげる
+fn join_text(tokens: &[Token]) -> String {
+    let mut s = String::new();
+    for (i, tk) in tokens.iter().enumerate() {
+        if i > 0 && tk.loc.has_space {
+            s.push(' ');
+        }
+        s.push_str(&tk.text());
+    }
+    s
+}
+
+/// #演算子。実引数の綴りを空白を1つに詰めてつなげ、文字列リテラルにする。
+fn stringize(arg: &[Token]) -> Token {
+    Token::new_str(join_text(arg)).unwrap()
+}
+
+/// #includeしたファイルを#pragma onceで区別するためのパス。同じファイルを別の経路で指しても同じになる。
+fn canonical_path(path: &str) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// ##演算子。2つのトークンの綴りをつなげ、1つのトークンとして読み直す。
+fn paste(lhs: &Token, rhs: &Token) -> Token {
+    let text = lhs.text() + &rhs.text();
+    let mut tokens = match tokenize_file(text.clone(), &lhs.loc.file) {
+        Ok(tokens) => tokens.tokens,
+        Err(e) => panic!("{e}"),
+    };
+    if tokens.len() != 1 {
+        panic!("'##'でつなげた{text}は1つのトークンになりません");
+    }
+    let mut tk = tokens.pop_front().unwrap();
+    tk.loc = lhs.loc.clone();
+    tk.hideset = lhs.hideset.clone();
+    tk
+}
+
+/// トークン列の各トークンの展開禁止集合にhidesetを加える
+fn add_hideset(tokens: &mut [Token], hideset: &[String]) {
+    for tk in tokens {
+        for name in hideset {
+            if !tk.hideset.contains(name) {
+                tk.hideset.push(name.clone());
+            }
+        }
+    }
+}
+
+/// ファイルを読み込み、そのパスと中身を返す
+fn read_file(path: &Path) -> (String, String) {
+    match std::fs::read_to_string(path) {
+        Ok(input) => (path.to_string_lossy().into_owned(), input),
+        Err(e) => panic!("ファイルを読み込めません: {}: {e}", path.display()),
+    }
+}
+
+/// #line 行番号 "ファイル名" を処理する。ディレクティブの次の行が指定した行番号になるように、
+/// 同じファイルの残りのトークンの位置を書き換える。ファイル名があれば、位置のファイル名も置き換える。
+fn set_line(tokens: &mut VecDeque<Token>, hash: &Token, line: &[Token]) {
+    let (num, file) = match line {
+        [num] if num.kind == TokenKind::Num => (num, None),
+        [num, file, ..] if num.kind == TokenKind::Num && file.kind == TokenKind::Str => {
+            (num, Some(Rc::from(file.str.as_str())))
+        }
+        _ => panic!("#lineには行番号が必要です"),
+    };
+    let next_line = hash.loc.line + 1;
+    for tk in tokens.iter_mut().filter(|tk| tk.loc.file == hash.loc.file) {
+        tk.loc.line = (tk.loc.line + num.val as usize).saturating_sub(next_line);
+        if let Some(file) = &file {
+            tk.loc.file = Rc::clone(file);
+        }
+    }
+}
+
+/// 条件が偽の分岐を読み飛ばす。入れ子の#if ... #endifは丸ごと読み飛ばし、
+/// 同じ深さの#elif, #else, #endifの直前で止まる。
+fn skip_cond_incl(tokens: &mut VecDeque<Token>) {
+    let mut depth = 0;
+    while let Some(tk) = tokens.front() {
+        if is_hash(Some(tk)) {
+            match tokens
+                .get(1)
+                .filter(|tk| !tk.loc.at_bol)
+                .map(|tk| tk.str.as_str())
+            {
+                Some("if" | "ifdef" | "ifndef") => depth += 1,
+                Some("elif" | "else") if depth == 0 => return,
+                Some("endif") if depth == 0 => return,
+                Some("endif") => depth -= 1,
+                _ => {}
+            }
+        }
+        tokens.pop_front();
+    }
+}
+
+impl Preprocessor {
+    /// 定義済みマクロを登録する
+    fn define_builtin_macros(&mut self) {
+        self.macros.insert(
+            "__FILE__".to_string(),
+            Macro::Builtin(|tk| Token::new_str(tk.loc.file.to_string()).unwrap()),
+        );
+        self.macros.insert(
+            "__LINE__".to_string(),
+            Macro::Builtin(|tk| Token::new_num(tk.loc.line as i64).unwrap()),
+        );
+        for (name, body) in PREDEFINED_MACROS {
+            let body = match tokenize(body.to_string()) {
+                Ok(body) => body.tokens.into(),
+                Err(e) => panic!("{e}"),
+            };
+            self.macros.insert(name.to_string(), Macro::Object(body));
+        }
+    }
+
+    /// 1つのファイルのトークン列を前処理し、結果をoutに追加する。depthはインクルードの深さ。
+    fn run(&mut self, mut tokens: VecDeque<Token>, out: &mut VecDeque<Token>, depth: usize) {
+        let mut conds: Vec<CondIncl> = Vec::new();
+        while let Some(tk) = tokens.pop_front() {
+            if !is_hash(Some(&tk)) {
+                if tk.kind == TokenKind::Stray {
+                    match tk.str.as_str() {
+                        "'" => panic!("{}: 文字リテラルが閉じられていません", tk.loc),
+                        _ => panic!("{}: 文字列リテラルが閉じられていません", tk.loc),
+                    }
+                }
+                if !self.expand_macro(&tk, &mut tokens) {
+                    out.push_back(tk);
+                }
+                continue;
+            }
+            // '#' だけの行は何もしない
+            if tokens.front().is_none_or(|tk| tk.loc.at_bol) {
+                continue;
+            }
+            let directive = tokens.pop_front().unwrap();
+            match directive.str.as_str() {
+                "include" => {
+                    // "ファイル名"か<ファイル名>の形でなければ、マクロを展開してから読む(C11 6.10.2p4)
+                    let mut line = read_line(&mut tokens);
+                    if !line
+                        .first()
+                        .is_some_and(|tk| tk.kind == TokenKind::Str || tk.str == "<")
+                    {
+                        line = self.expand_all(line);
+                    }
+                    let (path, input) = self.read_include(&line, &tk.loc.file);
+                    if self.pragma_once.contains(&canonical_path(&path)) {
+                        continue;
+                    }
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        panic!("インクルードの入れ子が深すぎます: {path}");
+                    }
+                    let included = match tokenize_file(input, &path) {
+                        Ok(included) => included,
+                        Err(e) => panic!("{e}"),
+                    };
+                    self.run(included.tokens, out, depth + 1);
+                }
+                "define" => {
+                    let mut line = VecDeque::from(read_line(&mut tokens));
+                    let name = match line.pop_front() {
+                        Some(tk) if is_ident(&tk) => tk.str,
+                        _ => panic!("#defineにはマクロ名が必要です"),
+                    };
+                    // マクロ名の直後に空白を挟まずに '(' が続く場合は関数形式マクロ
+                    let m = if line
+                        .front()
+                        .is_some_and(|tk| !tk.loc.has_space && is_op(tk, "("))
+                    {
+                        line.pop_front();
+                        let (params, is_variadic) = read_macro_params(&mut line, &name);
+                        Macro::Function {
+                            params,
+                            is_variadic,
+                            body: line.into(),
+                        }
+                    } else {
+                        Macro::Object(line.into())
+                    };
+                    self.macros.insert(name, m);
+                }
+                "undef" => {
+                    let name = expect_macro_name("undef", read_line(&mut tokens));
+                    self.macros.remove(&name);
+                }
+                "if" => {
+                    let included = self.eval_cond(read_line(&mut tokens));
+                    conds.push(CondIncl {
+                        in_else: false,
+                        included,
+                    });
+                    if !included {
+                        skip_cond_incl(&mut tokens);
+                    }
+                }
+                "ifdef" | "ifndef" => {
+                    let name = expect_macro_name(&directive.str, read_line(&mut tokens));
+                    let included = self.macros.contains_key(&name) == (directive.str == "ifdef");
+                    conds.push(CondIncl {
+                        in_else: false,
+                        included,
+                    });
+                    if !included {
+                        skip_cond_incl(&mut tokens);
+                    }
+                }
+                "elif" => {
+                    let line = read_line(&mut tokens);
+                    let cond = match conds.last_mut() {
+                        Some(cond) if !cond.in_else => cond,
+                        _ => panic!("対応する#ifのない#elifです"),
+                    };
+                    // すでに取り込んだ分岐がある場合は、条件を評価せずに読み飛ばす
+                    if !cond.included && self.eval_cond(line) {
+                        cond.included = true;
+                    } else {
+                        skip_cond_incl(&mut tokens);
+                    }
+                }
+                "else" => {
+                    read_line(&mut tokens);
+                    let cond = match conds.last_mut() {
+                        Some(cond) if !cond.in_else => cond,
+                        _ => panic!("対応する#ifのない#elseです"),
+                    };
+                    cond.in_else = true;
+                    if cond.included {
+                        skip_cond_incl(&mut tokens);
+                    }
+                    cond.included = true;
+                }
+                "endif" => {
+                    read_line(&mut tokens);
+                    if conds.pop().is_none() {
+                        panic!("対応する#ifのない#endifです");
+                    }
+                }
+                "pragma" => {
+                    // #pragma once以外のプラグマは無視する
+                    let line = read_line(&mut tokens);
+                    if line.first().is_some_and(|tk| tk.str == "once") {
+                        self.pragma_once.insert(canonical_path(&tk.loc.file));
+                    }
+                }
+                "error" => {
+                    let line = read_line(&mut tokens);
+                    eprintln!("{}: #error {}", tk.loc, join_text(&line));
+                    std::process::exit(1);
+                }
+                "line" => {
+                    let line = self.expand_all(read_line(&mut tokens));
+                    set_line(&mut tokens, &tk, &line);
+                }
+                // 行マーカー(# 行番号 "ファイル名")は#lineと同じように扱う
+                _ if directive.kind == TokenKind::Num => {
+                    let mut line = read_line(&mut tokens);
+                    line.insert(0, directive);
+                    set_line(&mut tokens, &tk, &line);
+                }
+                name => panic!("不明なディレクティブです: #{name}"),
+            }
+        }
+        if !conds.is_empty() {
+            panic!("#endifがありません");
+        }
+    }
+
+    /// tkがマクロ名なら、その展開結果をtokensの先頭に戻して真を返す。戻したトークンは
+    /// 呼び出し側で改めて読むことで再走査される。マクロでない場合と、関数形式マクロの名前の後に
+    /// '(' が続かない場合は偽を返す。
+    /// 展開したトークンの展開禁止集合(hideset)にはマクロ名を加え、同じマクロを再帰的に展開しない。
+    /// 展開したトークンの位置は、マクロを使った位置にする。
+    fn expand_macro(&self, tk: &Token, tokens: &mut VecDeque<Token>) -> bool {
+        if !is_ident(tk) || tk.hideset.contains(&tk.str) {
+            return false;
+        }
+        let mut expanded = match self.macros.get(&tk.str) {
+            None => return false,
+            Some(Macro::Builtin(f)) => vec![f(tk)],
+            Some(Macro::Object(body)) => {
+                let mut body = self.subst(body, None, false, &[]);
+                let mut hideset = tk.hideset.clone();
+                hideset.push(tk.str.clone());
+                add_hideset(&mut body, &hideset);
+                body
+            }
+            Some(Macro::Function {
+                params,
+                is_variadic,
+                body,
+            }) => {
+                if tokens.front().is_none_or(|tk| !is_op(tk, "(")) {
+                    return false;
+                }
+                tokens.pop_front();
+                let (args, rparen) = read_macro_args(tokens, &tk.str, params.len(), *is_variadic);
+                let mut body = self.subst(body, Some(params), *is_variadic, &args);
+                // 展開禁止集合は、マクロ名と閉じる ')' の両方が持っていた名前とマクロ名にする
+                let mut hideset: Vec<String> = tk
+                    .hideset
+                    .iter()
+                    .filter(|name| rparen.hideset.contains(name))
+                    .cloned()
+                    .collect();
+                hideset.push(tk.str.clone());
+                add_hideset(&mut body, &hideset);
+                body
+            }
+        };
+
+        for (i, body_tk) in expanded.iter_mut().enumerate() {
+            body_tk.loc.file = tk.loc.file.clone();
+            body_tk.loc.line = tk.loc.line;
+            body_tk.loc.at_bol = i == 0 && tk.loc.at_bol;
+            if i == 0 {
+                body_tk.loc.has_space = tk.loc.has_space;
+            }
+        }
+        for body_tk in expanded.into_iter().rev() {
+            tokens.push_front(body_tk);
+        }
+        true
+    }
+
+    /// トークン列のマクロをすべて展開する
+    fn expand_all(&self, tokens: Vec<Token>) -> Vec<Token> {
+        let mut tokens = VecDeque::from(tokens);
+        let mut out = Vec::new();
+        while let Some(tk) = tokens.pop_front() {
+            if !self.expand_macro(&tk, &mut tokens) {
+                out.push(tk);
+            }
+        }
+        out
+    }
+
+    /// マクロの置換リストの仮引数を実引数で置き換え、##でトークンをつなげる。
+    /// 実引数はマクロを展開してから埋め込むが、#と##の被演算子になる場合は展開せずにそのまま使う。
+    /// オブジェクト形式マクロではparamsをNoneにする。#は演算子として扱わない。
+    fn subst(
+        &self,
+        body: &[Token],
+        params: Option<&[String]>,
+        is_variadic: bool,
+        args: &[Vec<Token>],
+    ) -> Vec<Token> {
+        let arg_of = |tk: &Token| -> Option<&Vec<Token>> {
+            if !is_ident(tk) {
+                return None;
+            }
+            if is_variadic && tk.str == "__VA_ARGS__" {
+                return args.last();
+            }
+            params?.iter().position(|p| p == &tk.str).map(|i| &args[i])
+        };
+
+        let mut out: Vec<Token> = Vec::new();
+        let mut i = 0;
+        while i < body.len() {
+            let tk = &body[i];
+            if is_op(tk, "#") && params.is_some() {
+                match body.get(i + 1).and_then(arg_of) {
+                    Some(arg) => out.push(stringize(arg)),
+                    None => panic!("'#'の後には仮引数が必要です"),
+                }
+                i += 2;
+                continue;
+            }
+            if is_op(tk, "##") {
+                let lhs = match out.pop() {
+                    Some(lhs) => lhs,
+                    None => panic!("'##'は置換リストの先頭に書けません"),
+                };
+                let rhs = match body.get(i + 1) {
+                    Some(rhs) => rhs,
+                    None => panic!("'##'は置換リストの末尾に書けません"),
+                };
+                match arg_of(rhs) {
+                    // GNU拡張の , ## __VA_ARGS__ は、可変長引数が空ならコンマを取り除き、
+                    // そうでなければコンマとつなげずに可変長引数を続ける
+                    Some(arg) if is_op(&lhs, ",") && is_variadic && rhs.str == "__VA_ARGS__" => {
+                        if !arg.is_empty() {
+                            out.push(lhs);
+                            out.extend(arg.iter().cloned());
+                        }
+                    }
+                    Some(arg) => match arg.split_first() {
+                        Some((first, rest)) => {
+                            out.push(paste(&lhs, first));
+                            out.extend(rest.iter().cloned());
+                        }
+                        None => out.push(lhs),
+                    },
+                    None => out.push(paste(&lhs, rhs)),
+                }
+                i += 2;
+                continue;
+            }
+            if let Some(arg) = arg_of(tk) {
+                if body.get(i + 1).is_some_and(|tk| is_op(tk, "##")) {
+                    if !arg.is_empty() {
+                        out.extend(arg.iter().cloned());
+                        i += 1;
+                    } else if let Some(rhs) = body.get(i + 2).and_then(arg_of) {
+                        // 左辺の実引数が空なら、右辺の実引数をそのまま使う
+                        out.extend(rhs.iter().cloned());
+                        i += 3;
+                    } else {
+                        i += 2;
+                    }
+                    continue;
+                }
+                out.extend(self.expand_all(arg.clone()));
+                i += 1;
+                continue;
+            }
+            out.push(tk.clone());
+            i += 1;
+        }
+        out
+    }
+
+    /// #ifと#elifの条件式を評価する。definedを処理してからマクロを展開し、
+    /// 残った識別子は0に置き換えて整数定数式として計算する。
+    fn eval_cond(&self, line: Vec<Token>) -> bool {
+        if line.is_empty() {
+            panic!("#ifに条件式がありません");
+        }
+        let mut line = VecDeque::from(line);
+        let mut resolved = Vec::new();
+        while let Some(tk) = line.pop_front() {
+            if tk.kind == TokenKind::Ident && tk.str == "defined" {
+                let has_paren = line.front().is_some_and(|tk| tk.str == "(");
+                if has_paren {
+                    line.pop_front();
+                }
+                let name = match line.pop_front() {
+                    Some(tk) if is_ident(&tk) => tk.str,
+                    _ => panic!("definedにはマクロ名が必要です"),
+                };
+                if has_paren && line.pop_front().is_none_or(|tk| tk.str != ")") {
+                    panic!("')' is not found");
+                }
+                let defined = self.macros.contains_key(&name);
+                resolved.push(Token::new_num(defined as i64).unwrap());
+                continue;
+            }
+            resolved.push(tk);
+        }
+        let expanded = self.expand_all(resolved);
+
+        let tokens = expanded
+            .into_iter()
+            .map(|tk| match is_ident(&tk) {
+                true => Token::new_num(0).unwrap(),
+                false => tk,
+            })
+            .collect();
+        let mut cond = CondExpr {
+            tokens,
+            unevaluated: 0,
+        };
+        let val = cond.expr();
+        if cond.tokens.front().is_some() {
+            panic!("#ifの条件式の後に余分なトークンがあります");
+        }
+        val.val != 0
+    }
+
+    /// #includeの後に書かれたファイル名のファイルを探し、そのパスと中身を返す。
+    /// "..." の場合はインクルードしたファイルと同じディレクトリから、<...> の場合と見つからなかった場合は
+    /// -Iで指定されたディレクトリから順に探し、組み込みのヘッダファイル、システムのディレクトリの順に探す。
+    fn read_include(&self, line: &[Token], file: &str) -> (String, String) {
+        let (name, is_quoted) = match line {
+            [tk] if tk.kind == TokenKind::Str => (tk.str.clone(), true),
+            [open, rest @ ..] if open.str == "<" => {
+                let close = match rest.iter().position(|tk| tk.str == ">") {
+                    Some(close) if close + 1 == rest.len() => close,
+                    _ => panic!("#include <...> が閉じられていません"),
+                };
+                // <> の中はトークンに分割されているため、元の綴りをつなげ直す
+                let name = rest[..close].iter().map(|tk| tk.text()).collect();
+                (name, false)
+            }
+            _ => panic!("#includeには\"ファイル名\"か<ファイル名>が必要です"),
+        };
+
+        if is_quoted {
+            let dir = match file {
+                INPUT_NAME => Path::new("."),
+                file => Path::new(file).parent().unwrap_or(Path::new(".")),
+            };
+            let path = dir.join(&name);
+            if path.exists() {
+                return read_file(&path);
+            }
+        }
+        if let Some(path) = self
+            .include_paths
+            .iter()
+            .map(|dir| dir.join(&name))
+            .find(|path| path.exists())
+        {
+            return read_file(&path);
+        }
+        if let Some((header, input)) = BUILTIN_HEADERS.iter().find(|(header, _)| *header == name) {
+            return (format!("{BUILTIN_DIR}/{header}"), input.to_string());
+        }
+        match SYSTEM_INCLUDE_PATHS
+            .iter()
+            .map(|dir| Path::new(dir).join(&name))
+            .find(|path| path.exists())
+        {
+            Some(path) => read_file(&path),
+            None => panic!("インクルードファイルが見つかりません: {name}"),
+        }
+    }
+}
+
+/// #ifの条件式で使える二項演算子。優先順位の低いものから並べる。
+const COND_BINARY_OPS: [&[&str]; 10] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// #ifの条件式の値。整数はすべてintmax_tかuintmax_tとして計算する。
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CondValue {
+    val: i64,
+    is_unsigned: bool,
+}
+
+impl CondValue {
+    /// 比較や論理演算の結果。int型の0か1になる。
+    fn from_bool(b: bool) -> Self {
+        Self {
+            val: b as i64,
+            is_unsigned: false,
+        }
+    }
+}
+
+/// #ifの条件式を評価する。条件式はマクロを展開し、識別子を0に置き換えたトークン列として受け取る。
+struct CondExpr {
+    tokens: VecDeque<Token>,
+    unevaluated: usize, // &&, ||, ?: で評価されない被演算子の中にいる深さ。0で割ってもエラーにしない。
+}
+
+impl CondExpr {
+    fn consume(&mut self, op: &str) -> bool {
+        if self.tokens.front().is_some_and(|tk| is_op(tk, op)) {
+            self.tokens.pop_front();
+            return true;
+        }
+        false
+    }
+
+    /// expr = binary ("?" expr ":" expr)?
+    fn expr(&mut self) -> CondValue {
+        let cond = self.binary(0);
+        if !self.consume("?") {
+            return cond;
+        }
+        self.unevaluated += (cond.val == 0) as usize;
+        let then = self.expr();
+        self.unevaluated -= (cond.val == 0) as usize;
+        if !self.consume(":") {
+            panic!("#ifの条件式の'?'に対応する':'がありません");
+        }
+        self.unevaluated += (cond.val != 0) as usize;
+        let els = self.expr();
+        self.unevaluated -= (cond.val != 0) as usize;
+        // 結果の型は、2つの値に通常の算術変換をした型になる
+        CondValue {
+            val: if cond.val != 0 { then.val } else { els.val },
+            is_unsigned: then.is_unsigned || els.is_unsigned,
+        }
+    }
+
+    /// binary = unary (op unary)*
+    /// 優先順位がlevelの演算子の式を読み取る。演算子はCOND_BINARY_OPSの順に強く結合する。
+    fn binary(&mut self, level: usize) -> CondValue {
+        let Some(ops) = COND_BINARY_OPS.get(level) else {
+            return self.unary();
+        };
+        let mut lhs = self.binary(level + 1);
+        while let Some(op) = self
+            .tokens
+            .front()
+            .filter(|tk| tk.kind == TokenKind::Reserved && ops.contains(&tk.str.as_str()))
+            .map(|tk| tk.str.clone())
+        {
+            self.tokens.pop_front();
+            // 左辺で結果が決まる&&と||の右辺は評価しない
+            let short = op == "&&" && lhs.val == 0 || op == "||" && lhs.val != 0;
+            self.unevaluated += short as usize;
+            let rhs = self.binary(level + 1);
+            self.unevaluated -= short as usize;
+            lhs = self.binary_op(&op, lhs, rhs);
+        }
+        lhs
+    }
+
+    /// 二項演算をする。被演算子のどちらかが符号なしなら、両方をuintmax_tとして計算する。
+    fn binary_op(&self, op: &str, lhs: CondValue, rhs: CondValue) -> CondValue {
+        let is_unsigned = lhs.is_unsigned || rhs.is_unsigned;
+        let (l, r) = (lhs.val, rhs.val);
+        let (ul, ur) = (l as u64, r as u64);
+        let val = match op {
+            "||" => return CondValue::from_bool(l != 0 || r != 0),
+            "&&" => return CondValue::from_bool(l != 0 && r != 0),
+            "==" => return CondValue::from_bool(l == r),
+            "!=" => return CondValue::from_bool(l != r),
+            "<" | "<=" | ">" | ">=" => {
+                let ord = match is_unsigned {
+                    true => ul.cmp(&ur),
+                    false => l.cmp(&r),
+                };
+                return CondValue::from_bool(match op {
+                    "<" => ord.is_lt(),
+                    "<=" => ord.is_le(),
+                    ">" => ord.is_gt(),
+                    _ => ord.is_ge(),
+                });
+            }
+            // シフト演算の結果は左辺の型になる
+            "<<" => {
+                return CondValue {
+                    val: l.wrapping_shl(r as u32),
+                    is_unsigned: lhs.is_unsigned,
+                }
+            }
+            ">>" => {
+                return CondValue {
+                    val: match lhs.is_unsigned {
+                        true => ul.wrapping_shr(r as u32) as i64,
+                        false => l.wrapping_shr(r as u32),
+                    },
+                    is_unsigned: lhs.is_unsigned,
+                }
+            }
+            "|" => l | r,
+            "^" => l ^ r,
+            "&" => l & r,
+            "+" => l.wrapping_add(r),
+            "-" => l.wrapping_sub(r),
+            "*" => l.wrapping_mul(r),
+            "/" | "%" if r == 0 => match self.unevaluated {
+                0 => panic!("#ifの条件式の中で0で割っています"),
+                _ => 0,
+            },
+            "/" if is_unsigned => (ul / ur) as i64,
+            "/" => l.wrapping_div(r),
+            "%" if is_unsigned => (ul % ur) as i64,
+            _ => l.wrapping_rem(r),
+        };
+        CondValue { val, is_unsigned }
+    }
+
+    /// unary = ("+" | "-" | "!" | "~") unary | "(" expr ")" | num
+    fn unary(&mut self) -> CondValue {
+        if self.consume("+") {
+            return self.unary();
+        }
+        if self.consume("-") {
+            let val = self.unary();
+            return CondValue {
+                val: val.val.wrapping_neg(),
+                ..val
+            };
+        }
+        if self.consume("!") {
+            return CondValue::from_bool(self.unary().val == 0);
+        }
+        if self.consume("~") {
+            let val = self.unary();
+            return CondValue {
+                val: !val.val,
+                ..val
+            };
+        }
+        if self.consume("(") {
+            let val = self.expr();
+            if !self.consume(")") {
+                panic!("')' is not found");
+            }
+            return val;
+        }
+        match self.tokens.pop_front() {
+            // 文字定数はint型で、intに収まらない整数リテラルと接尾辞uのある整数リテラルは符号なしになる
+            Some(tk) if tk.kind == TokenKind::Num => {
+                let is_char = tk.spelling.as_ref().is_some_and(|s| s.ends_with('\''));
+                CondValue {
+                    val: tk.val,
+                    is_unsigned: !is_char && (tk.val < 0 || tk.str.contains('u')),
+                }
+            }
+            Some(tk) => panic!("#ifの条件式に使えないトークンです: {}", tk.text()),
+            None => panic!("#ifの条件式が途中で終わっています"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_tokens, preprocess};
+    use crate::tokenize::tokenize;
+
+    fn preprocessed(input: &str) -> Vec<String> {
+        let tokens = preprocess(tokenize(input.to_string()).unwrap(), &[]);
+        tokens.tokens.iter().map(|tk| tk.text()).collect()
+    }
+
+    #[test]
+    fn object_like_macros() {
+        assert_eq!(
+            preprocessed("#define A B + 1\n#define B A\nA;\n#undef A\nA;"),
+            vec!["A", "+", "1", ";", "A", ";"]
+        );
+    }
+
+    #[test]
+    fn conditional_inclusion() {
+        let input = "#define X 2\n\
+                     #if X == 1\na;\n#elif defined(X) && X * 2 == 4\nb;\n#else\nc;\n#endif\n\
+                     #ifndef X\nd;\n#if 1\ne;\n#endif\n#endif";
+        assert_eq!(preprocessed(input), vec!["b", ";"]);
+    }
+
+    #[test]
+    fn conditional_expressions() {
+        for cond in [
+            "7 % 4 == 3 && (5 | 2) == 7 && (6 & 3) == 2 && (6 ^ 3) == 5 && ~0 == -1",
+            "-1 > 0u && -1 < 0 && 0xffffffffffffffff > 0 && 1u << 63 >> 63 == 1",
+            "(-1) / 2 == 0 && -1 % 2 == -1 && (0u - 1) / 2 == 0x7fffffffffffffff",
+            "'a' == 97 && '\\377' < 0 && !defined X && (1 ? -1 : 0u) > 0",
+            "UNDEFINED == 0 && (0 && 1 / 0 || 1 || 1 % 0) && (1 ? 2 : 1 / 0) == 2",
+        ] {
+            let input = format!("#if {cond}\nyes;\n#else\nno;\n#endif");
+            assert_eq!(preprocessed(&input), vec!["yes", ";"], "{cond}");
+        }
+    }
+
+    #[test]
+    fn skip_unterminated_quotes() {
+        let input = "#if 0\ndon't \"stop\n#endif\nit;";
+        let tokens = preprocess(tokenize(input.to_string()).unwrap(), &[]);
+        assert_eq!(tokens.tokens.len(), 2);
+    }
+
+    #[test]
+    fn function_like_macros() {
+        let input = "#define ADD(a, b) ((a) + (b))\n\
+                     #define CALL(f, ...) f(__VA_ARGS__)\n\
+                     #define F (1)\n\
+                     ADD(1, F); CALL(ADD, 2, (3, 4)); ADD;";
+        assert_eq!(
+            preprocessed(input).concat(),
+            "((1)+((1)));((2)+((3,4)));ADD;"
+        );
+    }
+
+    #[test]
+    fn stringize_and_paste() {
+        let input = "#define STR(x) #x\n#define CAT(a, b) a ## b\n#define XSTR(x) STR(x)\n\
+                     STR( a  + \"b\" ); CAT(x, 12); CAT(, y); CAT(<, <); XSTR(CAT(1, 2));";
+        assert_eq!(
+            preprocessed(input),
+            vec![
+                r#""a + \"b\"""#,
+                ";",
+                "x12",
+                ";",
+                "y",
+                ";",
+                "<<",
+                ";",
+                r#""12""#,
+                ";"
+            ]
+        );
+        // リテラルはソースコード上の綴りのまま文字列にし、つなげる
+        let input = "#define STR(x) #x\n#define CAT(a, b) a ## b\n\
+                     STR(0x10) STR('a') STR(07) STR(\"a\\x41\") CAT(0x1, F);";
+        assert_eq!(
+            preprocessed(input),
+            vec![
+                r#""0x10""#,
+                r#""'a'""#,
+                r#""07""#,
+                r#""\"a\\x41\"""#,
+                "0x1F",
+                ";"
+            ]
+        );
+    }
+
+    #[test]
+    fn paste_comma_and_va_args() {
+        let input = "#define E(f, ...) p(f, ## __VA_ARGS__)\nE(\"x\", 1, 2); E(\"y\"); E(\"z\",);";
+        assert_eq!(
+            preprocessed(input),
+            vec![
+                "p", "(", r#""x""#, ",", "1", ",", "2", ")", ";", "p", "(", r#""y""#, ")", ";",
+                "p", "(", r#""z""#, ")", ";"
+            ]
+        );
+    }
+
+    #[test]
+    fn paste_in_object_like_macros() {
+        assert_eq!(preprocessed("#define C a ## b\nC;"), vec!["ab", ";"]);
+        // C11 6.10.3.3 の例。hash_hashは##というトークンになるが、演算子としては働かない
+        let input = "#define hash_hash # ## #\n#define mkstr(a) # a\n\
+                     #define in_between(a) mkstr(a)\n#define join(c, d) in_between(c hash_hash d)\n\
+                     join(x, y);";
+        assert_eq!(preprocessed(input), vec![r#""x ## y""#, ";"]);
+    }
+
+    #[test]
+    fn pragma_and_line() {
+        assert_eq!(
+            preprocessed("#pragma once\n#pragma GCC visibility push(default)\nx;"),
+            vec!["x", ";"]
+        );
+        let input = "__LINE__;\n#line 10\n__LINE__;\n#line 20 \"a.c\"\n__LINE__; __FILE__;\n\
+                     # 30 \"b.c\" 2\n__LINE__; __FILE__;";
+        assert_eq!(
+            preprocessed(input),
+            vec!["1", ";", "10", ";", "20", ";", "\"a.c\"", ";", "30", ";", "\"b.c\"", ";"]
+        );
+    }
+
+    #[test]
+    fn hideset() {
+        // C11 6.10.3.5 の例と同じ考え方で、展開中のマクロは再び展開しない
+        let input = "#define f(a) a*g\n#define g(a) f(a)\n#define x 2+x\nf(2)(9); x;";
+        assert_eq!(preprocessed(input).concat(), "2*9*g;2+x;");
+    }
+
+    #[test]
+    fn builtin_macros() {
+        assert_eq!(
+            preprocessed("\n__LINE__;\n#define L __LINE__\nL; __FILE__; __STDC__;"),
+            vec!["2", ";", "4", ";", "\"<input>\"", ";", "1", ";"]
+        );
+    }
+
+    #[test]
+    fn builtin_headers() {
+        assert_eq!(
+            preprocessed("#include <stdbool.h>\n#include \"stdbool.h\"\nbool x = true;"),
+            vec!["_Bool", "x", "=", "1", ";"]
+        );
+    }
+
+    #[test]
+    fn macro_expanded_include() {
+        assert_eq!(
+            preprocessed(
+                "#define H <stdbool.h>\n#include H\n#define Q(x) #x\n#include Q(stdbool.h)\ntrue;"
+            ),
+            vec!["1", ";"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "文字リテラルが閉じられていません")]
+    fn unterminated_char_literal() {
+        preprocessed("#if 1\ndon't\n#endif");
+    }
+
+    #[test]
+    #[should_panic]
+    fn unterminated_conditional() {
+        preprocessed("#ifdef X\n1;");
+    }
+
+    #[test]
+    fn format_preprocessed_tokens() {
+        let input = "#define F(x) x+1\n#define ID(x) x\n  int a = F(2);\n\nID(a)ID(a) ;\n\n\n\n\n\n\n\n\na;";
+        let tokens = preprocess(tokenize(input.to_string()).unwrap(), &[]);
+        assert_eq!(
+            format_tokens(&tokens),
+            "# 3 \"<input>\"\nint a = 2+1;\n\na a ;\n# 14 \"<input>\"\na;"
+        );
+        // リテラルはソースコード上の綴りのまま出力する
+        let input = "'\\n' 'a' 0x1Fu 010 1.5f \"a\\tb\";";
+        let tokens = preprocess(tokenize(input.to_string()).unwrap(), &[]);
+        assert_eq!(
+            format_tokens(&tokens),
+            "# 1 \"<input>\"\n'\\n' 'a' 0x1Fu 010 1.5f \"a\\tb\";"
+        );
+    }
+}
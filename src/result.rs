@@ -1,7 +1,7 @@
+use crate::tokenize::Location;
+
 #[derive(Debug, thiserror::Error, PartialEq)]
 pub enum TokenizeError {
-    #[error("invalid character: {0}")]
-    InvalidOperator(String),
     #[error("invalid number: {0}")]
     InvalidNumber(String),
     #[error("Failed to tokenize at: {0}")]
@@ -9,3 +9,13 @@ pub enum TokenizeError {
 }
 
 pub type TokenizeResult<T, E = TokenizeError> = anyhow::Result<T, E>;
+
+/// 意味解析のエラー。エラーの原因となった式のソースコード上の位置を持つ。
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("{loc}: {msg}")]
+pub struct SemaError {
+    pub loc: Location,
+    pub msg: String,
+}
+
+pub type SemaResult<T> = Result<T, SemaError>;
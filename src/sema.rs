@@ -0,0 +1,1390 @@
+use crate::parse::{
+    eval_binary, string_node, vla_size, Designator, GVar, Initializer, LVar, LVars, Node, NodeKind,
+    Reloc,
+};
+use crate::result::{SemaError, SemaResult};
+use crate::tokenize::Location;
+use crate::types::{align_to, common_type, promote, Type, TypeKind};
+
+/// 構文解析で得た文の並びと関数の定義を意味解析し、名前解決と型付けを済ませた構文木にする。
+/// 関数の本体は、その関数の変数の領域に入れ替えてから解析する。
+/// エラーのある文があっても残りの文の解析を続け、見つかったすべてのエラーを返す。
+pub fn analyze(code: &mut [Node], lvars: &mut LVars) -> Result<(), Vec<SemaError>> {
+    let mut errors: Vec<_> = code
+        .iter_mut()
+        .filter_map(|node| analyze_expr(node, lvars).err())
+        .collect();
+    let mut funcs = std::mem::take(&mut lvars.funcs);
+    for func in funcs.iter_mut() {
+        lvars.swap_frame(func);
+        lvars.return_ty = func.ty.return_ty.as_deref().cloned();
+        errors.extend(
+            func.body
+                .iter_mut()
+                .filter_map(|node| analyze_expr(node, lvars).err()),
+        );
+        lvars.return_ty = None;
+        lvars.swap_frame(func);
+    }
+    lvars.funcs = funcs;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// 与えられたノードとその子孫のノードの意味解析をする。
+/// 初期化子を展開するときには、要素の型と照らし合わせるために要素の式ごとにも呼び出す。
+/// 解析済みのノードはそのままにするので、同じノードを何度解析してもよい。
+pub fn analyze_expr(node: &mut Node, lvars: &mut LVars) -> SemaResult<()> {
+    resolve(node, lvars)?;
+    add_type(node, lvars)?;
+    alloc_return_buffers(node, lvars);
+    Ok(())
+}
+
+/// 構造体・共用体を返す関数呼び出しに、戻り値を置く名前のない変数を割り当て、そのオフセットをノードに記録する。
+fn alloc_return_buffers(node: &mut Node, lvars: &mut LVars) {
+    if node.kind == NodeKind::FuncCall
+        && node.offset == 0
+        && node.ty.as_ref().is_some_and(Type::is_aggregate)
+    {
+        node.offset = lvars.push(String::new(), node.ty.clone()).offset;
+    }
+    for child in [&mut node.lhs, &mut node.rhs, &mut node.cond]
+        .into_iter()
+        .flatten()
+    {
+        alloc_return_buffers(child, lvars);
+    }
+    for arg in node.args.iter_mut() {
+        alloc_return_buffers(arg, lvars);
+    }
+}
+
+/// ノードのソースコード上の位置。パーサが補ったノードは位置を持たないため、子孫のノードの位置で代用する。
+fn location(node: &Node) -> Location {
+    fn find(node: &Node) -> Option<&Location> {
+        node.loc
+            .as_ref()
+            .or_else(|| node.lhs.as_deref().and_then(find))
+            .or_else(|| node.rhs.as_deref().and_then(find))
+            .or_else(|| node.cond.as_deref().and_then(find))
+            .or_else(|| node.args.iter().find_map(find))
+    }
+    find(node).cloned().unwrap_or_default()
+}
+
+fn error(node: &Node, msg: &str) -> SemaError {
+    SemaError {
+        loc: location(node),
+        msg: msg.to_string(),
+    }
+}
+
+/// 識別子のノードを、その位置から見える宣言を指すノードに置き換える。
+/// 初期化子は、変数の型に合わせて展開したノードに置き換える。
+fn resolve(node: &mut Node, lvars: &mut LVars) -> SemaResult<()> {
+    match node.kind {
+        NodeKind::Ident => return resolve_ident(node, lvars, false),
+        NodeKind::Init | NodeKind::Compound => return initialize(node, lvars),
+        NodeKind::FuncCall => {
+            let callee = node.lhs.as_mut().unwrap();
+            if callee.kind == NodeKind::Ident {
+                resolve_ident(callee, lvars, true)?;
+            }
+        }
+        _ => {}
+    }
+    if let Some(lhs) = node.lhs.as_mut() {
+        resolve(lhs, lvars)?;
+    }
+    if let Some(rhs) = node.rhs.as_mut() {
+        resolve(rhs, lvars)?;
+    }
+    for arg in node.args.iter_mut() {
+        resolve(arg, lvars)?;
+    }
+    if let Some(cond) = node.cond.as_mut() {
+        resolve(cond, lvars)?;
+    }
+    Ok(())
+}
+
+/// 識別子を、それより前に宣言された変数か、宣言なしで使われて暗黙に宣言された変数に結びつける。
+/// どちらもなければ識別子の位置で暗黙に宣言し、それ以降のすべての位置から見えるようにする。
+/// 呼び出される関数名はint型を返し引数が不明な関数、それ以外は8バイトの変数とみなす。
+/// 後で宣言される識別子を、宣言より前に使うことはできない。
+fn resolve_ident(node: &mut Node, lvars: &mut LVars, is_callee: bool) -> SemaResult<()> {
+    let name = node.label.as_str();
+    let scope = node.scope;
+    let gvar = lvars.gvars[..scope.gvars]
+        .iter()
+        .find(|v| v.name == name)
+        .or_else(|| {
+            lvars
+                .implicit_funcs
+                .iter()
+                .map(|&i| &lvars.gvars[i])
+                .find(|v| v.name == name)
+        });
+    let lvar = lvars.vec[..scope.lvars]
+        .iter()
+        .find(|v| v.name == name)
+        .or_else(|| lvars.vec.iter().find(|v| v.name == name && v.ty.is_none()));
+
+    let resolved = if let Some(gvar) = gvar {
+        gvar_node(gvar)
+    } else if let Some(lvar) = lvar {
+        lvar_node(lvar)
+    } else if lvars.find_typedef(name).is_some() {
+        return Err(error(
+            node,
+            &format!("型名を式の中で使うことはできません: {name}"),
+        ));
+    } else if is_callee
+        && (!lvars.is_declared(name) || lvars.find_gvar(name).is_some_and(|v| v.ty.is_func()))
+    {
+        eprintln!("{}: 警告: 関数が宣言されていません: {name}", location(node));
+        lvars.implicit_funcs.push(lvars.gvars.len());
+        lvars.gvars.push(GVar {
+            name: name.to_string(),
+            label: name.to_string(),
+            ty: Type::func(Type::int(), Vec::new(), true),
+            data: None,
+            relocs: Vec::new(),
+        });
+        gvar_node(lvars.gvars.last().unwrap())
+    } else if lvars.is_declared(name) {
+        return Err(error(
+            node,
+            &format!("識別子が宣言より前に使われています: {name}"),
+        ));
+    } else {
+        let name = name.to_string();
+        lvar_node(lvars.push(name, None))
+    };
+    *node = Node {
+        loc: node.loc.take(),
+        ..resolved
+    };
+    Ok(())
+}
+
+fn gvar_node(gvar: &GVar) -> Node {
+    Node {
+        kind: NodeKind::GVar,
+        label: gvar.label.clone(),
+        ty: Some(gvar.ty.clone()),
+        ..Node::default()
+    }
+}
+
+/// 可変長配列の変数は確保した領域へのポインタを持つので、そのポインタを参照外しした配列とする。
+fn lvar_node(lvar: &LVar) -> Node {
+    let node = Node {
+        kind: NodeKind::LVar,
+        offset: lvar.offset,
+        ty: lvar.ty.clone(),
+        ..Node::default()
+    };
+    match &lvar.ty {
+        Some(ty) if ty.is_vla() => Node {
+            kind: NodeKind::Deref,
+            lhs: Some(Box::new(Node {
+                ty: Some(ty.clone().decay()),
+                ..node
+            })),
+            ty: Some(ty.clone()),
+            ..Node::default()
+        },
+        _ => node,
+    }
+}
+
+/// アドレスを持つ値、つまり代入の左辺やアドレス演算子のオペランドにできる値かどうか。
+/// 複合リテラルは、名前のない変数を初期化してからその変数を参照するComma(init, var)になっている。
+fn is_lvalue(node: &Node) -> bool {
+    match node.kind {
+        NodeKind::LVar | NodeKind::GVar | NodeKind::Deref => true,
+        NodeKind::Member => is_lvalue(node.lhs.as_ref().unwrap()),
+        NodeKind::Comma => is_lvalue(node.rhs.as_ref().unwrap()),
+        _ => false,
+    }
+}
+
+/// メンバを参照できる構造体・共用体、つまり左辺値か、戻り値を変数に置いた関数呼び出しかどうか
+fn has_address(node: &Node) -> bool {
+    match node.kind {
+        NodeKind::FuncCall => true,
+        NodeKind::Member => has_address(node.lhs.as_ref().unwrap()),
+        NodeKind::Comma => has_address(node.rhs.as_ref().unwrap()),
+        _ => is_lvalue(node),
+    }
+}
+
+/// 代入の左辺、アドレス演算子のオペランド、メンバを参照する構造体・共用体が左辺値であることを検査する。
+fn check_lvalue(node: &Node) -> SemaResult<()> {
+    let msg = match node.kind {
+        NodeKind::Assign => "代入の左辺値が変数ではありません",
+        NodeKind::Addr => "左辺値ではない値のアドレスは取得できません",
+        NodeKind::Member => "左辺値ではない構造体・共用体のメンバは参照できません",
+        _ => return Ok(()),
+    };
+    let lhs = node.lhs.as_ref().unwrap();
+    let ok = if node.kind == NodeKind::Member {
+        has_address(lhs)
+    } else {
+        is_lvalue(lhs)
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(error(node, msg))
+    }
+}
+
+/// 両辺が算術型の二項演算のノードについて、両辺を共通の型に変換するノードを挟み、その型を返す。
+/// どちらかが算術型でない場合は何もせずにNoneを返す。
+fn usual_arith_conv(node: &mut Node) -> Option<Type> {
+    let lhs = node.lhs.take().unwrap();
+    let rhs = node.rhs.take().unwrap();
+    let (lhs_ty, rhs_ty) = (lhs.ty.clone().unwrap(), rhs.ty.clone().unwrap());
+    if !lhs_ty.is_numeric() || !rhs_ty.is_numeric() {
+        node.lhs = Some(lhs);
+        node.rhs = Some(rhs);
+        return None;
+    }
+
+    let ty = common_type(&lhs_ty, &rhs_ty);
+    node.lhs = Some(Box::new(Node::new_cast(*lhs, ty.clone())));
+    node.rhs = Some(Box::new(Node::new_cast(*rhs, ty.clone())));
+    Some(ty)
+}
+
+/// long double型は大きさとアラインメントだけを扱い、値は扱わない。long double型の式をアドレス演算子
+/// 以外の演算の対象にすることや、long double型への変換、long double型を受け渡す関数の呼び出しはエラーにする。
+fn check_long_double(node: &Node) -> SemaResult<()> {
+    let is_ldouble = |ty: &Type| ty.kind == TypeKind::LDouble;
+    let msg = "long double型の値はサポートしていません";
+    if node.kind == NodeKind::Cast && node.ty.as_ref().is_some_and(is_ldouble) {
+        return Err(error(node, msg));
+    }
+    if node.kind == NodeKind::FuncCall {
+        let func = node
+            .lhs
+            .as_ref()
+            .unwrap()
+            .ty
+            .as_ref()
+            .map(|ty| match &ty.base {
+                Some(base) if ty.kind == TypeKind::Ptr => base.as_ref(),
+                _ => ty,
+            });
+        if func.is_some_and(|func| {
+            func.return_ty.as_deref().is_some_and(is_ldouble) || func.params.iter().any(is_ldouble)
+        }) {
+            return Err(error(node, msg));
+        }
+    }
+    // アドレス演算子やsizeofのオペランドは、値として使わない
+    if node.kind == NodeKind::Addr || node.kind == NodeKind::Sizeof {
+        return Ok(());
+    }
+    let children = [&node.lhs, &node.rhs, &node.cond];
+    let operands = children.into_iter().flatten().map(Box::as_ref);
+    match operands
+        .chain(node.args.iter())
+        .find(|n| n.ty.as_ref().is_some_and(is_ldouble))
+    {
+        Some(operand) => Err(error(operand, msg)),
+        None => Ok(()),
+    }
+}
+
+/// 構造体・共用体が、入れ子の構造体・共用体や配列の中も含めてconst修飾されたメンバを持つかどうか。
+/// そのような構造体・共用体の変数には、まとめて代入することもできない。
+fn has_const_member(ty: &Type) -> bool {
+    ty.members.iter().any(|member| {
+        let mut ty = &member.ty;
+        while let (true, Some(base)) = (ty.is_array(), ty.base.as_ref()) {
+            ty = base;
+        }
+        ty.is_const || has_const_member(ty)
+    })
+}
+
+/// 式が空ポインタ定数(値が0の整数定数)かどうか。整数型への型変換は読み飛ばす。
+fn is_null_pointer_constant(node: &Node) -> bool {
+    match node.kind {
+        NodeKind::Num => node.val == 0 && node.ty.as_ref().is_none_or(Type::is_integer),
+        NodeKind::Cast if node.ty.as_ref().is_some_and(Type::is_integer) => {
+            node.lhs.as_deref().is_some_and(is_null_pointer_constant)
+        }
+        _ => false,
+    }
+}
+
+/// fromの式の値をtoの型の変数に代入できるかどうか。
+/// 算術型同士とポインタ同士は暗黙に変換する。整数をポインタに代入できるのは空ポインタ定数の場合だけで、
+/// ポインタを整数に代入できるのは_Boolの場合だけ。構造体・共用体は同じ型の場合のみ代入できる。
+fn is_assignable(to: &Type, from: &Node) -> bool {
+    let from_ty = from.ty.clone().unwrap().decay();
+    if to.is_numeric() && from_ty.is_numeric() {
+        return true;
+    }
+    if to.kind == TypeKind::Bool {
+        return from_ty.is_pointer() || from_ty.is_func();
+    }
+    if to.is_pointer() {
+        return from_ty.is_pointer() || from_ty.is_func() || is_null_pointer_constant(from);
+    }
+    to.is_aggregate() && to.kind == from_ty.kind && to.tag == from_ty.tag && to.size == from_ty.size
+}
+
+/// 関数呼び出しのノードの引数を、プロトタイプで宣言された引数の型に変換するノードを挟む。
+/// 可変長引数の部分や引数が不明な関数の引数は、既定の実引数拡張(整数拡張とfloatからdoubleへの変換)をする。
+fn convert_args(func: &Type, node: &mut Node) -> SemaResult<()> {
+    let nargs = node.args.len();
+    if nargs < func.params.len() || !func.is_variadic && nargs > func.params.len() {
+        return Err(error(
+            node,
+            &format!(
+                "引数の個数が一致しません: {}個の引数に対して{}個が渡されています",
+                func.params.len(),
+                nargs
+            ),
+        ));
+    }
+
+    for (i, arg) in std::mem::take(&mut node.args).into_iter().enumerate() {
+        let arg_ty = arg.ty.clone().unwrap().decay();
+        let ty = match func.params.get(i) {
+            Some(param) if is_assignable(param, &arg) => param.clone().unqualified(),
+            Some(_) => {
+                return Err(error(
+                    &arg,
+                    &format!("{}番目の引数の型が一致しません", i + 1),
+                ))
+            }
+            None if arg_ty.kind == TypeKind::Float => Type::double(),
+            None if arg_ty.is_integer() => promote(&arg_ty),
+            None => arg_ty,
+        };
+        // 構造体・共用体は型変換せずにそのまま渡す
+        if ty.is_aggregate() {
+            node.args.push(arg);
+        } else {
+            node.args.push(Node::new_cast(arg, ty));
+        }
+    }
+    Ok(())
+}
+
+/// 与えられたノードとその子孫のノードに型を付け、左辺値と代入の型を検査する。
+/// パーサで型が決まっているノード(宣言された変数や型変換)はそのままにする。
+/// 算術型同士の演算や代入には、必要な型変換のノードを挟む。
+/// メンバ参照にはメンバのオフセットと型を付け、sizeofはオペランドの型の大きさに置き換える。
+fn add_type(node: &mut Node, lvars: &LVars) -> SemaResult<()> {
+    if let Some(lhs) = node.lhs.as_mut() {
+        add_type(lhs, lvars)?;
+    }
+    if let Some(rhs) = node.rhs.as_mut() {
+        add_type(rhs, lvars)?;
+    }
+    for arg in node.args.iter_mut() {
+        add_type(arg, lvars)?;
+    }
+    if let Some(cond) = node.cond.as_mut() {
+        add_type(cond, lvars)?;
+    }
+    check_lvalue(node)?;
+    check_long_double(node)?;
+    if node.kind == NodeKind::Cast
+        && node
+            .ty
+            .as_ref()
+            .is_some_and(|ty| ty.is_aggregate() || ty.is_array() || ty.is_func())
+    {
+        return Err(error(
+            node,
+            "構造体・共用体・配列・関数型にはキャストできません",
+        ));
+    }
+    if matches!(node.kind, NodeKind::VaStart | NodeKind::VaArg) {
+        check_va_builtin(node)?;
+    }
+    if node.ty.is_some() {
+        return Ok(());
+    }
+
+    let lhs_ty = node.lhs.as_ref().and_then(|n| n.ty.clone());
+    let rhs_ty = node.rhs.as_ref().and_then(|n| n.ty.clone());
+    node.ty = match node.kind {
+        NodeKind::Num => match i32::try_from(node.val) {
+            Ok(_) => Some(Type::int()),
+            Err(_) => Some(Type::long()),
+        },
+        // 論理演算の結果は、gccと同様に0か1のint型になる
+        NodeKind::Not | NodeKind::LogAnd | NodeKind::LogOr => Some(Type::int()),
+        NodeKind::Eq | NodeKind::Ne | NodeKind::Lt | NodeKind::Le => {
+            usual_arith_conv(node);
+            Some(Type::int())
+        }
+        NodeKind::LVar => Some(Type::long()),
+        NodeKind::Add | NodeKind::Sub | NodeKind::Mul | NodeKind::Div
+            if lhs_ty.as_ref().unwrap().is_numeric() && rhs_ty.as_ref().unwrap().is_numeric() =>
+        {
+            usual_arith_conv(node)
+        }
+        NodeKind::Shl | NodeKind::Shr => {
+            if lhs_ty.as_ref().unwrap().is_flonum() || rhs_ty.as_ref().unwrap().is_flonum() {
+                return Err(error(node, "浮動小数点数はシフトできません"));
+            }
+            // シフト演算の結果は、整数拡張した左辺の型になる
+            let ty = promote(lhs_ty.as_ref().unwrap());
+            let lhs = node.lhs.take().unwrap();
+            node.lhs = Some(Box::new(Node::new_cast(*lhs, ty.clone())));
+            Some(ty)
+        }
+        // 配列に整数を足した結果は、要素へのポインタになる
+        NodeKind::Add => match (lhs_ty, rhs_ty) {
+            (Some(lhs), Some(rhs)) if !lhs.is_pointer() && rhs.is_pointer() => Some(rhs.decay()),
+            (lhs, _) => lhs.map(Type::decay),
+        },
+        NodeKind::Mul | NodeKind::Div => lhs_ty,
+        NodeKind::Sub => match (lhs_ty, rhs_ty) {
+            // ポインタ同士の引き算は要素数の差になる
+            (Some(lhs), Some(rhs)) if lhs.is_pointer() && rhs.is_pointer() => Some(Type::long()),
+            (lhs, _) => lhs.map(Type::decay),
+        },
+        NodeKind::Assign => {
+            let lhs_ty = lhs_ty.unwrap();
+            if lhs_ty.is_array() || lhs_ty.is_vla() {
+                return Err(error(node, "配列には代入できません"));
+            }
+            if lhs_ty.is_const {
+                return Err(error(node, "const修飾された値には代入できません"));
+            }
+            if has_const_member(&lhs_ty) {
+                return Err(error(
+                    node,
+                    "const修飾されたメンバを持つ構造体・共用体には代入できません",
+                ));
+            }
+            if rhs_ty.is_some() && !is_assignable(&lhs_ty, node.rhs.as_ref().unwrap()) {
+                return Err(error(node, "代入する値の型が変数の型と一致しません"));
+            }
+            if !lhs_ty.is_aggregate() {
+                let rhs = node.rhs.take().unwrap();
+                node.rhs = Some(Box::new(Node::new_cast(*rhs, lhs_ty.clone())));
+            }
+            Some(lhs_ty)
+        }
+        NodeKind::Return => {
+            // 戻り値は関数の戻り値の型に変換してから返す。main関数の戻り値はint型
+            let return_ty = lvars.return_ty.clone().unwrap_or(Type::int());
+            let is_void = return_ty.kind == TypeKind::Void;
+            match node.lhs.as_ref() {
+                None if is_void => {}
+                None => return Err(error(node, "戻り値がありません")),
+                Some(_) if is_void => {
+                    return Err(error(node, "void型の関数は値を返せません"));
+                }
+                Some(lhs) if !is_assignable(&return_ty, lhs) => {
+                    return Err(error(node, "戻り値の型が関数の戻り値の型と一致しません"));
+                }
+                Some(_) if !return_ty.is_aggregate() && lhs_ty != Some(return_ty.clone()) => {
+                    let lhs = node.lhs.take().unwrap();
+                    node.lhs = Some(Box::new(Node::new_cast(*lhs, return_ty)));
+                }
+                Some(_) => {}
+            }
+            None
+        }
+        NodeKind::Addr if lhs_ty.as_ref().is_some_and(Type::is_vla) => {
+            return Err(error(node, "可変長配列へのポインタはサポートしていません"));
+        }
+        NodeKind::Addr if lhs_ty.as_ref().is_some_and(Type::is_bitfield) => {
+            return Err(error(node, "ビットフィールドのアドレスは取得できません"));
+        }
+        NodeKind::Addr => lhs_ty.map(Type::pointer_to),
+        NodeKind::Member => {
+            let ty = match lhs_ty.map(|ty| lvars.complete(ty)) {
+                Some(ty) if ty.is_aggregate() => ty,
+                _ => {
+                    return Err(error(
+                        node,
+                        "構造体・共用体ではない値のメンバを参照しています",
+                    ))
+                }
+            };
+            let Some(member) = ty.find_member(&node.label) else {
+                let msg = format!("構造体・共用体にメンバがありません: {}", node.label);
+                return Err(error(node, &msg));
+            };
+            node.offset = member.offset;
+            // const修飾された構造体のメンバもconst修飾される
+            Some(lvars.complete(member.ty.clone()).qualified(&ty))
+        }
+        // sizeofのオペランドは評価せず、その型の大きさにする。可変長配列の大きさは実行時に求める
+        NodeKind::Sizeof => {
+            let ty = lhs_ty.unwrap();
+            *node = Node {
+                loc: node.loc.take(),
+                ..if ty.is_vla() {
+                    vla_size(&ty)
+                } else {
+                    Node::new_num(ty.size)
+                }
+            };
+            return add_type(node, lvars);
+        }
+        NodeKind::Continue if node.val < 0 => {
+            return Err(error(node, "ループの外でcontinueが使われています"));
+        }
+        NodeKind::Alloca => Some(Type::pointer_to(Type::void())),
+        NodeKind::VaStart => Some(Type::void()),
+        NodeKind::Comma => rhs_ty,
+        // 算術型同士なら通常の算術変換をし、そうでなければ真の場合の値の型にする
+        NodeKind::Cond => usual_arith_conv(node).or(lhs_ty.map(Type::decay)),
+        NodeKind::FuncCall => {
+            // 関数と関数へのポインタのどちらでも呼び出せる
+            let func = match lhs_ty {
+                Some(ty) if ty.is_func() => ty,
+                Some(Type {
+                    kind: TypeKind::Ptr,
+                    base: Some(base),
+                    ..
+                }) if base.is_func() => *base,
+                _ => return Err(error(node, "関数ではない値を呼び出しています")),
+            };
+            convert_args(&func, node)?;
+            let return_ty = *func.return_ty.unwrap();
+            Some(return_ty)
+        }
+        // 関数を参照外しした値は、関数そのものとして扱う
+        NodeKind::Deref if lhs_ty.as_ref().is_some_and(Type::is_func) => lhs_ty,
+        NodeKind::Deref => match lhs_ty {
+            Some(Type {
+                kind: TypeKind::Ptr | TypeKind::Array | TypeKind::Vla,
+                base: Some(base),
+                ..
+            }) => Some(*base),
+            _ => return Err(error(node, "ポインタではない値を参照外ししています")),
+        },
+        _ => None,
+    };
+    Ok(())
+}
+
+/// __builtin_va_startと__builtin_va_argの引数を確かめる。
+/// va_startは可変長引数の関数の中でのみ使え、va_argで取り出せるのは整数・ポインタ・浮動小数点数の値。
+fn check_va_builtin(node: &Node) -> SemaResult<()> {
+    let ap_ty = node.lhs.as_ref().unwrap().ty.clone().unwrap();
+    if ap_ty.decay().deep_unqualified() != Type::va_list().decay() {
+        return Err(error(node, "va_listではない値が渡されています"));
+    }
+    if node.kind == NodeKind::VaStart && node.offset == 0 {
+        return Err(error(node, "可変長引数の関数の外でva_startは使えません"));
+    }
+    if node.kind == NodeKind::VaArg
+        && !node
+            .ty
+            .as_ref()
+            .is_some_and(|ty| ty.is_numeric() || ty.is_pointer())
+    {
+        return Err(error(node, "va_argで取り出せない型です"));
+    }
+    Ok(())
+}
+
+/// 宣言した変数や複合リテラルの初期化子を、変数の型に合わせて展開する。
+/// 自動変数は要素ごとに代入する式にし、静的変数は初期値を求めて空の文にする。
+/// 要素数を省略した配列の変数は、初期化子から要素数を決めた型の領域を確保し直す。
+fn initialize(node: &mut Node, lvars: &mut LVars) -> SemaResult<()> {
+    let init = *node.init.take().unwrap();
+    let mut entries = Vec::new();
+    if !node.label.is_empty() {
+        let i = lvars
+            .gvars
+            .iter()
+            .position(|v| v.label == node.label)
+            .unwrap();
+        let ty = init_entries(lvars, lvars.gvars[i].ty.clone(), init, 0, &mut entries)?;
+        let (data, relocs) = static_data(lvars, &ty, entries)?;
+        let gvar = &mut lvars.gvars[i];
+        (gvar.ty, gvar.data, gvar.relocs) = (ty, Some(data), relocs);
+        *node = Node::default();
+        return Ok(());
+    }
+
+    let i = node.val as usize;
+    let ty = init_entries(
+        lvars,
+        lvars.vec[i].ty.clone().unwrap(),
+        init,
+        0,
+        &mut entries,
+    )?;
+    if lvars.vec[i].ty.as_ref() != Some(&ty) {
+        lvars.vec[i].offset = align_to(lvars.stack_size() + ty.size, ty.align);
+        lvars.vec[i].ty = Some(ty.clone());
+    }
+    let var = lvar_node(&lvars.vec[i]);
+    let mut init = init_lvar(var.offset, ty, entries);
+    analyze_expr(&mut init, lvars)?;
+    *node = match node.kind {
+        NodeKind::Compound => Node {
+            loc: node.loc.take(),
+            ..Node::new_op(NodeKind::Comma, init, var)
+        },
+        _ => init,
+    };
+    Ok(())
+}
+
+/// rbpからoffsetの位置にあるty型の変数を初期化する式を作る。
+fn init_lvar(offset: i64, ty: Type, entries: Vec<(i64, Type, Node)>) -> Node {
+    let mut node = Node::default();
+    // 配列や構造体は、初期化子で指定されなかった要素を0にするため、先に全体を0で埋める
+    if ty.is_aggregate() || ty.is_array() {
+        node = Node::new_op(
+            NodeKind::Comma,
+            node,
+            Node {
+                kind: NodeKind::MemZero,
+                offset,
+                ty: Some(ty),
+                ..Node::default()
+            },
+        );
+    }
+    for (elem_offset, elem_ty, expr) in entries {
+        // 要素は変数の先頭からelem_offsetの位置にある、elem_ty型の変数とみなして代入する。
+        // 初期化はconst修飾された変数や、const修飾されたメンバを持つ構造体にもできる。
+        let elem = Node {
+            kind: NodeKind::LVar,
+            offset: offset - elem_offset,
+            ty: Some(elem_ty.deep_unqualified()),
+            ..Node::default()
+        };
+        node = Node::new_op(
+            NodeKind::Comma,
+            node,
+            Node::new_op(NodeKind::Assign, elem, expr),
+        );
+    }
+    node
+}
+
+/// 初期化子をty型の値として展開し、(変数の先頭からのオフセット, 型, 値)の並びをentriesに追加する。
+/// 要素数が省略された配列型の場合は、初期化子から要素数を決めた型を返す。
+fn init_entries(
+    lvars: &mut LVars,
+    ty: Type,
+    init: Initializer,
+    offset: i64,
+    entries: &mut Vec<(i64, Type, Node)>,
+) -> SemaResult<Type> {
+    match init {
+        Initializer::Str(s) if is_char_array(&ty) => {
+            // 配列の大きさが足りる場合のみ、終端の'\0'を書き込む
+            let len = match ty.array_len {
+                len if len < 0 => s.len() as i64 + 1,
+                len => len,
+            };
+            let bytes = s.bytes().map(i64::from).chain(std::iter::once(0));
+            for (i, byte) in bytes.take(len as usize).enumerate() {
+                entries.push((offset + i as i64, Type::char(), Node::new_num(byte)));
+            }
+            Ok(Type::array_of(*ty.base.unwrap(), len))
+        }
+        // char型の配列以外では、文字列リテラルは名前のない配列を指す式になる
+        Initializer::Str(s) => {
+            let init = Initializer::Expr(Box::new(string_node(lvars, s)));
+            init_entries(lvars, ty, init, offset, entries)
+        }
+        Initializer::List(loc, items) if ty.is_aggregate() || ty.is_array() => {
+            // {"abc"} のように、char型の配列の文字列リテラルは{}で囲んでもよい
+            if is_char_array(&ty)
+                && items.len() == 1
+                && items[0].0.is_empty()
+                && matches!(items[0].1, Initializer::Str(_))
+            {
+                let (_, init) = items.into_iter().next().unwrap();
+                return init_entries(lvars, ty, init, offset, entries);
+            }
+            let mut items = items.into_iter().peekable();
+            let ty = init_list(lvars, ty, &loc, &mut items, offset, entries, true)?;
+            if items.next().is_some() {
+                return Err(SemaError {
+                    loc,
+                    msg: "初期化子が多すぎます".to_string(),
+                });
+            }
+            Ok(ty)
+        }
+        Initializer::List(loc, items) => {
+            // スカラー型の初期化子は {} で囲んでもよい
+            match items.into_iter().next() {
+                Some((designators, _)) if !designators.is_empty() => Err(SemaError {
+                    loc,
+                    msg: "スカラー型の初期化子に指示子は使えません".to_string(),
+                }),
+                Some((_, init)) => init_entries(lvars, ty, init, offset, entries),
+                None => {
+                    entries.push((offset, ty.clone(), Node::new_num(0)));
+                    Ok(ty)
+                }
+            }
+        }
+        Initializer::Expr(expr) if ty.is_array() => {
+            Err(error(&expr, "配列の初期化には{}が必要です"))
+        }
+        Initializer::Expr(expr) => {
+            entries.push((offset, ty.clone(), *expr));
+            Ok(ty)
+        }
+    }
+}
+
+/// 配列・構造体・共用体の初期化子の並びを、先頭から要素に割り当てていく。
+/// bracedが偽の場合は{}が省略された内側の要素を初期化しており、要素が埋まったら残りを外側に返す。
+/// locは並びを囲む"{"の位置で、指示子の誤りを報告するのに使う。
+fn init_list(
+    lvars: &mut LVars,
+    ty: Type,
+    loc: &Location,
+    items: &mut std::iter::Peekable<std::vec::IntoIter<(Vec<Designator>, Initializer)>>,
+    offset: i64,
+    entries: &mut Vec<(i64, Type, Node)>,
+    braced: bool,
+) -> SemaResult<Type> {
+    let mut index = 0;
+    let mut len = 0;
+    while let Some((designators, init)) = items.peek_mut() {
+        if let Some(designator) = designators.first() {
+            // 指示子は、{}が省略された内側ではなく外側の要素を指す
+            if !braced {
+                break;
+            }
+            index = designated_index(&ty, designator).map_err(|msg| SemaError {
+                loc: loc.clone(),
+                msg,
+            })?;
+            designators.remove(0);
+        } else if is_filled(&ty, index, len) {
+            break;
+        }
+
+        let (elem_ty, elem_offset) = element(lvars, &ty, index);
+        let elem_offset = offset + elem_offset;
+        if designators.is_empty() && needs_brace_elision(lvars, &elem_ty, init)? {
+            init_list(lvars, elem_ty, loc, items, elem_offset, entries, false)?;
+        } else {
+            let (designators, init) = items.next().unwrap();
+            if designators.is_empty() {
+                init_entries(lvars, elem_ty, init, elem_offset, entries)?;
+            } else {
+                // [1].x = 1 のように指示子が続く場合は、内側の要素をその指示子で初期化する
+                let init = Initializer::List(loc.clone(), vec![(designators, init)]);
+                init_entries(lvars, elem_ty, init, elem_offset, entries)?;
+            }
+        }
+        index += 1;
+        len = len.max(index);
+    }
+
+    if ty.is_array() && ty.array_len < 0 {
+        Ok(Type::array_of(*ty.base.unwrap(), len))
+    } else {
+        Ok(ty)
+    }
+}
+
+fn is_char_array(ty: &Type) -> bool {
+    ty.is_array() && ty.base.as_ref().unwrap().size == 1
+}
+
+/// 指示子が指す要素の番号。指示子が型と合わない場合はエラーメッセージを返す。
+fn designated_index(ty: &Type, designator: &Designator) -> Result<i64, String> {
+    match designator {
+        Designator::Index(i) if ty.is_array() => {
+            if *i < 0 || ty.array_len >= 0 && *i >= ty.array_len {
+                return Err(format!("配列の添字が範囲外です: {i}"));
+            }
+            Ok(*i)
+        }
+        Designator::Member(name) if ty.is_aggregate() => {
+            match ty.members.iter().position(|m| &m.name == name) {
+                Some(i) => Ok(i as i64),
+                None => Err(format!("構造体・共用体にメンバがありません: {name}")),
+            }
+        }
+        _ => Err("指示子が型と一致しません".to_string()),
+    }
+}
+
+/// index番目以降に初期化できる要素が残っていないかどうか。共用体は1つのメンバだけを初期化する。
+fn is_filled(ty: &Type, index: i64, len: i64) -> bool {
+    match ty.kind {
+        TypeKind::Array => ty.array_len >= 0 && index >= ty.array_len,
+        TypeKind::Union => len > 0,
+        _ => index >= ty.members.len() as i64,
+    }
+}
+
+/// index番目の要素の型と、tyの先頭からのオフセット
+fn element(lvars: &LVars, ty: &Type, index: i64) -> (Type, i64) {
+    if ty.is_array() {
+        let base = ty.base.as_ref().unwrap();
+        return (*base.clone(), base.size * index);
+    }
+    let member = &ty.members[index as usize];
+    (lvars.complete(member.ty.clone()), member.offset)
+}
+
+/// 配列・構造体・共用体の要素を、{}で囲まれていない初期化子で初期化する場合は、{}が省略されたものとみなす。
+/// ただし、同じ型の構造体の値による初期化と、char型の配列の文字列リテラルによる初期化は除く。
+fn needs_brace_elision(lvars: &mut LVars, ty: &Type, init: &mut Initializer) -> SemaResult<bool> {
+    Ok(match init {
+        Initializer::List(..) => false,
+        Initializer::Str(_) => !is_char_array(ty) && (ty.is_aggregate() || ty.is_array()),
+        Initializer::Expr(_) if ty.is_array() => true,
+        Initializer::Expr(expr) if ty.is_aggregate() => {
+            analyze_expr(expr, lvars)?;
+            !expr.ty.as_ref().is_some_and(Type::is_aggregate)
+        }
+        Initializer::Expr(_) => false,
+    })
+}
+
+/// 静的変数の初期値のバイト列と、そこに書き込む他の変数のアドレスを作る。
+/// 初期化子の値はすべて定数式か、静的変数のアドレスに定数を足したものでなければならない。
+fn static_data(
+    lvars: &mut LVars,
+    ty: &Type,
+    entries: Vec<(i64, Type, Node)>,
+) -> SemaResult<(Vec<u8>, Vec<Reloc>)> {
+    let mut data = vec![0; ty.size as usize];
+    let mut relocs = Vec::new();
+    for (offset, ty, mut expr) in entries {
+        analyze_expr(&mut expr, lvars)?;
+        if ty.size == 8 && !ty.is_flonum() && !ty.is_aggregate() {
+            if let Some((label, addend)) = eval_addr(&expr) {
+                relocs.push(Reloc {
+                    offset,
+                    label,
+                    addend,
+                });
+                continue;
+            }
+        }
+        // 代入と同様に、整数は値を変数の型に変換してから書き込む
+        let expr = match ty.kind {
+            _ if ty.is_flonum() || ty.is_aggregate() => expr,
+            _ => Node::new_cast(expr, ty.clone()),
+        };
+        let bytes = match ty.kind {
+            TypeKind::Float => eval_flonum(&expr).map(|val| (val as f32).to_le_bytes().to_vec()),
+            TypeKind::Double => eval_flonum(&expr).map(|val| val.to_le_bytes().to_vec()),
+            _ if ty.is_aggregate() => None,
+            _ => eval(&expr).map(|val| val.to_le_bytes().to_vec()),
+        };
+        let Some(bytes) = bytes else {
+            return Err(error(&expr, "静的変数の初期化子が定数式ではありません"));
+        };
+        let offset = offset as usize;
+        let size = ty.size as usize;
+        if ty.is_bitfield() {
+            // ビットフィールドは、同じ領域にある他のビットフィールドの値を残したまま書き込む
+            let mut unit = [0; 8];
+            unit[..size].copy_from_slice(&data[offset..offset + size]);
+            let mask = (u64::MAX >> (64 - ty.bit_width)) << ty.bit_offset;
+            let val = u64::from_le_bytes(bytes.try_into().unwrap()) << ty.bit_offset;
+            let unit = u64::from_le_bytes(unit) & !mask | val & mask;
+            data[offset..offset + size].copy_from_slice(&unit.to_le_bytes()[..size]);
+            continue;
+        }
+        data[offset..offset + size].copy_from_slice(&bytes[..size]);
+    }
+    Ok((data, relocs))
+}
+
+/// 静的変数のアドレスに定数を足した式を評価し、変数のラベルと足すバイト数を返す。
+/// そのような式でなければNoneを返す。
+fn eval_addr(node: &Node) -> Option<(String, i64)> {
+    let lhs = node.lhs.as_deref();
+    let rhs = node.rhs.as_deref();
+    match node.kind {
+        // 配列と関数は、そのアドレスを値として扱う
+        NodeKind::GVar
+            if node
+                .ty
+                .as_ref()
+                .is_some_and(|ty| ty.is_array() || ty.is_func()) =>
+        {
+            Some((node.label.clone(), 0))
+        }
+        NodeKind::Addr => eval_lval_addr(lhs?),
+        NodeKind::Cast if node.ty.as_ref().is_some_and(|ty| ty.size == 8) => eval_addr(lhs?),
+        // ポインタに整数を足すときは、整数にポインタの指す先の型の大きさを掛ける
+        NodeKind::Add => match (eval_addr(lhs?), eval_addr(rhs?)) {
+            (Some((label, addend)), None) => {
+                Some((label, addend + eval(rhs?)? * pointee_size(lhs?)))
+            }
+            (None, Some((label, addend))) => {
+                Some((label, addend + eval(lhs?)? * pointee_size(rhs?)))
+            }
+            _ => None,
+        },
+        NodeKind::Sub if rhs?.ty.as_ref().is_some_and(|ty| !ty.is_pointer()) => {
+            let (label, addend) = eval_addr(lhs?)?;
+            Some((label, addend - eval(rhs?)? * pointee_size(lhs?)))
+        }
+        _ => None,
+    }
+}
+
+/// 静的変数を指す左辺値のアドレスを評価し、変数のラベルと足すバイト数を返す。
+fn eval_lval_addr(node: &Node) -> Option<(String, i64)> {
+    match node.kind {
+        NodeKind::GVar => Some((node.label.clone(), 0)),
+        NodeKind::Member => {
+            let (label, addend) = eval_lval_addr(node.lhs.as_ref()?)?;
+            Some((label, addend + node.offset))
+        }
+        NodeKind::Deref => eval_addr(node.lhs.as_ref()?),
+        _ => None,
+    }
+}
+
+/// ポインタ(配列)の指す先の型の大きさ。ポインタでも配列でもなければ1。
+fn pointee_size(node: &Node) -> i64 {
+    match node.ty.as_ref().and_then(|ty| ty.base.as_ref()) {
+        Some(base) if node.ty.as_ref().unwrap().is_pointer() => base.size,
+        _ => 1,
+    }
+}
+
+/// 意味解析を済ませた整数定数式を評価する。定数式でなければNoneを返す。
+fn eval(node: &Node) -> Option<i64> {
+    let lhs = || eval(node.lhs.as_ref()?);
+    let rhs = || eval(node.rhs.as_ref()?);
+    let is_unsigned = node
+        .lhs
+        .as_ref()
+        .and_then(|lhs| lhs.ty.as_ref())
+        .is_some_and(|ty| ty.is_unsigned);
+    match node.kind {
+        NodeKind::Num if node.ty.as_ref().is_some_and(Type::is_flonum) => None,
+        NodeKind::Num => Some(node.val),
+        // ポインタに整数を足すときは、整数にポインタの指す先の型の大きさを掛ける。
+        // 演算結果はノードの型の大きさに切り詰める
+        NodeKind::Add => {
+            let lval = lhs()?.wrapping_mul(pointee_size(node.rhs.as_ref()?));
+            let rval = rhs()?.wrapping_mul(pointee_size(node.lhs.as_ref()?));
+            Some(node.ty.as_ref()?.truncate(lval.wrapping_add(rval)))
+        }
+        // ポインタ同士の引き算は要素数の差になる
+        NodeKind::Sub if node.rhs.as_ref()?.ty.as_ref().is_some_and(Type::is_pointer) => {
+            Some(lhs()?.wrapping_sub(rhs()?) / pointee_size(node.lhs.as_ref()?))
+        }
+        NodeKind::Sub => {
+            let rval = rhs()?.wrapping_mul(pointee_size(node.lhs.as_ref()?));
+            Some(node.ty.as_ref()?.truncate(lhs()?.wrapping_sub(rval)))
+        }
+        NodeKind::Mul | NodeKind::Div | NodeKind::Shl | NodeKind::Shr => {
+            let val = eval_binary(&node.kind, lhs()?, rhs()?, is_unsigned)?;
+            Some(node.ty.as_ref()?.truncate(val))
+        }
+        NodeKind::Eq | NodeKind::Ne | NodeKind::Lt | NodeKind::Le => {
+            eval_binary(&node.kind, lhs()?, rhs()?, is_unsigned)
+        }
+        NodeKind::Not => Some((lhs()? == 0) as i64),
+        NodeKind::LogAnd => Some((lhs()? != 0 && rhs()? != 0) as i64),
+        NodeKind::LogOr => Some((lhs()? != 0 || rhs()? != 0) as i64),
+        NodeKind::Cond if eval(node.cond.as_ref()?)? != 0 => lhs(),
+        NodeKind::Cond => rhs(),
+        NodeKind::Cast => {
+            let inner = node.lhs.as_ref()?;
+            let ty = node.ty.as_ref()?;
+            let val = match &inner.ty {
+                Some(inner_ty) if inner_ty.is_flonum() && ty.kind == TypeKind::Bool => {
+                    (eval_flonum(inner)? != 0.0) as i64
+                }
+                Some(inner_ty) if inner_ty.is_flonum() => eval_flonum(inner)? as i64,
+                _ => lhs()?,
+            };
+            Some(ty.truncate(val))
+        }
+        // offsetofの &((T *)0)->member のような、定数のアドレスから求めたメンバのアドレス。
+        // 配列のメンバは、そのアドレスを値として扱う
+        NodeKind::Addr => eval_lval(node.lhs.as_ref()?),
+        NodeKind::Member if node.ty.as_ref().is_some_and(Type::is_array) => eval_lval(node),
+        _ => None,
+    }
+}
+
+/// 定数のアドレスを参照外しした左辺値のアドレスを評価する
+fn eval_lval(node: &Node) -> Option<i64> {
+    match node.kind {
+        NodeKind::Member => Some(eval_lval(node.lhs.as_ref()?)? + node.offset),
+        NodeKind::Deref => eval(node.lhs.as_ref()?),
+        _ => None,
+    }
+}
+
+/// 浮動小数点数の定数式を評価する。定数式でなければNoneを返す。
+fn eval_flonum(node: &Node) -> Option<f64> {
+    let lhs = || eval_flonum(node.lhs.as_ref()?);
+    let rhs = || eval_flonum(node.rhs.as_ref()?);
+    if !node.ty.as_ref().is_some_and(|ty| ty.is_flonum()) {
+        return eval(node).map(|val| val as f64);
+    }
+    match node.kind {
+        NodeKind::Num => Some(node.fval),
+        NodeKind::Add => Some(lhs()? + rhs()?),
+        NodeKind::Sub => Some(lhs()? - rhs()?),
+        NodeKind::Mul => Some(lhs()? * rhs()?),
+        NodeKind::Div => Some(lhs()? / rhs()?),
+        NodeKind::Cast => match node.ty.as_ref()?.kind {
+            TypeKind::Float => Some(lhs()? as f32 as f64),
+            _ => lhs(),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_type, analyze};
+    use crate::parse::{program, LVars, Node, NodeKind};
+    use crate::tokenize::tokenize;
+    use crate::types::Type;
+
+    /// ソースコードを構文解析して意味解析し、その結果の構文木と変数を返す
+    fn analyzed(input: &str) -> (Vec<Node>, LVars) {
+        let mut tokens = tokenize(input.to_string()).unwrap();
+        let mut code = program(&mut tokens);
+        analyze(&mut code, &mut tokens.lvars).unwrap();
+        (code, tokens.lvars)
+    }
+
+    /// 意味解析のエラーを "行番号: メッセージ" の形で返す
+    fn errors(input: &str) -> Vec<String> {
+        let mut tokens = tokenize(input.to_string()).unwrap();
+        let mut code = program(&mut tokens);
+        analyze(&mut code, &mut tokens.lvars)
+            .unwrap_err()
+            .into_iter()
+            .map(|err| format!("{}: {}", err.loc.line, err.msg))
+            .collect()
+    }
+
+    #[test]
+    fn resolve_names() {
+        let (code, lvars) = analyzed("a = 1; int b = 2; long *p = &a; b;");
+        let assign = &code[0];
+        assert_eq!(assign.kind, NodeKind::Assign);
+        let lhs = assign.lhs.as_ref().unwrap();
+        assert_eq!(lhs.kind, NodeKind::LVar);
+        assert_eq!(lhs.offset, lvars.find("a").unwrap().offset);
+        assert_eq!(lhs.ty, Some(Type::long()));
+        // 宣言された変数は、宣言された型になる
+        assert_eq!(code[3].kind, NodeKind::LVar);
+        assert_eq!(code[3].offset, lvars.find("b").unwrap().offset);
+        assert_eq!(code[3].ty, Some(Type::int()));
+    }
+
+    #[test]
+    fn resolve_undeclared_function() {
+        let (code, lvars) = analyzed("foo(1, 2);");
+        assert_eq!(code[0].kind, NodeKind::FuncCall);
+        assert_eq!(code[0].ty, Some(Type::int()));
+        let callee = code[0].lhs.as_ref().unwrap();
+        assert_eq!(callee.kind, NodeKind::GVar);
+        assert_eq!(callee.label, "foo");
+        assert!(lvars.find_gvar("foo").unwrap().ty.is_func());
+        assert!(lvars.find("foo").is_none());
+    }
+
+    #[test]
+    fn resolve_names_by_position() {
+        // 暗黙に宣言された変数と関数は、それ以降の位置から見える
+        let (code, lvars) = analyzed("x = 2; x + sizeof(x); f(x); f(1);");
+        assert_eq!(lvars.vec.len(), 1);
+        assert_eq!(lvars.gvars.len(), 1);
+        assert_eq!(code[2].lhs.as_ref().unwrap().label, "f");
+        // 宣言より前の位置からは、後で宣言される変数は見えない
+        assert_eq!(
+            errors("a = 300;\nchar a;\na;"),
+            vec!["1: 識別子が宣言より前に使われています: a"]
+        );
+        assert_eq!(
+            errors("ext1 = 7;\nextern int ext1;"),
+            vec!["1: 識別子が宣言より前に使われています: ext1"]
+        );
+    }
+
+    #[test]
+    fn lvalue_errors() {
+        assert_eq!(
+            errors("1 = 2;"),
+            vec!["1: 代入の左辺値が変数ではありません"]
+        );
+        assert_eq!(
+            errors("a = 1;\n&(a + 1);"),
+            vec!["2: 左辺値ではない値のアドレスは取得できません"]
+        );
+        assert_eq!(
+            errors("a = 1;\n\na + 1 = 3;"),
+            vec!["3: 代入の左辺値が変数ではありません"]
+        );
+    }
+
+    #[test]
+    fn assignment_errors() {
+        assert_eq!(
+            errors("int x[2];\nx = 0;"),
+            vec!["2: 配列には代入できません"]
+        );
+        assert_eq!(
+            errors("struct { int a; } s;\ndouble d;\nd = s;"),
+            vec!["3: 代入する値の型が変数の型と一致しません"]
+        );
+        assert_eq!(
+            errors("int *p; double d;\nd = p;"),
+            vec!["2: 代入する値の型が変数の型と一致しません"]
+        );
+        assert_eq!(
+            errors("int *p; long l;\nl = p;\np = 1;\np = l;"),
+            vec![
+                "2: 代入する値の型が変数の型と一致しません",
+                "3: 代入する値の型が変数の型と一致しません",
+                "4: 代入する値の型が変数の型と一致しません",
+            ]
+        );
+        assert_eq!(
+            errors("int add2(int, int); int *p;\nadd2(p, 1);"),
+            vec!["2: 1番目の引数の型が一致しません"]
+        );
+        analyzed("int *p = 0; char *q; q = (char *)0; p = 0L; q = (void *)p;");
+        let msg = "const修飾されたメンバを持つ構造体・共用体には代入できません";
+        for input in [
+            "struct S { const int a; int b; } s, t;\ns = t;",
+            "struct { int a; union { char c; const char d[2]; } u; } s, t;\ns = t;",
+            "struct S { struct { const long l; } in[2]; } s, t;\ns = t;",
+        ] {
+            assert_eq!(errors(input), vec![format!("2: {msg}")], "{input}");
+        }
+        analyzed("struct S { const int a; int b; } s = {1, 2}, t = s;\ns.b = 1;");
+    }
+
+    #[test]
+    fn long_double_errors() {
+        let msg = "long double型の値はサポートしていません";
+        for input in [
+            "long double x;\nx + 1;",
+            "long double x;\nx = 1.0;",
+            "(long double)1;\n",
+            "long double f(void);\nf();",
+            "void g(long double);\ng(1.0);",
+        ] {
+            assert_eq!(
+                errors(input),
+                vec![format!("{}: {msg}", input.lines().count())],
+                "{input}"
+            );
+        }
+        analyzed("struct S { long double x; } s, *p = &s; long double *q = &s.x; sizeof(s.x);");
+    }
+
+    #[test]
+    fn report_every_error() {
+        assert_eq!(
+            errors("1 = 2;\na = 3;\n*a;\nint f(int);\nf(1, 2);"),
+            vec![
+                "1: 代入の左辺値が変数ではありません",
+                "3: ポインタではない値を参照外ししています",
+                "5: 引数の個数が一致しません: 1個の引数に対して2個が渡されています",
+            ]
+        );
+    }
+
+    #[test]
+    fn report_errors_in_sizeof_and_members() {
+        assert_eq!(
+            errors("int x;\nsizeof(1 = x);\nstruct { int a; } s;\nx->a;\n*x;"),
+            vec![
+                "2: 代入の左辺値が変数ではありません",
+                "4: ポインタではない値を参照外ししています",
+                "5: ポインタではない値を参照外ししています",
+            ]
+        );
+    }
+
+    #[test]
+    fn expression_errors() {
+        assert_eq!(
+            errors("int x;\nx.a;"),
+            vec!["2: 構造体・共用体ではない値のメンバを参照しています"]
+        );
+        assert_eq!(
+            errors("struct { int a; } s;\ns.b;"),
+            vec!["2: 構造体・共用体にメンバがありません: b"]
+        );
+        assert_eq!(
+            errors("typedef int T;\n1 + T;"),
+            vec!["2: 型名を式の中で使うことはできません: T"]
+        );
+        assert_eq!(
+            errors("struct S { int a; } s;\n(struct S)s;"),
+            vec!["2: 構造体・共用体・配列・関数型にはキャストできません"]
+        );
+        assert_eq!(
+            errors("1;\ncontinue;"),
+            vec!["2: ループの外でcontinueが使われています"]
+        );
+    }
+
+    #[test]
+    fn initializer_errors() {
+        assert_eq!(
+            errors("int x;\nint a[2] = {1, 2, 3};"),
+            vec!["2: 初期化子が多すぎます"]
+        );
+        assert_eq!(
+            errors("struct { int a; } s = {1};\nstruct { int a; } t = {.b = 1};"),
+            vec!["2: 構造体・共用体にメンバがありません: b"]
+        );
+        assert_eq!(
+            errors("int x;\nint a[2] = x;"),
+            vec!["2: 配列の初期化には{}が必要です"]
+        );
+        assert_eq!(
+            errors("int x = 3;\nstatic int y = x;"),
+            vec!["2: 静的変数の初期化子が定数式ではありません"]
+        );
+    }
+
+    #[test]
+    fn function_definitions() {
+        let (code, lvars) = analyzed("int f(int a, double b) { int c; return a + b; } f(1, 2);");
+        let func = &lvars.funcs[0];
+        assert_eq!(func.vars.len(), 3);
+        assert_eq!(func.vars[1].ty, Some(Type::double()));
+        let ret = func.body[1].lhs.as_ref().unwrap();
+        assert_eq!(ret.kind, NodeKind::Cast);
+        assert_eq!(ret.ty, Some(Type::int()));
+        assert_eq!(code[1].lhs.as_ref().unwrap().label, "f");
+        assert_eq!(
+            errors("void f(void) {\nreturn 1; }\nint g(void) { return; }"),
+            vec!["2: void型の関数は値を返せません", "3: 戻り値がありません"]
+        );
+        assert_eq!(
+            errors("int *f(int x) {\nreturn x; }"),
+            vec!["2: 戻り値の型が関数の戻り値の型と一致しません"]
+        );
+    }
+
+    #[test]
+    fn variadic_functions() {
+        analyzed(
+            "int f(int n, ...) { __builtin_va_list ap;\
+             __builtin_va_start(ap, n); __builtin_va_arg(ap, double); }",
+        );
+        assert_eq!(
+            errors("int f(int n) { __builtin_va_list ap;\n__builtin_va_start(ap, n); }"),
+            vec!["2: 可変長引数の関数の外でva_startは使えません"]
+        );
+        assert_eq!(
+            errors("int f(int n, ...) {\n__builtin_va_start(n, n); }"),
+            vec!["2: va_listではない値が渡されています"]
+        );
+        assert_eq!(
+            errors("struct S { int a; };\nint f(int n, ...) { __builtin_va_list ap;\n__builtin_va_arg(ap, struct S); }"),
+            vec!["3: va_argで取り出せない型です"]
+        );
+    }
+
+    #[test]
+    fn struct_calls() {
+        let (code, lvars) =
+            analyzed("struct S { long a, b, c; } s;\nstruct S g(struct S);\ng(s).b;");
+        let call = code[2].lhs.as_ref().unwrap();
+        assert_eq!(call.kind, NodeKind::FuncCall);
+        assert_eq!(call.offset, lvars.vec.last().unwrap().offset);
+        assert_eq!(
+            errors("struct S { int a; };\nstruct S g();\ng().a = 1;"),
+            vec!["3: 代入の左辺値が変数ではありません"]
+        );
+    }
+
+    #[test]
+    fn add_type_with_function_call_arguments() {
+        let func = Type::func(Type::void(), vec![Type::double()], true);
+        let mut node = Node {
+            kind: NodeKind::FuncCall,
+            lhs: Some(Box::new(Node {
+                kind: NodeKind::GVar,
+                label: "f".to_string(),
+                ty: Some(func),
+                ..Node::default()
+            })),
+            args: vec![
+                Node::new_num(1),
+                Node::new_cast(Node::new_num(2), Type::float()),
+                Node::new_cast(Node::new_num(3), Type::char()),
+            ],
+            ..Node::default()
+        };
+        add_type(&mut node, &LVars::new()).unwrap();
+        assert_eq!(node.ty, Some(Type::void()));
+        let tys: Vec<_> = node
+            .args
+            .iter()
+            .map(|arg| arg.ty.clone().unwrap())
+            .collect();
+        assert_eq!(tys, vec![Type::double(), Type::double(), Type::int()]);
+    }
+
+    #[test]
+    fn add_type_with_usual_arithmetic_conversion() {
+        let mut node = Node::new_op(
+            NodeKind::Add,
+            Node::new_cast(Node::new_num(1), Type::char()),
+            Node::new_num(4294967296),
+        );
+        add_type(&mut node, &LVars::new()).unwrap();
+        assert_eq!(node.ty, Some(Type::long()));
+        let lhs = node.lhs.unwrap();
+        assert_eq!(lhs.kind, NodeKind::Cast);
+        assert_eq!(lhs.ty, Some(Type::long()));
+        assert_eq!(lhs.lhs.unwrap().ty, Some(Type::char()));
+        let rhs = node.rhs.unwrap();
+        assert_eq!(rhs.kind, NodeKind::Cast);
+        assert_eq!(rhs.ty, Some(Type::long()));
+    }
+
+    #[test]
+    fn add_type_with_assignment_to_const() {
+        let lhs = Node {
+            kind: NodeKind::LVar,
+            offset: 4,
+            ty: Some(Type {
+                is_const: true,
+                ..Type::int()
+            }),
+            ..Node::default()
+        };
+        let err = add_type(
+            &mut Node::new_op(NodeKind::Assign, lhs, Node::new_num(1)),
+            &LVars::new(),
+        )
+        .unwrap_err();
+        assert_eq!(err.msg, "const修飾された値には代入できません");
+    }
+}
@@ -1,7 +1,8 @@
 use std::collections::VecDeque;
+use std::rc::Rc;
 
 use crate::{
-    parse::LVars,
+    parse::{Function, LVars, Node},
     result::{TokenizeError, TokenizeResult},
 };
 
@@ -30,88 +31,222 @@ impl UserInput {
     }
 
     fn starts_with(&self, s: &str) -> bool {
-        self.chars.iter().zip(s.chars()).all(|(a, b)| a == &b)
+        self.chars.len() >= s.len() && self.chars.iter().zip(s.chars()).all(|(a, b)| a == &b)
     }
 
-    pub fn parse_num(&mut self) -> Option<usize> {
-        if !self.front()?.is_ascii_digit() {
-            return None;
+    /// 整数リテラルの値を読み取る。0xで始まる場合は16進数、0bで始まる場合は2進数、
+    /// 0で始まる場合は8進数として読む。値がusizeに収まらない場合はエラーを返す。
+    pub fn parse_num(&mut self) -> TokenizeResult<Option<usize>> {
+        if !self.front().is_some_and(|c| c.is_ascii_digit()) {
+            return Ok(None);
         }
 
-        let mut num = 0;
-        while let Some(c) = self.front() {
-            if !c.is_ascii_digit() {
-                break;
+        let is_digit_at = |i: usize, radix: u32| self.get(i).is_some_and(|c| c.is_digit(radix));
+        let radix = if (self.starts_with("0x") || self.starts_with("0X")) && is_digit_at(2, 16) {
+            self.chars.drain(..2);
+            16
+        } else if (self.starts_with("0b") || self.starts_with("0B")) && is_digit_at(2, 2) {
+            self.chars.drain(..2);
+            2
+        } else if self.starts_with("0") {
+            8
+        } else {
+            10
+        };
+
+        let mut num = Some(0usize);
+        let mut digits = String::new();
+        while let Some(c) = self.front().filter(|c| c.is_digit(radix)).copied() {
+            let d = c.to_digit(radix).unwrap() as usize;
+            num = num
+                .and_then(|num| num.checked_mul(radix as usize))
+                .and_then(|num| num.checked_add(d));
+            digits.push(c);
+            self.pop_front();
+        }
+        match num {
+            Some(num) => Ok(Some(num)),
+            None => Err(TokenizeError::InvalidNumber(digits)),
+        }
+    }
+
+    /// 整数リテラルの接尾辞(u, l, ll とその組み合わせ)を読み取り、小文字にして返す
+    pub fn parse_int_suffix(&mut self) -> String {
+        let mut i = 0;
+        while self.get(i).is_some_and(|c| "uUlL".contains(*c)) {
+            i += 1;
+        }
+        let suffix: String = self.chars.drain(..i).collect::<String>().to_lowercase();
+        if !["", "u", "l", "ll", "ul", "lu", "ull", "llu"].contains(&suffix.as_str()) {
+            panic!("不正な整数リテラルの接尾辞です: {suffix}");
+        }
+        suffix
+    }
+
+    /// 同じ行で閉じられていない引用符を読み取る。#if 0 の中の don't のような綴りは、
+    /// 前処理で読み飛ばされるならエラーにしないため、1文字のトークンにしておく。
+    pub fn parse_stray_quote(&mut self) -> Option<char> {
+        let quote = *self.front()?;
+        if quote != '\'' && quote != '"' {
+            return None;
+        }
+        let mut i = 1;
+        loop {
+            match self.get(i) {
+                Some(c) if *c == quote => return None,
+                Some('\\') => i += 2,
+                Some('\n') | None => return self.pop_front(),
+                Some(_) => i += 1,
             }
-            num = num * 10 + (c.to_digit(10).unwrap() as usize);
+        }
+    }
+
+    /// 文字リテラルを読み取り、その値を返す。文字リテラルはint型の整数として扱う。
+    /// ワイド文字リテラル(L'...')のwchar_tもint型なので、同じように読む。
+    pub fn parse_char(&mut self) -> Option<i64> {
+        if self.starts_with("L'") {
             self.pop_front();
         }
-        Some(num)
+        if self.front()? != &'\'' {
+            return None;
+        }
+        self.pop_front();
+        let c = match self.pop_front() {
+            Some('\\') => self.parse_escape(),
+            Some('\'' | '\n') | None => panic!("文字リテラルが正しくありません"),
+            Some(c) => c,
+        };
+        if self.pop_front() != Some('\'') {
+            panic!("文字リテラルが閉じられていません");
+        }
+        // charは符号付きなので、1バイトの文字は符号拡張する
+        match u8::try_from(c) {
+            Ok(b) => Some(b as i8 as i64),
+            Err(_) => Some(c as i64),
+        }
     }
 
-    pub fn parse_op(&mut self) -> Option<String> {
-        match self.front()? {
-            '=' => match self.get(1)? {
-                '=' => {
-                    self.pop_front()?;
-                    self.pop_front()?;
-                    Some("==".to_string())
-                }
-                _ => {
-                    self.pop_front()?;
-                    Some("=".to_string())
-                }
-            },
-            '!' => match self.get(1)? {
-                '=' => {
-                    self.pop_front()?;
-                    self.pop_front()?;
-                    Some("!=".to_string())
+    /// '\\' の後のエスケープシーケンスを読み取り、それが表す文字を返す。
+    /// \x に続く16進数と、3桁までの8進数は文字コードを表す。
+    fn parse_escape(&mut self) -> char {
+        if self.front().is_some_and(|c| c.is_digit(8)) {
+            let mut code = 0;
+            for _ in 0..3 {
+                match self.front().and_then(|c| c.to_digit(8)) {
+                    Some(d) => code = code * 8 + d,
+                    None => break,
                 }
-                _ => None,
-            },
-            '<' => match self.get(1)? {
-                '=' => {
-                    self.pop_front()?;
-                    self.pop_front()?;
-                    Some("<=".to_string())
-                }
-                _ => {
-                    self.pop_front()?;
-                    Some("<".to_string())
-                }
-            },
-            '>' => match self.get(1)? {
-                '=' => {
-                    self.pop_front()?;
-                    self.pop_front()?;
-                    Some(">=".to_string())
-                }
-                _ => {
-                    self.pop_front()?;
-                    Some(">".to_string())
+                self.pop_front();
+            }
+            return char::from_u32(code).unwrap();
+        }
+        if self.starts_with("x") && self.get(1).is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.pop_front();
+            let mut code: u32 = 0;
+            while let Some(d) = self.front().and_then(|c| c.to_digit(16)) {
+                code = code.saturating_mul(16).saturating_add(d);
+                self.pop_front();
+            }
+            return match char::from_u32(code) {
+                Some(c) => c,
+                None => panic!("エスケープシーケンスの文字コードが不正です: {code:x}"),
+            };
+        }
+        match self.pop_front() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('a') => '\x07',
+            Some('b') => '\x08',
+            Some('f') => '\x0c',
+            Some('v') => '\x0b',
+            Some('e') => '\x1b',
+            Some(c) => c,
+            None => panic!("エスケープシーケンスが正しくありません"),
+        }
+    }
+
+    /// 浮動小数点数のリテラルを読み取り、接尾辞を含めたその文字列を返す。
+    /// 小数点も指数部も含まない場合は整数なので、何も読み進めずにNoneを返す。
+    pub fn parse_float(&mut self) -> Option<String> {
+        let is_digit = |i: usize| self.get(i).is_some_and(|c| c.is_ascii_digit());
+
+        let mut i = 0;
+        while is_digit(i) {
+            i += 1;
+        }
+        let mut is_float = false;
+        if self.get(i) == Some(&'.') {
+            // 数字を伴わない '.' はメンバ参照の演算子
+            if i == 0 && !is_digit(1) {
+                return None;
+            }
+            is_float = true;
+            i += 1;
+            while is_digit(i) {
+                i += 1;
+            }
+        }
+        if i == 0 {
+            return None;
+        }
+        if matches!(self.get(i), Some('e' | 'E')) {
+            let mut j = i + 1;
+            if matches!(self.get(j), Some('+' | '-')) {
+                j += 1;
+            }
+            if is_digit(j) {
+                is_float = true;
+                i = j;
+                while is_digit(i) {
+                    i += 1;
                 }
-            },
-            '+' | '-' | '*' | '/' | '(' | ')' => {
-                let c = self.pop_front()?;
-                Some(c.to_string())
             }
-            ';' => {
-                let c = self.pop_front()?;
-                Some(c.to_string())
+        }
+        if !is_float {
+            return None;
+        }
+        if matches!(self.get(i), Some('f' | 'F' | 'l' | 'L')) {
+            i += 1;
+        }
+        Some(self.chars.drain(..i).collect())
+    }
+
+    /// 記号を読み取る。複数の文字からなる記号は、できるだけ長く読み取る。
+    pub fn parse_op(&mut self) -> Option<String> {
+        let op = PUNCTUATORS.iter().find(|op| self.starts_with(op))?;
+        self.chars.drain(..op.len());
+        Some(op.to_string())
+    }
+
+    /// 文字列リテラルを読み取り、エスケープシーケンスを解釈した中身を返す。
+    /// 閉じる '"' がない場合はパニックする。
+    pub fn parse_str(&mut self) -> Option<String> {
+        if self.front()? != &'"' {
+            return None;
+        }
+        self.pop_front();
+
+        let mut s = String::new();
+        loop {
+            match self.pop_front() {
+                Some('"') => return Some(s),
+                Some('\\') => s.push(self.parse_escape()),
+                Some(c) => s.push(c),
+                None => panic!("文字列リテラルが閉じられていません"),
             }
-            _ => None,
         }
     }
 
+    /// 識別子を読み取る。識別子は英字か'_'で始まり、英数字か'_'が続く。
     pub fn parse_lvar(&mut self) -> Option<String> {
-        if !self.front()?.is_ascii_alphabetic() {
+        if !is_ident_start(self.front()?) {
             return None;
         }
 
         let mut lvar = String::new();
         while let Some(c) = self.front() {
-            if c.is_ascii_alphanumeric() {
+            if is_ident_char(c) {
                 lvar.push(*c);
                 self.pop_front();
             } else {
@@ -122,43 +257,237 @@ impl UserInput {
     }
 
     pub fn parse_return(&mut self) -> Option<Token> {
-        if self.starts_with("return") && !self.get(6)?.is_ascii_alphanumeric() {
+        if self.starts_with("return") && !is_ident_char(self.get(6)?) {
             self.chars.drain(..6);
             Some(Token {
                 kind: TokenKind::Return,
                 val: 0,
                 str: "return".to_string(),
                 len: 6,
+                loc: Location::default(),
+                hideset: Vec::new(),
+                spelling: None,
             })
         } else {
             None
         }
     }
+
+    /// `return` 以外の予約語を読み取る。
+    /// 予約語の直後に英数字か'_'が続く場合は識別子の一部とみなし、Noneを返す。
+    pub fn parse_keyword(&mut self) -> Option<Token> {
+        let (word, kind) = KEYWORDS.iter().find(|(word, _)| {
+            self.starts_with(word) && !self.get(word.len()).is_some_and(is_ident_char)
+        })?;
+        self.chars.drain(..word.len());
+        Some(Token {
+            kind: *kind,
+            val: 0,
+            str: word.to_string(),
+            len: word.len(),
+            loc: Location::default(),
+            hideset: Vec::new(),
+            spelling: None,
+        })
+    }
+
+    /// コメントを読み飛ばし、読み飛ばした部分に含まれていた改行の数を返す。
+    /// コメントでなければ何も読み進めずにNoneを返す。
+    pub fn skip_comment(&mut self) -> Option<usize> {
+        if self.starts_with("//") {
+            while self.front().is_some_and(|c| c != &'\n') {
+                self.pop_front();
+            }
+            return Some(0);
+        }
+        if !self.starts_with("/*") {
+            return None;
+        }
+        self.chars.drain(..2);
+        let mut lines = 0;
+        while !self.starts_with("*/") {
+            match self.pop_front() {
+                Some('\n') => lines += 1,
+                Some(_) => {}
+                None => panic!("コメントが閉じられていません"),
+            }
+        }
+        self.chars.drain(..2);
+        Some(lines)
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+fn is_ident_start(c: &char) -> bool {
+    c.is_ascii_alphabetic() || c == &'_'
+}
+
+fn is_ident_char(c: &char) -> bool {
+    c.is_ascii_alphanumeric() || c == &'_'
+}
+
+/// 記号の一覧。長いものから順に並べる。
+const PUNCTUATORS: [&str; 48] = [
+    "<<=", ">>=", "...", "==", "!=", "<=", ">=", "->", "++", "--", "+=", "-=", "*=", "/=", "%=",
+    "&=", "|=", "^=", "&&", "||", "<<", ">>", "##", "+", "-", "*", "/", "%", "(", ")", "<", ">",
+    "=", "!", "&", "|", "^", "~", "?", ":", ";", ",", ".", "[", "]", "{", "}", "#",
+];
+
+/// 予約語の一覧。"__" で始まるものは、glibcのヘッダファイルで使われるGNU拡張の綴り。
+const KEYWORDS: [(&str, TokenKind); 40] = [
+    ("do", TokenKind::Do),
+    ("while", TokenKind::While),
+    ("continue", TokenKind::Continue),
+    ("sizeof", TokenKind::Sizeof),
+    ("char", TokenKind::Char),
+    ("short", TokenKind::Short),
+    ("int", TokenKind::Int),
+    ("long", TokenKind::Long),
+    ("signed", TokenKind::Signed),
+    ("unsigned", TokenKind::Unsigned),
+    ("float", TokenKind::FloatType),
+    ("double", TokenKind::Double),
+    ("struct", TokenKind::Struct),
+    ("union", TokenKind::Union),
+    ("enum", TokenKind::Enum),
+    ("typedef", TokenKind::Typedef),
+    ("static", TokenKind::Static),
+    ("extern", TokenKind::Extern),
+    ("const", TokenKind::Const),
+    ("volatile", TokenKind::Volatile),
+    ("void", TokenKind::Void),
+    ("_Bool", TokenKind::Bool),
+    ("_Alignof", TokenKind::Alignof),
+    ("_Alignas", TokenKind::Alignas),
+    ("restrict", TokenKind::Restrict),
+    ("__restrict", TokenKind::Restrict),
+    ("__restrict__", TokenKind::Restrict),
+    ("inline", TokenKind::Inline),
+    ("__inline", TokenKind::Inline),
+    ("__inline__", TokenKind::Inline),
+    ("__const", TokenKind::Const),
+    ("__volatile__", TokenKind::Volatile),
+    ("__signed__", TokenKind::Signed),
+    ("__alignof__", TokenKind::Alignof),
+    ("__attribute__", TokenKind::Attribute),
+    ("__attribute", TokenKind::Attribute),
+    ("__extension__", TokenKind::Extension),
+    ("__asm__", TokenKind::Asm),
+    ("__asm", TokenKind::Asm),
+    ("__builtin_va_list", TokenKind::VaList),
+];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum TokenKind {
-    Reserved, // 記号
-    Return,   // return
-    Ident,    // 識別子
-    Num,      // 整数トークン
+    Reserved,  // 記号
+    Return,    // return
+    Do,        // do
+    While,     // while
+    Continue,  // continue
+    Sizeof,    // sizeof
+    Char,      // char
+    Short,     // short
+    Int,       // int
+    Long,      // long
+    Signed,    // signed
+    Unsigned,  // unsigned
+    FloatType, // float
+    Double,    // double
+    Struct,    // struct
+    Union,     // union
+    Enum,      // enum
+    Typedef,   // typedef
+    Static,    // static
+    Extern,    // extern
+    Const,     // const
+    Volatile,  // volatile
+    Void,      // void
+    Bool,      // _Bool
+    Alignof,   // _Alignof
+    Alignas,   // _Alignas
+    Restrict,  // restrict
+    Inline,    // inline
+    Attribute, // __attribute__
+    Extension, // __extension__
+    Asm,       // __asm__
+    VaList,    // __builtin_va_list
+    Ident,     // 識別子
+    Num,       // 整数トークン
+    Float,     // 浮動小数点数トークン
+    Str,       // 文字列リテラル
+    Stray,     // 閉じられていない引用符。前処理で読み飛ばされなければエラーになる
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub val: i64,
     pub str: String,
     pub len: usize,
+    pub loc: Location,
+    pub hideset: Vec<String>, // マクロ展開で使う。このトークンから展開してはならないマクロの名前
+    pub spelling: Option<String>, // 整数・文字・文字列リテラルのソースコード上の綴り
+}
+
+/// トークンのソースコード上の位置。プリプロセッサが行の区切りを判断するのに使う。
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Location {
+    pub file: Rc<str>,   // ファイル名
+    pub line: usize,     // 行番号(1始まり)
+    pub at_bol: bool,    // 行頭のトークンの場合に真
+    pub has_space: bool, // 直前に空白がある場合に真
+}
+
+/// エラーメッセージに使う "ファイル名:行番号" の形式
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
 }
 
 impl Token {
+    /// ソースコード上の綴り。ソースコードから読んだリテラルはその綴りをそのまま返す。
+    /// マクロが作ったリテラルは、整数は値から、文字列リテラルは中身をエスケープし直して作る。
+    pub fn text(&self) -> String {
+        if let Some(spelling) = &self.spelling {
+            return spelling.clone();
+        }
+        match self.kind {
+            // 整数リテラルは負にならないので、符号なしとして綴る
+            TokenKind::Num => format!("{}{}", self.val as u64, self.str.trim()),
+            TokenKind::Str => {
+                let mut s = String::from('"');
+                for c in self.str.chars() {
+                    match c {
+                        '"' => s.push_str("\\\""),
+                        '\\' => s.push_str("\\\\"),
+                        '\n' => s.push_str("\\n"),
+                        '\t' => s.push_str("\\t"),
+                        '\r' => s.push_str("\\r"),
+                        '\0' => s.push_str("\\0"),
+                        '\x07' => s.push_str("\\a"),
+                        '\x08' => s.push_str("\\b"),
+                        '\x0c' => s.push_str("\\f"),
+                        '\x0b' => s.push_str("\\v"),
+                        '\x1b' => s.push_str("\\e"),
+                        c => s.push(c),
+                    }
+                }
+                s.push('"');
+                s
+            }
+            _ => self.str.clone(),
+        }
+    }
+
     pub fn new_op(c: String) -> TokenizeResult<Token> {
         Ok(Self {
             kind: TokenKind::Reserved,
             val: 0,
             str: c.clone(),
             len: c.len(),
+            loc: Location::default(),
+            hideset: Vec::new(),
+            spelling: None,
         })
     }
 
@@ -168,6 +497,63 @@ impl Token {
             val,
             str: " ".to_string(),
             len: 1,
+            loc: Location::default(),
+            hideset: Vec::new(),
+            spelling: None,
+        })
+    }
+
+    /// 接尾辞のある整数リテラルは、接尾辞をstrに格納する。パーサが型を決めるのに使う。
+    pub fn new_int(val: i64, suffix: String) -> TokenizeResult<Token> {
+        if suffix.is_empty() {
+            return Self::new_num(val);
+        }
+        Ok(Self {
+            kind: TokenKind::Num,
+            val,
+            len: suffix.len(),
+            str: suffix,
+            loc: Location::default(),
+            hideset: Vec::new(),
+            spelling: None,
+        })
+    }
+
+    /// 浮動小数点数の値は、パーサがstrを解釈して求める
+    pub fn new_float(literal: String) -> TokenizeResult<Token> {
+        Ok(Self {
+            kind: TokenKind::Float,
+            val: 0,
+            str: literal.clone(),
+            len: literal.len(),
+            loc: Location::default(),
+            hideset: Vec::new(),
+            spelling: None,
+        })
+    }
+
+    /// strにはエスケープシーケンスを解釈した後の文字列の中身を格納する
+    pub fn new_str(s: String) -> TokenizeResult<Token> {
+        Ok(Self {
+            kind: TokenKind::Str,
+            val: 0,
+            len: s.len(),
+            str: s,
+            loc: Location::default(),
+            hideset: Vec::new(),
+            spelling: None,
+        })
+    }
+
+    pub fn new_stray(quote: char) -> TokenizeResult<Token> {
+        Ok(Self {
+            kind: TokenKind::Stray,
+            val: 0,
+            str: quote.to_string(),
+            len: 1,
+            loc: Location::default(),
+            hideset: Vec::new(),
+            spelling: None,
         })
     }
 
@@ -177,15 +563,24 @@ impl Token {
             val: 0,
             str: lvar.clone(),
             len: lvar.len(),
+            loc: Location::default(),
+            hideset: Vec::new(),
+            spelling: None,
         })
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct Tokens {
     pub user_input: String,
     pub lvars: LVars,
     pub tokens: VecDeque<Token>,
+    pub label_count: i64,                 // これまでに割り当てたラベル番号の数
+    pub continue_label: Option<i64>,      // continue文の飛び先となるループのラベル番号
+    pub vla_sizes: Vec<Node>,             // 可変長配列の大きさを計算する、未評価の式
+    pub param_names: Vec<Option<String>>, // 最後に読んだ関数の宣言子の引数の名前
+    pub is_inline: bool,                  // 読んでいる宣言の宣言指定子にinlineがあるかどうか
+    pub func: Option<Function>,           // 本体を読んでいる関数の定義。トップレベルの文ではNone
 }
 
 impl Tokens {
@@ -194,6 +589,12 @@ impl Tokens {
             user_input,
             lvars: LVars::new(),
             tokens: VecDeque::with_capacity(capasity),
+            label_count: 0,
+            continue_label: None,
+            vla_sizes: Vec::new(),
+            param_names: Vec::new(),
+            is_inline: false,
+            func: None,
         }
     }
 
@@ -205,10 +606,25 @@ impl Tokens {
         self.tokens.pop_front()
     }
 
+    /// 読み進めたトークンを先頭に戻す
+    pub fn push_front(&mut self, token: Token) {
+        self.tokens.push_front(token)
+    }
+
     pub fn front(&self) -> Option<&Token> {
         self.tokens.front()
     }
 
+    /// 次のトークンの位置。構文木のノードに記録して、意味解析のエラーの表示に使う。
+    pub fn loc(&self) -> Option<Location> {
+        self.front().map(|tk| tk.loc.clone())
+    }
+
+    /// index番目のトークンを読み進めずに参照する
+    pub fn get(&self, index: usize) -> Option<&Token> {
+        self.tokens.get(index)
+    }
+
     pub fn len(&self) -> usize {
         self.tokens.len()
     }
@@ -227,50 +643,115 @@ impl Tokens {
     }
 
     pub fn consume_return(&mut self) -> bool {
+        self.consume(TokenKind::Return)
+    }
+
+    /// 次のトークンが指定された種類のときには、トークンを1つ読み進めて真を返す。
+    /// それ以外の場合には偽を返す。
+    pub fn consume(&mut self, kind: TokenKind) -> bool {
         let token = match self.front() {
             Some(token) => token,
             None => return false,
         };
-        if token.kind == TokenKind::Return {
+        if token.kind == kind {
             self.pop_front();
-            return true;
+            true
         } else {
             false
         }
     }
 }
 
+/// コマンドラインで与えられたソースコードのファイル名として扱う名前
+pub const INPUT_NAME: &str = "<input>";
+
 pub fn tokenize(input: String) -> TokenizeResult<Tokens> {
+    tokenize_file(input, INPUT_NAME)
+}
+
+/// fileという名前のファイルの中身としてinputをトークンに分割し、各トークンにその位置を記録する。
+pub fn tokenize_file(input: String, file: &str) -> TokenizeResult<Tokens> {
     let mut user_input = UserInput::new(input.clone());
+    let chars: Vec<char> = input.chars().collect();
     let mut tokens = Tokens::init(input.clone(), input.capacity());
+    let file: Rc<str> = Rc::from(file);
+    let mut line = 1;
+    let mut at_bol = true;
+    let mut has_space = false;
 
     while let Some(c) = user_input.front() {
-        if c.is_ascii_whitespace() {
+        if c == &'\n' {
             user_input.pop_front();
+            line += 1;
+            at_bol = true;
+            has_space = false;
             continue;
         }
-
-        if let Some(return_token) = user_input.parse_return() {
-            tokens.push_back(return_token);
+        if c.is_ascii_whitespace() {
+            user_input.pop_front();
+            has_space = true;
             continue;
         }
-
-        if let Some(lvar) = user_input.parse_lvar() {
-            tokens.push_back(Token::new_lvar(lvar)?);
+        // 行末の'\'は次の行との連結を表す
+        if user_input.starts_with("\\\n") {
+            user_input.chars.drain(..2);
+            line += 1;
+            has_space = true;
             continue;
         }
-
-        if let Some(op) = user_input.parse_op() {
-            tokens.push_back(Token::new_op(op)?);
+        if let Some(lines) = user_input.skip_comment() {
+            line += lines;
+            has_space = true;
             continue;
         }
 
-        if let Some(num) = user_input.parse_num() {
-            tokens.push_back(Token::new_num(num as i64)?);
-            continue;
+        let loc = Location {
+            file: file.clone(),
+            line,
+            at_bol,
+            has_space,
+        };
+        at_bol = false;
+        has_space = false;
+        let start = chars.len() - user_input.chars.len();
+        let mut token = if let Some(return_token) = user_input.parse_return() {
+            return_token
+        } else if let Some(keyword) = user_input.parse_keyword() {
+            keyword
+        } else if let Some(quote) = user_input.parse_stray_quote() {
+            Token::new_stray(quote)?
+        } else if let Some(c) = user_input.parse_char() {
+            Token::new_num(c)?
+        } else if let Some(lvar) = user_input.parse_lvar() {
+            Token::new_lvar(lvar)?
+        } else if let Some(s) = user_input.parse_str() {
+            Token::new_str(s)?
+        } else if let Some(literal) = user_input.parse_float() {
+            Token::new_float(literal)?
+        } else if let Some(op) = user_input.parse_op() {
+            Token::new_op(op)?
+        } else if let Some(num) = user_input.parse_num().map_err(|e| match e {
+            TokenizeError::InvalidNumber(digits) => {
+                TokenizeError::InvalidNumber(format!("{file}:{line}: {digits}"))
+            }
+            e => e,
+        })? {
+            Token::new_int(num as i64, user_input.parse_int_suffix())?
+        } else {
+            let rest: String = user_input
+                .chars
+                .iter()
+                .take_while(|c| **c != '\n')
+                .collect();
+            return Err(TokenizeError::InvalidSyntax(format!(
+                "{file}:{line}: {rest}"
+            )));
+        };
+        if matches!(token.kind, TokenKind::Num | TokenKind::Str) {
+            let end = chars.len() - user_input.chars.len();
+            token.spelling = Some(chars[start..end].iter().collect());
         }
-
-        return Err(TokenizeError::InvalidSyntax(input));
+        tokens.push_back(Token { loc, ..token });
     }
 
     Ok(tokens)
@@ -278,23 +759,131 @@ pub fn tokenize(input: String) -> TokenizeResult<Tokens> {
 
 #[cfg(test)]
 mod tests_userinput {
-    use crate::tokenize::Token;
+    use crate::result::TokenizeError;
+    use crate::tokenize::{Location, Token};
 
     use super::UserInput;
 
     #[test]
     fn parse_num() {
         let mut user_input = UserInput::new("123".to_string());
-        assert_eq!(user_input.parse_num(), Some(123));
-        assert_eq!(user_input.parse_num(), None);
+        assert_eq!(user_input.parse_num(), Ok(Some(123)));
+        assert_eq!(user_input.parse_num(), Ok(None));
     }
 
     #[test]
     fn parse_num_to_be_skipped() {
         let mut user_input_op = UserInput::new("+".to_string());
         let mut user_input_lvar = UserInput::new("abc".to_string());
-        assert_eq!(user_input_op.parse_num(), None);
-        assert_eq!(user_input_lvar.parse_num(), None);
+        assert_eq!(user_input_op.parse_num(), Ok(None));
+        assert_eq!(user_input_lvar.parse_num(), Ok(None));
+    }
+
+    #[test]
+    fn parse_num_with_prefix_and_suffix() {
+        for (input, expected, suffix) in [
+            ("0x1fUL", 31, "ul"),
+            ("0XffU", 255, "u"),
+            ("0b101", 5, ""),
+            ("017", 15, ""),
+            ("0", 0, ""),
+            ("10lu", 10, "lu"),
+            ("7LL", 7, "ll"),
+        ] {
+            let mut user_input = UserInput::new(input.to_string());
+            assert_eq!(user_input.parse_num(), Ok(Some(expected)), "{input}");
+            assert_eq!(user_input.parse_int_suffix(), suffix, "{input}");
+            assert_eq!(user_input.front(), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn parse_num_too_large() {
+        let mut user_input = UserInput::new("18446744073709551615".to_string());
+        assert_eq!(user_input.parse_num(), Ok(Some(usize::MAX)));
+        for input in [
+            "18446744073709551616",
+            "0x10000000000000000",
+            "99999999999999999999",
+        ] {
+            let mut user_input = UserInput::new(input.to_string());
+            assert!(
+                matches!(user_input.parse_num(), Err(TokenizeError::InvalidNumber(_))),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_char() {
+        for (input, expected) in [
+            ("'a'", 97),
+            ("'\\n'", 10),
+            ("'\\''", 39),
+            ("'\\x7f'", 127),
+            ("'\\377'", -1),
+            ("'\\0'", 0),
+            ("L'\\x100'", 256),
+        ] {
+            let mut user_input = UserInput::new(input.to_string());
+            assert_eq!(user_input.parse_char(), Some(expected), "{input}");
+            assert_eq!(user_input.front(), None, "{input}");
+        }
+        assert_eq!(UserInput::new("L".to_string()).parse_char(), None);
+    }
+
+    #[test]
+    fn parse_float() {
+        for (input, expected) in [
+            ("1.5", "1.5"),
+            ("1.", "1."),
+            (".5;", ".5"),
+            ("1e3", "1e3"),
+            ("2.5E-2f+1", "2.5E-2f"),
+            ("3.0L", "3.0L"),
+        ] {
+            let mut user_input = UserInput::new(input.to_string());
+            assert_eq!(
+                user_input.parse_float(),
+                Some(expected.to_string()),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_float_to_be_skipped() {
+        for input in ["123", "1e", "1ex", ".a", "abc", "+"] {
+            let mut user_input = UserInput::new(input.to_string());
+            assert_eq!(user_input.parse_float(), None, "{input}");
+            assert_eq!(user_input.chars.len(), input.len(), "{input}");
+        }
+    }
+
+    #[test]
+    fn parse_str() {
+        let mut user_input = UserInput::new(r#""a\"b\n\\" c"#.to_string());
+        assert_eq!(user_input.parse_str(), Some("a\"b\n\\".to_string()));
+        assert_eq!(user_input.parse_str(), None);
+        assert_eq!(user_input.chars.len(), 2);
+    }
+
+    #[test]
+    fn parse_stray_quote() {
+        let mut user_input = UserInput::new("'t s\n'a'".to_string());
+        assert_eq!(user_input.parse_stray_quote(), Some('\''));
+        assert_eq!(user_input.front(), Some(&'t'));
+        for input in ["'a'", "'\\''", "\"a'\"", "x'"] {
+            assert_eq!(
+                UserInput::new(input.to_string()).parse_stray_quote(),
+                None,
+                "{input}"
+            );
+        }
+        assert_eq!(
+            UserInput::new("\"a\\\"".to_string()).parse_stray_quote(),
+            Some('"')
+        );
     }
 
     #[test]
@@ -309,6 +898,10 @@ mod tests_userinput {
         let mut user_input = UserInput::new("<=".to_string());
         assert_eq!(user_input.parse_op(), Some("<=".to_string()));
         assert_eq!(user_input.parse_op(), None);
+
+        let mut user_input = UserInput::new("###".to_string());
+        assert_eq!(user_input.parse_op(), Some("##".to_string()));
+        assert_eq!(user_input.parse_op(), Some("#".to_string()));
     }
 
     #[test]
@@ -344,6 +937,9 @@ mod tests_userinput {
                 str: "return".to_string(),
                 val: 0,
                 len: 6,
+                loc: Location::default(),
+                hideset: Vec::new(),
+                spelling: None,
             })
         );
         assert_eq!(user_input.parse_return(), None);
@@ -358,6 +954,34 @@ mod tests_userinput {
         assert_eq!(user_input_op.parse_return(), None);
         assert_eq!(user_input_lvar.parse_return(), None);
     }
+
+    #[test]
+    fn parse_keyword() {
+        let mut user_input = UserInput::new("do a; while".to_string());
+        assert_eq!(
+            user_input.parse_keyword(),
+            Some(Token {
+                kind: super::TokenKind::Do,
+                str: "do".to_string(),
+                val: 0,
+                len: 2,
+                loc: Location::default(),
+                hideset: Vec::new(),
+                spelling: None,
+            })
+        );
+        assert_eq!(user_input.parse_keyword(), None);
+    }
+
+    #[test]
+    fn parse_keyword_to_be_skipped() {
+        let mut user_input_prefix = UserInput::new("done".to_string());
+        let mut user_input_short = UserInput::new("whil".to_string());
+        let mut user_input_return = UserInput::new("return".to_string());
+        assert_eq!(user_input_prefix.parse_keyword(), None);
+        assert_eq!(user_input_short.parse_keyword(), None);
+        assert_eq!(user_input_return.parse_keyword(), None);
+    }
 }
 
 #[cfg(test)]
@@ -509,6 +1133,84 @@ mod tests_tokenize {
         assert_eq!(result[2].val, 2);
     }
 
+    #[test]
+    fn shift_operators_with_spaces() {
+        let result = tokenize("1 << 2 >> 3 <= 4".to_string()).unwrap().tokens;
+        assert_eq!(result.len(), 7);
+        assert_eq!(result[1].str, "<<");
+        assert_eq!(result[3].str, ">>");
+        assert_eq!(result[5].str, "<=");
+    }
+
+    #[test]
+    fn array_initializer() {
+        let result = tokenize("char s[] = \"a b\";".to_string()).unwrap().tokens;
+        assert_eq!(result.len(), 7);
+        assert_eq!(result[2].str, "[");
+        assert_eq!(result[3].str, "]");
+        assert_eq!(result[5].kind, TokenKind::Str);
+        assert_eq!(result[5].str, "a b");
+    }
+
+    #[test]
+    fn function_pointer_declaration() {
+        let result = tokenize("void (*f)(int, ...);".to_string()).unwrap().tokens;
+        assert_eq!(result.len(), 11);
+        assert_eq!(result[0].kind, TokenKind::Void);
+        assert_eq!(result[8].str, "...");
+        assert_eq!(result[9].str, ")");
+    }
+
+    #[test]
+    fn logical_operators() {
+        let result = tokenize("!a && _Bool || b != c".to_string())
+            .unwrap()
+            .tokens;
+        assert_eq!(result.len(), 8);
+        assert_eq!(result[0].str, "!");
+        assert_eq!(result[2].str, "&&");
+        assert_eq!(result[3].kind, TokenKind::Bool);
+        assert_eq!(result[4].str, "||");
+        assert_eq!(result[6].str, "!=");
+    }
+
+    #[test]
+    fn alignment_keywords() {
+        let result = tokenize("_Alignof(int) _Alignas(8) x".to_string())
+            .unwrap()
+            .tokens;
+        assert_eq!(result.len(), 9);
+        assert_eq!(result[0].kind, TokenKind::Alignof);
+        assert_eq!(result[4].kind, TokenKind::Alignas);
+        assert_eq!(result[8].kind, TokenKind::Ident);
+    }
+
+    #[test]
+    fn gnu_keywords() {
+        let result = tokenize(
+            "__extension__ __attribute__ __asm__ __restrict restrict __inline __const \
+             __builtin_va_list __restrict_x"
+                .to_string(),
+        )
+        .unwrap()
+        .tokens;
+        let kinds: Vec<TokenKind> = result.iter().map(|tk| tk.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Extension,
+                TokenKind::Attribute,
+                TokenKind::Asm,
+                TokenKind::Restrict,
+                TokenKind::Restrict,
+                TokenKind::Inline,
+                TokenKind::Const,
+                TokenKind::VaList,
+                TokenKind::Ident,
+            ]
+        );
+    }
+
     #[test]
     fn ne_operator_with_spaces() {
         let result = tokenize("1 != 2".to_string()).unwrap().tokens;
@@ -575,12 +1277,76 @@ mod tests_tokenize {
         assert_eq!(result[11].val, 3);
     }
 
+    #[test]
+    fn do_while_keywords() {
+        let result = tokenize("do continue; while (a);".to_string())
+            .unwrap()
+            .tokens;
+        assert_eq!(result.len(), 8);
+        assert_eq!(result[0].kind, TokenKind::Do);
+        assert_eq!(result[1].kind, TokenKind::Continue);
+        assert_eq!(result[2].str, ";");
+        assert_eq!(result[3].kind, TokenKind::While);
+        assert_eq!(result[4].str, "(");
+        assert_eq!(result[5].kind, TokenKind::Ident);
+        assert_eq!(result[6].str, ")");
+        assert_eq!(result[7].str, ";");
+    }
+
+    #[test]
+    fn struct_operators() {
+        let result = tokenize("struct {int a;} *p; &p->a - x.a;".to_string())
+            .unwrap()
+            .tokens;
+        assert_eq!(result.len(), 18);
+        assert_eq!(result[0].kind, TokenKind::Struct);
+        assert_eq!(result[1].str, "{");
+        assert_eq!(result[2].kind, TokenKind::Int);
+        assert_eq!(result[5].str, "}");
+        assert_eq!(result[6].str, "*");
+        assert_eq!(result[9].str, "&");
+        assert_eq!(result[11].str, "->");
+        assert_eq!(result[13].str, "-");
+        assert_eq!(result[15].str, ".");
+    }
+
     #[test]
     fn invalid_operator() {
-        let result = tokenize("1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 0 % 4".to_string());
+        let result = tokenize("1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 0 @ 4".to_string());
         assert!(
             result.is_err(),
             "エラーが発生すべきですが、発生しませんでした。\n{result:?}"
         );
     }
+
+    #[test]
+    fn locations() {
+        let result = tokenize("#define A 1 // x\n  A /* a\nb */ _x1\\\n;".to_string())
+            .unwrap()
+            .tokens;
+        let locs: Vec<_> = result
+            .iter()
+            .map(|tk| {
+                (
+                    tk.str.as_str(),
+                    tk.loc.line,
+                    tk.loc.at_bol,
+                    tk.loc.has_space,
+                )
+            })
+            .collect();
+        assert_eq!(
+            locs,
+            vec![
+                ("#", 1, true, false),
+                ("define", 1, false, false),
+                ("A", 1, false, true),
+                (" ", 1, false, true),
+                ("A", 2, true, true),
+                ("_x1", 3, false, true),
+                (";", 4, false, true),
+            ]
+        );
+        assert_eq!(&*result[0].loc.file, "<input>");
+    }
 }
@@ -0,0 +1,615 @@
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TypeKind {
+    Bool,    // _Bool
+    Char,    // char
+    Short,   // short
+    Int,     // int
+    Long,    // long (宣言なしで使われた変数もこの型になる)
+    Ptr,     // pointer
+    Array,   // array
+    Vla,     // 可変長配列 (要素の領域へのポインタとして扱う)
+    Struct,  // struct
+    Union,   // union
+    Enum,    // enum (intとして扱う)
+    Float,   // float
+    Double,  // double
+    LDouble, // long double (大きさとアラインメントだけを扱い、値は扱わない)
+    Void,    // void
+    Func,    // function
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub size: i64,                    // sizeofの値
+    pub align: i64,                   // アライメント
+    pub is_unsigned: bool,            // 符号なし整数型(とポインタ)の場合に真
+    pub is_const: bool,               // const修飾されている場合に真
+    pub is_volatile: bool,            // volatile修飾されている場合に真
+    pub base: Option<Box<Type>>,      // kindがPtrかArrayかVlaの場合のみ使う
+    pub array_len: i64,               // kindがArrayの場合のみ使う。省略された場合は-1
+    pub vla_size: i64,                // kindがVlaの場合のみ使う。大きさを格納する変数のオフセット
+    pub bit_width: i64,               // ビットフィールドのメンバの型の場合のみ使う。ビット幅
+    pub bit_offset: i64,              // ビットフィールドのメンバの型の場合のみ使う。ビット位置
+    pub tag: Option<String>,          // kindがStructかUnionかEnumの場合のみ使う
+    pub members: Vec<Member>,         // kindがStructかUnionの場合のみ使う
+    pub return_ty: Option<Box<Type>>, // kindがFuncの場合のみ使う
+    pub params: Vec<Type>,            // kindがFuncの場合のみ使う
+    pub is_variadic: bool,            // kindがFuncで、可変長引数を取るか引数が不明な場合に真
+}
+
+/// 構造体・共用体のメンバ
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Member {
+    pub name: String,
+    pub ty: Type,
+    pub offset: i64,
+}
+
+impl Type {
+    fn new(kind: TypeKind, size: i64, align: i64) -> Self {
+        Self {
+            kind,
+            size,
+            align,
+            is_unsigned: false,
+            is_const: false,
+            is_volatile: false,
+            base: None,
+            array_len: 0,
+            vla_size: 0,
+            bit_width: 0,
+            bit_offset: 0,
+            tag: None,
+            members: Vec::new(),
+            return_ty: None,
+            params: Vec::new(),
+            is_variadic: false,
+        }
+    }
+
+    /// 値は0か1のみなので、符号なしとして扱う
+    pub fn bool() -> Self {
+        Self {
+            is_unsigned: true,
+            ..Self::new(TypeKind::Bool, 1, 1)
+        }
+    }
+
+    pub fn char() -> Self {
+        Self::new(TypeKind::Char, 1, 1)
+    }
+
+    pub fn short() -> Self {
+        Self::new(TypeKind::Short, 2, 2)
+    }
+
+    pub fn int() -> Self {
+        Self::new(TypeKind::Int, 4, 4)
+    }
+
+    pub fn long() -> Self {
+        Self::new(TypeKind::Long, 8, 8)
+    }
+
+    pub fn float() -> Self {
+        Self::new(TypeKind::Float, 4, 4)
+    }
+
+    pub fn double() -> Self {
+        Self::new(TypeKind::Double, 8, 8)
+    }
+
+    /// x86-64のlong doubleは80ビットの拡張倍精度で、16バイトの領域に置かれる
+    pub fn ldouble() -> Self {
+        Self::new(TypeKind::LDouble, 16, 16)
+    }
+
+    pub fn void() -> Self {
+        Self::new(TypeKind::Void, 1, 1)
+    }
+
+    /// 関数型。sizeofはgccに合わせて1とする。
+    pub fn func(return_ty: Type, params: Vec<Type>, is_variadic: bool) -> Self {
+        Self {
+            return_ty: Some(Box::new(return_ty)),
+            params,
+            is_variadic,
+            ..Self::new(TypeKind::Func, 1, 1)
+        }
+    }
+
+    /// 同じ大きさの符号なし整数型にする
+    pub fn unsigned(self) -> Self {
+        Self {
+            is_unsigned: true,
+            ..self
+        }
+    }
+
+    /// fromの型修飾子(const, volatile)を付け加える
+    pub fn qualified(self, from: &Type) -> Self {
+        Self {
+            is_const: self.is_const || from.is_const,
+            is_volatile: self.is_volatile || from.is_volatile,
+            ..self
+        }
+    }
+
+    /// アラインメントをalign以上に引き上げる。_Alignasで指定されたアラインメントに使う。
+    pub fn aligned(self, align: i64) -> Self {
+        Self {
+            align: self.align.max(align),
+            ..self
+        }
+    }
+
+    /// 型修飾子(const, volatile)を取り除く
+    pub fn unqualified(self) -> Self {
+        Self {
+            is_const: false,
+            is_volatile: false,
+            ..self
+        }
+    }
+
+    /// 配列の要素と構造体・共用体のメンバの型修飾子も、入れ子の中まで含めて取り除く。
+    /// const修飾されたメンバを持つ構造体の変数も、初期化では値をまとめて代入できるようにするため。
+    pub fn deep_unqualified(self) -> Self {
+        let mut ty = self.unqualified();
+        if ty.is_array() {
+            ty.base = ty.base.map(|base| Box::new(base.deep_unqualified()));
+        }
+        ty.members = ty
+            .members
+            .into_iter()
+            .map(|member| Member {
+                ty: member.ty.deep_unqualified(),
+                ..member
+            })
+            .collect();
+        ty
+    }
+
+    pub fn enum_type(tag: Option<String>) -> Self {
+        Self {
+            tag,
+            ..Self::new(TypeKind::Enum, 4, 4)
+        }
+    }
+
+    /// ポインタの比較は符号なしで行うため、is_unsignedを真にしておく。
+    pub fn pointer_to(base: Type) -> Self {
+        Self {
+            base: Some(Box::new(base)),
+            is_unsigned: true,
+            ..Self::new(TypeKind::Ptr, 8, 8)
+        }
+    }
+
+    /// 配列は値として使うとポインタになるため、ポインタと同様にis_unsignedを真にしておく。
+    /// lenが負の場合は要素数が省略された不完全な配列型になる。
+    pub fn array_of(base: Type, len: i64) -> Self {
+        let (size, align) = (base.size * len.max(0), base.align);
+        Self {
+            base: Some(Box::new(base)),
+            array_len: len,
+            is_unsigned: true,
+            ..Self::new(TypeKind::Array, size, align)
+        }
+    }
+
+    /// __builtin_va_list。System V AMD64 ABIのva_listと同じく、要素数1の構造体の配列にする。
+    pub fn va_list() -> Self {
+        let elem = Self::new_struct(
+            None,
+            vec![
+                ("gp_offset".to_string(), Self::int().unsigned()),
+                ("fp_offset".to_string(), Self::int().unsigned()),
+                (
+                    "overflow_arg_area".to_string(),
+                    Self::pointer_to(Self::void()),
+                ),
+                ("reg_save_area".to_string(), Self::pointer_to(Self::void())),
+            ],
+        );
+        Self::array_of(elem, 1)
+    }
+
+    /// 可変長配列型。変数自体は確保した領域へのポインタを持ち、
+    /// 配列全体の大きさは実行時にrbpからvla_sizeの位置の変数に格納される。
+    pub fn vla_of(base: Type, vla_size: i64) -> Self {
+        Self {
+            base: Some(Box::new(base)),
+            vla_size,
+            is_unsigned: true,
+            ..Self::new(TypeKind::Vla, 8, 8)
+        }
+    }
+
+    /// 配列型をその要素へのポインタ型に、関数型をその関数へのポインタ型にする。
+    /// それ以外の型はそのまま返す。
+    pub fn decay(self) -> Self {
+        match self.kind {
+            TypeKind::Array | TypeKind::Vla => Type::pointer_to(*self.base.unwrap()),
+            TypeKind::Func => Type::pointer_to(self),
+            _ => self,
+        }
+    }
+
+    /// メンバを宣言順に並べ、それぞれのアライメントに合わせてオフセットを決める。
+    /// 構造体全体のサイズはメンバの最大のアライメントの倍数に切り上げる。
+    /// メンバが空の場合は不完全型として扱う。
+    ///
+    /// ビットフィールドはgccと同様に、直前のメンバに続くビットに詰めて配置する。ただし、
+    /// 型の大きさの境界をまたぐ場合は次の境界から配置する。名前のないビットフィールドは
+    /// 領域を空けるだけでメンバには含めず、幅が0の場合は次の型の大きさの境界まで空ける。
+    pub fn new_struct(tag: Option<String>, members: Vec<(String, Type)>) -> Self {
+        let mut bits = 0;
+        let mut align = 1;
+        let members = members
+            .into_iter()
+            .filter_map(|(name, mut ty)| {
+                // 名前のないビットフィールドは、gccと同様に構造体のアライメントに影響しない
+                if !name.is_empty() {
+                    align = align.max(ty.align);
+                }
+                let offset = if ty.is_bitfield() || name.is_empty() {
+                    let unit = ty.size * 8;
+                    if ty.bit_width == 0 || bits / unit != (bits + ty.bit_width - 1) / unit {
+                        bits = align_to(bits, unit);
+                    }
+                    let offset = bits / 8 / ty.size * ty.size;
+                    ty.bit_offset = bits - offset * 8;
+                    bits += ty.bit_width;
+                    offset
+                } else {
+                    bits = align_to(bits, ty.align * 8);
+                    let offset = bits / 8;
+                    bits += ty.size * 8;
+                    offset
+                };
+                (!name.is_empty()).then_some(Member { name, offset, ty })
+            })
+            .collect();
+        Self {
+            tag,
+            members,
+            ..Self::new(
+                TypeKind::Struct,
+                align_to(align_to(bits, 8) / 8, align),
+                align,
+            )
+        }
+    }
+
+    /// 共用体のメンバはすべてオフセット0に置く。ビットフィールドも最下位のビットから配置する。
+    /// 共用体全体のサイズは最大のメンバのサイズを、メンバの最大のアライメントの倍数に切り上げたものになる。
+    pub fn new_union(tag: Option<String>, members: Vec<(String, Type)>) -> Self {
+        // 名前のないビットフィールドは、大きさにもアライメントにも影響しない
+        let members: Vec<_> = members
+            .into_iter()
+            .filter(|(name, _)| !name.is_empty())
+            .collect();
+        let size = members.iter().map(|(_, ty)| ty.size).max().unwrap_or(0);
+        let align = members.iter().map(|(_, ty)| ty.align).max().unwrap_or(1);
+        let members = members
+            .into_iter()
+            .map(|(name, ty)| Member {
+                name,
+                ty,
+                offset: 0,
+            })
+            .collect();
+        Self {
+            tag,
+            members,
+            ..Self::new(TypeKind::Union, align_to(size, align), align)
+        }
+    }
+
+    /// ビット幅を指定したビットフィールドの型にする。ビット位置は構造体の配置を決めるときに決める。
+    pub fn bitfield(self, bit_width: i64) -> Self {
+        Self {
+            bit_width,
+            bit_offset: 0,
+            ..self
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self.kind,
+            TypeKind::Bool
+                | TypeKind::Char
+                | TypeKind::Short
+                | TypeKind::Int
+                | TypeKind::Long
+                | TypeKind::Enum
+        )
+    }
+
+    /// 浮動小数点数型かどうか
+    pub fn is_flonum(&self) -> bool {
+        matches!(self.kind, TypeKind::Float | TypeKind::Double)
+    }
+
+    /// 整数型か浮動小数点数型かどうか
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_flonum()
+    }
+
+    /// 構造体か共用体かどうか
+    pub fn is_aggregate(&self) -> bool {
+        matches!(self.kind, TypeKind::Struct | TypeKind::Union)
+    }
+
+    /// ポインタか配列かどうか。どちらも指す先の型をbaseに持つ。
+    pub fn is_pointer(&self) -> bool {
+        matches!(self.kind, TypeKind::Ptr | TypeKind::Array | TypeKind::Vla)
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.kind == TypeKind::Array
+    }
+
+    pub fn is_bitfield(&self) -> bool {
+        self.bit_width > 0
+    }
+
+    pub fn is_vla(&self) -> bool {
+        self.kind == TypeKind::Vla
+    }
+
+    pub fn is_func(&self) -> bool {
+        self.kind == TypeKind::Func
+    }
+
+    pub fn find_member(&self, name: &str) -> Option<&Member> {
+        self.members.iter().find(|m| m.name == name)
+    }
+
+    /// 整数の値をこの型の大きさに切り詰め、符号付きの型は符号拡張、符号なしの型はゼロ拡張した値にする。
+    /// _Bool型は0か1にする。8バイトの型の値はそのまま返す。
+    pub fn truncate(&self, val: i64) -> i64 {
+        match (self.size, self.is_unsigned) {
+            _ if self.kind == TypeKind::Bool => (val != 0) as i64,
+            (1, false) => val as i8 as i64,
+            (1, true) => val as u8 as i64,
+            (2, false) => val as i16 as i64,
+            (2, true) => val as u16 as i64,
+            (4, false) => val as i32 as i64,
+            (4, true) => val as u32 as i64,
+            _ => val,
+        }
+    }
+}
+
+/// nをalignの倍数に切り上げる
+pub fn align_to(n: i64, align: i64) -> i64 {
+    (n + align - 1) / align * align
+}
+
+/// intより小さい整数型とintより狭いビットフィールドをintにする(整数拡張)。
+pub fn promote(ty: &Type) -> Type {
+    if ty.size < 4 || ty.is_bitfield() && ty.bit_width < 32 {
+        Type::int()
+    } else {
+        ty.clone().bitfield(0)
+    }
+}
+
+/// 算術型の二項演算で両辺を揃える型を求める(通常の算術型変換)。
+/// どちらかが浮動小数点数ならdouble、floatの順に優先する。
+/// 整数同士なら整数拡張をした上で大きい方の型に揃え、同じ大きさなら符号なしの方を優先する。
+pub fn common_type(lhs: &Type, rhs: &Type) -> Type {
+    if lhs.kind == TypeKind::Double || rhs.kind == TypeKind::Double {
+        return Type::double();
+    }
+    if lhs.kind == TypeKind::Float || rhs.kind == TypeKind::Float {
+        return Type::float();
+    }
+
+    let (lhs, rhs) = (promote(lhs), promote(rhs));
+    let ty = if lhs.size == 8 || rhs.size == 8 {
+        Type::long()
+    } else {
+        Type::int()
+    };
+    let is_unsigned = match lhs.size.cmp(&rhs.size) {
+        std::cmp::Ordering::Less => rhs.is_unsigned,
+        std::cmp::Ordering::Greater => lhs.is_unsigned,
+        std::cmp::Ordering::Equal => lhs.is_unsigned || rhs.is_unsigned,
+    };
+    if is_unsigned {
+        ty.unsigned()
+    } else {
+        ty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{align_to, common_type, promote, Type};
+
+    #[test]
+    fn align() {
+        assert_eq!(align_to(0, 8), 0);
+        assert_eq!(align_to(1, 8), 8);
+        assert_eq!(align_to(8, 8), 8);
+        assert_eq!(align_to(5, 4), 8);
+        assert_eq!(align_to(5, 1), 5);
+    }
+
+    #[test]
+    fn struct_layout() {
+        let ty = Type::new_struct(
+            Some("s".to_string()),
+            vec![
+                ("a".to_string(), Type::char()),
+                ("b".to_string(), Type::int()),
+                ("c".to_string(), Type::char()),
+                ("d".to_string(), Type::pointer_to(Type::char())),
+                ("e".to_string(), Type::char()),
+            ],
+        );
+        let offsets: Vec<i64> = ty.members.iter().map(|m| m.offset).collect();
+        assert_eq!(offsets, vec![0, 4, 8, 16, 24]);
+        assert_eq!(ty.size, 32);
+        assert_eq!(ty.align, 8);
+    }
+
+    #[test]
+    fn union_layout() {
+        let ty = Type::new_union(
+            None,
+            vec![
+                ("a".to_string(), Type::char()),
+                ("b".to_string(), Type::int()),
+                (
+                    "c".to_string(),
+                    Type::new_struct(
+                        None,
+                        vec![
+                            ("x".to_string(), Type::char()),
+                            ("y".to_string(), Type::char()),
+                            ("z".to_string(), Type::char()),
+                            ("w".to_string(), Type::char()),
+                            ("v".to_string(), Type::char()),
+                        ],
+                    ),
+                ),
+            ],
+        );
+        assert!(ty.members.iter().all(|m| m.offset == 0));
+        assert_eq!(ty.size, 8);
+        assert_eq!(ty.align, 4);
+    }
+
+    #[test]
+    fn struct_layout_with_chars_only() {
+        let ty = Type::new_struct(
+            None,
+            vec![
+                ("a".to_string(), Type::char()),
+                ("b".to_string(), Type::char()),
+                ("c".to_string(), Type::char()),
+            ],
+        );
+        assert_eq!(ty.size, 3);
+        assert_eq!(ty.align, 1);
+    }
+
+    #[test]
+    fn array_layout() {
+        let ty = Type::array_of(Type::array_of(Type::int(), 3), 2);
+        assert_eq!(ty.size, 24);
+        assert_eq!(ty.align, 4);
+        assert_eq!(ty.base.as_ref().unwrap().size, 12);
+        assert_eq!(Type::array_of(Type::char(), -1).size, 0);
+        assert_eq!(
+            Type::array_of(Type::short(), 4).decay(),
+            Type::pointer_to(Type::short())
+        );
+
+        let ty = Type::new_struct(
+            None,
+            vec![
+                ("a".to_string(), Type::char()),
+                ("b".to_string(), Type::array_of(Type::int(), 3)),
+            ],
+        );
+        assert_eq!(ty.members[1].offset, 4);
+        assert_eq!(ty.size, 16);
+    }
+
+    #[test]
+    fn function_decay() {
+        let func = Type::func(Type::int(), vec![Type::int(), Type::long()], false);
+        assert_eq!(func.clone().decay(), Type::pointer_to(func));
+        assert_eq!(Type::pointer_to(Type::void()).size, 8);
+    }
+
+    #[test]
+    fn bitfield_layout() {
+        let ty = Type::new_struct(
+            None,
+            vec![
+                ("a".to_string(), Type::char()),
+                ("b".to_string(), Type::int().bitfield(4)),
+                ("c".to_string(), Type::int().bitfield(30)),
+                (String::new(), Type::int().bitfield(0)),
+                ("d".to_string(), Type::short().unsigned().bitfield(3)),
+            ],
+        );
+        let layout: Vec<_> = ty
+            .members
+            .iter()
+            .map(|m| (m.offset, m.ty.bit_offset, m.ty.bit_width))
+            .collect();
+        assert_eq!(layout, vec![(0, 0, 0), (0, 8, 4), (4, 0, 30), (8, 0, 3)]);
+        assert_eq!((ty.size, ty.align), (12, 4));
+        assert_eq!(promote(&ty.members[3].ty), Type::int());
+    }
+
+    #[test]
+    fn vla_layout() {
+        let vla = Type::vla_of(Type::array_of(Type::int(), 3), 16);
+        assert_eq!((vla.size, vla.align, vla.vla_size), (8, 8, 16));
+        assert!(vla.is_pointer() && !vla.is_array());
+        assert_eq!(
+            vla.decay(),
+            Type::pointer_to(Type::array_of(Type::int(), 3))
+        );
+    }
+
+    #[test]
+    fn common_types() {
+        assert_eq!(common_type(&Type::char(), &Type::char()), Type::int());
+        assert_eq!(common_type(&Type::char(), &Type::int()), Type::int());
+        assert_eq!(common_type(&Type::int(), &Type::long()), Type::long());
+        assert_eq!(common_type(&Type::long(), &Type::char()), Type::long());
+        assert_eq!(
+            common_type(&Type::int(), &Type::int().unsigned()),
+            Type::int().unsigned()
+        );
+        assert_eq!(
+            common_type(&Type::int().unsigned(), &Type::long()),
+            Type::long()
+        );
+        assert_eq!(
+            common_type(&Type::long().unsigned(), &Type::int()),
+            Type::long().unsigned()
+        );
+        assert_eq!(
+            common_type(&Type::char().unsigned(), &Type::short().unsigned()),
+            Type::int()
+        );
+    }
+
+    #[test]
+    fn common_types_with_flonums() {
+        assert_eq!(common_type(&Type::int(), &Type::double()), Type::double());
+        assert_eq!(common_type(&Type::float(), &Type::double()), Type::double());
+        assert_eq!(
+            common_type(&Type::long().unsigned(), &Type::float()),
+            Type::float()
+        );
+        assert_eq!(common_type(&Type::float(), &Type::char()), Type::float());
+    }
+
+    #[test]
+    fn bool_type() {
+        assert!(Type::bool().is_integer());
+        assert_eq!(promote(&Type::bool()), Type::int());
+        assert_eq!(common_type(&Type::bool(), &Type::bool()), Type::int());
+    }
+
+    #[test]
+    fn promotions() {
+        assert_eq!(promote(&Type::char()), Type::int());
+        assert_eq!(promote(&Type::short().unsigned()), Type::int());
+        assert_eq!(promote(&Type::int().unsigned()), Type::int().unsigned());
+        assert_eq!(promote(&Type::long()), Type::long());
+    }
+}
@@ -1,10 +1,11 @@
 #!/bin/bash
+# 3番目以降の引数は、コンパイラにそのまま渡すオプション
 assert() {
   expected="$1"
   input="$2"
 
-  ./target/release/main "$input" > tests/tmp/tmp.s
-  cc -o tests/tmp/tmp.out tests/tmp/tmp.s
+  ./target/release/main "${@:3}" "$input" > tests/tmp/tmp.s
+  cc -o tests/tmp/tmp.out tests/tmp/tmp.s tests/tmp/ext.o
   ./tests/tmp/tmp.out
   actual="$?"
 
@@ -15,8 +16,113 @@ assert() {
   fi
 }
 
+# -Eの出力がexpectedと一致することを確かめる
+assert_pp() {
+  expected="$1"
+  input="$2"
+
+  actual="$(./target/release/main -E "${@:3}" "$input")"
+
+  if [ "$actual" = "$expected" ]; then
+    echo "$input => $actual"
+  else
+    echo "$input => $expected expected, but got $actual"
+  fi
+}
+
+# コンパイルが失敗し、標準エラー出力がexpectedと一致することを確かめる
+assert_error() {
+  expected="$1"
+  input="$2"
+
+  actual="$(./target/release/main "${@:3}" "$input" 2>&1 > /dev/null)"
+
+  if [ "$?" != 0 ] && [ "$actual" = "$expected" ]; then
+    echo "$input => $actual"
+  else
+    echo "$input => $expected expected, but got $actual"
+  fi
+}
+
 cargo build -r
-mkdir ./tests/tmp
+mkdir -p ./tests/tmp
+
+# extern宣言と関数呼び出しのテストで参照する変数と関数
+cat <<EOF | cc -xc -c -o tests/tmp/ext.o -
+int ext1 = 5;
+long ext2[3] = {1, 2, 3};
+int add2(int a, int b) { return a + b; }
+int sub2(int a, int b) { return a - b; }
+long add6(long a, long b, long c, long d, long e, long f) { return a + b + c + d + e + f; }
+double addd(double a, double b) { return a + b; }
+float mulf(float a, float b) { return a * b; }
+int mix(int a, double b, int c, float d) { return a * 1000 + (int)(b * 100) + c * 10 + (int)d; }
+int apply(int (*f)(int, int), int a, int b) { return f(a, b); }
+int (*pick(int i))(int, int) { return i ? sub2 : add2; }
+char retchar(void) { return -3; }
+long add8(long a, long b, long c, long d, long e, long f, long g, long h) { return a - b + c - d + e - f + g - h; }
+double addd10(double a, double b, double c, double d, double e, double f, double g, double h, double i, double j) { return a + b + c + d + e + f + g + h + i * j; }
+struct Small { char c; int i; };
+struct Pair { long a, b; };
+struct Mixed { double d; int i; };
+struct Flt { float x, y, z; };
+struct Big { long a, b, c; };
+struct Odd { char c[3]; };
+int sum_small(struct Small s) { return s.c + s.i; }
+long sum_pair(int x, struct Pair p) { return x + p.a - p.b; }
+int sum_mixed(struct Mixed m, double d) { return (int)(m.d * d) + m.i; }
+int sum_flt(struct Flt f) { return (int)(f.x + f.y * f.z); }
+long sum_big(struct Big b) { return b.a + b.b * b.c; }
+int sum_odd(struct Odd o) { return o.c[0] + o.c[1] + o.c[2]; }
+long sum_many(long a, long b, long c, long d, long e, struct Pair p, struct Big q, int g) { return a + b + c + d + e + p.a - p.b + q.a + q.b + q.c + g; }
+struct Small make_small(int i) { struct Small s = {'a', i}; return s; }
+struct Pair make_pair(long a, long b) { struct Pair p = {a, b}; return p; }
+struct Mixed make_mixed(double d, int i) { struct Mixed m = {d, i}; return m; }
+struct Flt make_flt(float x) { struct Flt f = {x, x * 2, x * 3}; return f; }
+struct Big make_big(long a) { struct Big b = {a, a + 1, a + 2}; return b; }
+struct Odd make_odd(char c) { struct Odd o = {{c, c + 1, c + 2}}; return o; }
+long call8(long (*f)(long, long, long, long, long, long, long, long)) { return f(1, 2, 3, 4, 5, 6, 7, 8); }
+long call_vsum(long (*f)(int, ...)) { return f(7, 1L, 2L, 3L, 4L, 5L, 6L, 7L); }
+double call_vsumd(double (*f)(int, ...)) { return f(10, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.5); }
+long call_structs(long (*f)(struct Small, struct Pair, struct Mixed, struct Flt, struct Odd)) { return f((struct Small){'a', 1}, (struct Pair){9, 4}, (struct Mixed){2.5, 2}, (struct Flt){1, 2, 3}, (struct Odd){{1, 2, 3}}); }
+long call_many(long (*f)(long, long, long, long, long, struct Pair, struct Big, int)) { return f(1, 2, 3, 4, 5, (struct Pair){10, 2}, (struct Big){3, 4, 5}, 1); }
+long call_make_pair(struct Pair (*f)(long, long)) { struct Pair p = f(7, 4); return p.a * 10 + p.b; }
+int call_make_mixed(struct Mixed (*f)(double, int)) { struct Mixed m = f(1.5, 6); return (int)(m.d * 2) + m.i; }
+int call_make_flt(struct Flt (*f)(float)) { struct Flt v = f(1.5); return (int)(v.x + v.y + v.z); }
+long call_make_big(struct Big (*f)(long)) { struct Big b = f(3); return b.a * 100 + b.b * 10 + b.c; }
+int call_make_odd(struct Odd (*f)(char)) { struct Odd o = f(1); return o.c[0] * 100 + o.c[1] * 10 + o.c[2]; }
+EOF
+
+# #includeのテストで使うヘッダファイル
+mkdir -p tests/tmp/include/sub
+cat <<EOF > tests/tmp/include/three.h
+#ifndef THREE_H
+#define THREE_H
+#define THREE 3
+int three = THREE;
+#endif
+EOF
+cat <<EOF > tests/tmp/include/sub/outer.h
+/* 同じディレクトリのinner.hを探す */
+#include "inner.h"
+#define OUTER (INNER + 1)
+EOF
+cat <<EOF > tests/tmp/include/sub/inner.h
+#define INNER 10
+EOF
+cat <<EOF > tests/tmp/include/bad.h
+int a[2];
+a = 0;
+EOF
+cat <<EOF > tests/tmp/include/once.h
+#pragma once
+int once = 2;
+EOF
+# 組み込みのヘッダファイルより-Iで指定したディレクトリが優先される
+mkdir -p tests/tmp/include/override
+cat <<EOF > tests/tmp/include/override/stddef.h
+#define NULL 5
+EOF
 
 echo "===test start==="
 
@@ -69,4 +175,638 @@ assert 21 "return 1+2+3+4+5+6;"
 assert 3 "return 3; return 5;"
 assert 15 "foo=3; bar=5; return foo*bar; return 8;"
 
+# DO-WHILE STATEMENT
+assert 10 "a=0; do a=a+1; while (a<10); a;"
+assert 1 "a=0; do a=a+1; while (0); a;"
+assert 12 "i=0; n=0; do do n=n+1; while (n<3*(i+1)); while ((i=i+1)<4); n;"
+assert 10 "a=0; do continue; while ((a=a+1)<10); a;"
+assert 3 "a=0; do return 3; while (1); a;"
+
+# POINTERS
+assert 3 "int x; int *y; x=3; y=&x; *y;"
+assert 5 "int x; int *y; x=3; y=&x; *y=5; x;"
+assert 7 "int x; int *y; int **z; y=&x; z=&y; **z=7; x;"
+
+# STRUCTS
+assert 3 "struct {int a; int b;} x; x.a=1; x.b=2; x.a+x.b;"
+assert 8 "struct {char a; int b;} x; sizeof(x);"
+assert 8 "struct {char a; int b; char c;} x; &x.c - &x.a;"
+assert 24 "struct {char a; int *b; char c;} x; sizeof x;"
+assert 2 "struct {char a; char b;} x; sizeof(x);"
+assert 24 "struct {char a; struct {char b; int *c;} d;} x; sizeof(x);"
+assert 16 "struct {char a; struct {int *b; char c;} d;} x; &x.d.c - &x.a;"
+assert 7 "struct {char a; struct {char b; int c;} d;} x; x.d.c=7; x.d.c;"
+assert 6 "struct {int a; int b;} x; struct {int a; int b;} *p; p=&x; p->a=2; p->b=4; x.a+x.b;"
+assert 5 "struct P {int x; int y;}; struct P a; struct P b; a.x=2; a.y=3; b=a; b.x+b.y;"
+assert 3 "struct P {int x; int y;}; struct P a, *p; p=&a; p->y=3; a.y;"
+assert 6 "struct N {int v; struct N *next;}; struct N a, b, c; a.v=1; b.v=2; c.v=3; a.next=&b; b.next=&c; a.v+a.next->v+a.next->next->v;"
+assert 3 "struct N {int v; struct N *next;}; struct N a, b, c; a.next=&b; b.v=3; c=*a.next; c.v;"
+assert 12 "struct T {struct T *l; struct T *r; int v;}; struct T n, m; n.l=&m; m.v=12; n.l->v;"
+assert 1 "struct {char a; int b;} x; x.a=1; x.b=2; x.a;"
+
+# UNIONS
+assert 16 "union {int a; struct {char x; int *y;} c;} x; sizeof(x);"
+assert 4 "union {int a; char b;} x; sizeof(x);"
+assert 8 "union {char a; struct {char x; char y; char z; char w; char v;} b; int c;} x; sizeof(x);"
+assert 0 "union {int a; char b;} x; &x.b - &x.b;"
+assert 3 "union {int a; char b;} x; x.a=515; x.b;"
+assert 2 "union {int a; char b;} x; x.a=515; *(&x.b+1);"
+assert 7 "union U {int a; struct {char lo; char hi;} s;}; union U x; x.s.lo=7; x.s.hi=0; x.a=x.a; x.s.lo;"
+assert 5 "union U {int a; char b;}; union U x, y, *p; x.a=5; y=x; p=&y; p->a;"
+assert 1 "struct S {union {int i; char c;} u; int tag;}; struct S s; s.tag=1; s.u.i=9; s.tag;"
+
+# ENUMS
+assert 0 "enum {A, B, C}; A;"
+assert 1 "enum {A, B, C}; B;"
+assert 2 "enum {A, B, C}; C;"
+assert 5 "enum {A, B=5, C}; B;"
+assert 6 "enum {A, B=5, C}; C;"
+assert 7 "enum {A=3, B=A+4, C,}; B;"
+assert 12 "enum {A=2*3, B=(A-1)/2+6, C=B<A}; A+B-C-2;"
+assert 4 "enum E {X, Y}; enum E e; sizeof(e);"
+assert 1 "enum E {X, Y}; enum E e; e=Y; e;"
+assert 12 "enum E {X=10, Y}; struct {enum E e; char c;} s; s.e=Y; s.e+sizeof(X)-3;"
+assert 3 "enum {A=3}; enum {B=A}; B;"
+
+# TYPEDEF
+assert 1 "typedef char T; T x; sizeof(x);"
+assert 3 "typedef int T; T *x; T y; x=&y; *x=3; y;"
+assert 8 "typedef int T; T * x; sizeof(x);"
+assert 6 "typedef struct {int a; int b;} P, *PP; P p; PP q; q=&p; q->a=2; q->b=4; p.a+p.b;"
+assert 5 "typedef struct N N; struct N {int v; N *next;}; N a, b; a.next=&b; b.v=5; a.next->v;"
+assert 4 "typedef int T; typedef T U; U x; sizeof(x);"
+assert 7 "typedef enum {R, G=7} Color; Color c; c=G; c;"
+assert 12 "typedef int T; struct {T a; T *b;} s; sizeof(s)-4;"
+assert 6 "a=2; b=3; a * b;"
+
+# CASTS AND ARITHMETIC CONVERSIONS
+assert 44 "(char)300;"
+assert 1 "(char)255 == -1;"
+assert 1 "(char)-1 < 0;"
+assert 1 "(int)4294967297;"
+assert 1 "char c; c=200; c<0;"
+assert 1 "int x; x=2147483647; x+1<0;"
+assert 0 "x=2147483647; x+1<0;"
+assert 1 "int x; x=2147483647; (y=x) == 2147483647;"
+assert 4 "sizeof(1);"
+assert 8 "sizeof(4294967297);"
+assert 1 "sizeof((char)1);"
+assert 4 "char c; sizeof(c+c);"
+assert 1 "char c; sizeof(c);"
+assert 4 "sizeof(int);"
+assert 1 "sizeof(char);"
+assert 8 "sizeof(char *);"
+assert 8 "sizeof(struct {char a; int b;});"
+assert 8 "int x; sizeof(x + y);"
+assert 7 "int x; int *p; p=&x; x=7; *(char *)p;"
+assert 3 "int x; int *p; p=&x; x=770; *((char *)p+1);"
+assert 5 "x=5; -(int)x+10;"
+assert 3 "typedef int T; (T)3;"
+assert 2 "char c; c=258; c;"
+assert 1 "int x; x=4294967297; x;"
+
+# INTEGER TYPES
+assert 2 "short x; sizeof(x);"
+assert 2 "short int x; sizeof(x);"
+assert 2 "int short x; sizeof(x);"
+assert 8 "long x; sizeof(x);"
+assert 8 "long int x; sizeof(x);"
+assert 8 "long long x; sizeof(x);"
+assert 8 "unsigned long long int x; sizeof(x);"
+assert 4 "unsigned x; sizeof(x);"
+assert 4 "signed x; sizeof(x);"
+assert 1 "unsigned char x; sizeof(x);"
+assert 1 "signed char x; sizeof(x);"
+assert 2 "unsigned short x; sizeof(x);"
+assert 4 "sizeof(unsigned int);"
+assert 24 "struct {char a; short b; long c; int d;} x; sizeof(x);"
+assert 1 "struct {char a; short b;} x; &x.b - (short *)&x.a;"
+assert 1 "short x; x=65535; x<0;"
+assert 0 "unsigned short x; x=65535; x<0;"
+assert 255 "unsigned char x; x=255; x;"
+assert 1 "unsigned char x; x=255; x==255;"
+assert 1 "char x; x=255; x==-1;"
+assert 1 "long x; x=2147483647; x=x+1; x==2147483648;"
+assert 0 "unsigned x; x=0; x-1<0;"
+assert 1 "int x; x=0; x-1<0;"
+assert 1 "unsigned x; x=0; x-1==4294967295;"
+assert 0 "-1 < (unsigned)1;"
+assert 1 "-1 < (long)(unsigned)1;"
+assert 1 "-1 < (long)1;"
+assert 1 "unsigned x; x=1; (x>=0)==1;"
+assert 0 "(unsigned long)-1 <= 1;"
+assert 1 "int x; int *p; int *q; p=&x; q=p+1; p<q;"
+
+# DIVISION AND SHIFTS
+assert 3 "(-7)/(-2);"
+assert 1 "-7/2 == -3;"
+assert 1 "(unsigned)-7/2 == 2147483644;"
+assert 1 "(unsigned long)-8/2 == 9223372036854775804;"
+assert 16 "1<<4;"
+assert 5 "20>>2;"
+assert 1 "-16>>2 == -4;"
+assert 1 "(unsigned)-16>>28 == 15;"
+assert 1 "(unsigned long)-1>>63;"
+assert 1 "(long)-1>>63 == -1;"
+assert 1 "1<<31 < 0;"
+assert 1 "(long)1<<32 == 4294967296;"
+assert 1 "char c; c=64; c<<2 == 256;"
+assert 1 "unsigned char c; c=255; c>>4 == 15;"
+
+# FLOATING POINT
+assert 3 "(int)3.7;"
+assert 1 "(int)-3.7 == -3;"
+assert 5 "(int)(2.5 + 2.5);"
+assert 6 "(int)(1.5 * 4);"
+assert 2 "(int)(5.0 / 2);"
+assert 1 "1.5 - 0.5 == 1;"
+assert 1 "0.1 + 0.2 != 0.3;"
+assert 1 "0.1f + 0.2f == 0.3f;"
+assert 1 "1.0 < 2.0;"
+assert 0 "2.0 < 2.0;"
+assert 1 "2.0 <= 2.0;"
+assert 1 "-1.0 < 0;"
+assert 1 "1e3 == 1000;"
+assert 1 "2.5e-1 == .25;"
+assert 4 "sizeof(float);"
+assert 8 "sizeof(double);"
+assert 16 "sizeof(long double);"
+assert 32 "struct S { char c; long double x; }; sizeof(struct S) + _Alignof(long double) - 16;"
+assert 48 $'#include <stddef.h>\n_Alignof(max_align_t) + sizeof(max_align_t);'
+assert_error "<input>:1: long double型の値はサポートしていません" "long double x; double d = x;"
+assert_error "<input>:1: long double型の値はサポートしていません" "long double strtold(const char *, char **); strtold(\"1\", 0);"
+assert 8 "sizeof(1.0);"
+assert 4 "sizeof(1.0f);"
+assert 8 "sizeof(1.0f + 1.0);"
+assert 4 "sizeof(1.0f + 1);"
+assert 7 "double d; d = 7; (int)d;"
+assert 3 "float f; f = 3.9; (int)f;"
+assert 1 "double d; float f; f = 0.5; d = f; d == 0.5;"
+assert 1 "float f; f = 1.0 / 3; f != 1.0 / 3;"
+assert 1 "int i; i = 2.9; i == 2;"
+assert 1 "char c; c = -1.5; c == -1;"
+assert 1 "unsigned long u; u = 1e19; u == 10000000000000000000;"
+assert 1 "unsigned long u; u = 18446744073709551615; (double)u == 18446744073709551615.0;"
+assert_error "invalid number: <input>:1: 18446744073709551616" "unsigned long u; u = 18446744073709551616;"
+assert 1 "unsigned u; u = -1; (double)u == 4294967295.0;"
+assert 1 "double d; d = -3; (long)d == -3;"
+assert 4 "struct P { char c; float f; } p; p.f = 4.5; (int)p.f;"
+assert 9 "double d; double *p; p = &d; *p = 9.25; (int)d;"
+assert 3 "double d; d = 0; do d = d + 0.5; while (d < 1.5); (int)(d * 2);"
+assert 5 "return 5.9;"
+
+# ARRAYS AND INITIALIZERS
+assert 3 "int a[2]; a[0] = 1; a[1] = 2; a[0] + a[1];"
+assert 12 "int a[3]; sizeof(a);"
+assert 24 "int a[2][3]; sizeof(a);"
+assert 12 "int a[2][3]; sizeof(a[1]);"
+assert 12 "sizeof(int[3]);"
+assert 5 "int a[2][3]; a[1][2] = 5; *(*(a + 1) + 2);"
+assert 2 "int a[3]; int *p; p = a + 2; p - a;"
+assert 7 "int a[3]; *(a + 1) = 7; 1[a];"
+assert 3 "int x = 3; x;"
+assert 6 "int a[3] = {1, 2, 3}; a[0] + a[1] + a[2];"
+assert 12 "int a[] = {1, 2, 3}; sizeof(a);"
+assert 0 "int a[4] = {1, 2}; a[2] + a[3];"
+assert 7 "int a[5] = {[3] = 7, 1}; a[3] + a[0] + a[1] + a[2];"
+assert 1 "int a[5] = {[3] = 7, 1}; a[4];"
+assert 24 "int a[] = {[5] = 1}; sizeof(a);"
+assert 4 "char s[] = \"abc\"; sizeof(s);"
+assert 98 "char s[] = \"abc\"; s[1];"
+assert 0 "char s[] = \"abc\"; s[3];"
+assert 3 "char s[3] = \"abc\"; sizeof(s);"
+assert 10 "char s[] = \"a\\n\"; s[1];"
+assert 4 "char s[] = {\"abc\"}; sizeof(s);"
+assert 0 "char s[8] = \"ab\"; s[7];"
+assert 6 "int a[2][3] = {{1, 2, 3}, {4, 5, 6}}; a[1][2];"
+assert 5 "int a[2][3] = {1, 2, 3, 4, 5}; a[1][1];"
+assert 0 "int a[2][3] = {{1}, {4}}; a[0][1] + a[1][2];"
+assert 24 "int a[][3] = {1, 2, 3, 4}; sizeof(a);"
+assert 3 "struct P { int x; int y; } p = {1, 2}; p.x + p.y;"
+assert 2 "struct P { int x; int y; } p = {.y = 2}; p.x + p.y;"
+assert 5 "struct P { int x; int y; } p = {.y = 2, .x = 5}; p.x;"
+assert 9 "struct P { int x; int a[2]; } p = {1, {3, 5}}; p.x + p.a[0] + p.a[1];"
+assert 9 "struct P { int x; int a[2]; } p = {1, 3, 5}; p.x + p.a[0] + p.a[1];"
+assert 7 "struct P { int x; int y; } ps[2] = {{1, 2}, [1].y = 4}; ps[0].x + ps[0].y + ps[1].y + ps[1].x;"
+assert 5 "struct P { int x; int y; } ps[] = {1, 2, 3}; ps[1].x + ps[1].y + sizeof(ps) / 8;"
+assert 7 "struct P { int x; int y; } p = {3, 4}; struct P q = p; q.x + q.y;"
+assert 4 "union U { int a; char b; } u = {.b = 4}; u.a;"
+assert 3 "union U { char b; int a; } u = {259}; u.a;"
+assert 3 "struct S { char s[4]; int n; } s = {\"ab\", 1}; s.s[0] - 96 + s.s[2] + s.n + 1;"
+assert 5 "int x = {5}; x;"
+assert 3 "double a[] = {1, 2.5}; (int)(a[0] + a[1] - 0.5);"
+assert 7 "int a = 3, b = a + 4; b;"
+assert 1 "char c = 257; c;"
+assert 2 "int a[4] = {1, 2, 3, 4}; int *p = &a[1]; *p;"
+
+# STORAGE CLASSES
+assert 3 "static int x = 3; x;"
+assert 0 "static int x; x;"
+assert 7 "static int x; x = 7; x;"
+assert 1 "static int x; static int y; &x != &y;"
+assert 1 "static int x; int y; &x != &y;"
+assert 6 "static int a[] = {1, 2, 3}; a[0] + a[1] + a[2];"
+assert 12 "static int a[] = {1, 2, 3}; sizeof(a);"
+assert 98 "static char s[] = \"abc\"; s[1];"
+assert 4 "static struct P { char c; int x; } p = {1, 3}; p.c + p.x;"
+assert 1 "static long l = 4294967297; l == 4294967297;"
+assert 255 "static char c = -1; (unsigned char)c;"
+assert 3 "static double d = 1.5 * 2; (int)d;"
+assert 2 "static float f = 2.75f; (int)f;"
+assert 5 "static int x = (char)261; x;"
+assert 105 "static char s[] = \"hi\"; static char *p = s + 1; *p;"
+assert 3 "static int a[] = {1, 2, 3, 4}; static int *p[] = {a, &a[2], a + 3 - 1}; (p[1] - p[0]) + (p[2] == p[1]);"
+assert 7 "static struct { int x; int y; } s = {3, 4}; static int *p = &s.y; static long q = (long)&s.x; *p + *(int *)q;"
+assert 5 "extern int ext1; static int *p = &ext1; *p;"
+assert 5 "extern int ext1; ext1;"
+assert 9 "extern int ext1; ext1 = 9; ext1;"
+assert 6 "extern long ext2[3]; ext2[0] + ext2[1] + ext2[2];"
+assert 1 "extern long ext2[]; ext2[0];"
+
+# QUALIFIERS
+assert 3 "const int x = 3; x;"
+assert 5 "int const x = 5; x;"
+assert 4 "unsigned const int x = 4; x;"
+assert 7 "volatile int x; x = 7; x;"
+assert 2 "const volatile long x = 2; x;"
+assert 8 "int x; int * const p = &x; *p = 8; x;"
+assert 6 "int x; const int *p; p = &x; x = 6; *p;"
+assert 9 "const int a[] = {4, 5}; a[0] + a[1];"
+assert 3 "const struct P { int x; int y; } p = {1, 2}; p.x + p.y;"
+assert 4 "sizeof(const int);"
+assert 8 "sizeof(const char * volatile);"
+assert 1 "typedef const int T; T x = 1; x;"
+assert 5 "static const int x = 5; x;"
+assert 2 "const double d = 2.5; (int)d;"
+
+# FUNCTION CALLS AND FUNCTION POINTERS
+assert 5 "int add2(int a, int b); add2(2, 3);"
+assert 21 "long add6(long, long, long, long, long, long); add6(1, 2, 3, 4, 5, 6);"
+assert 3 "double addd(double, double); (int)addd(1.25, 1.75);"
+assert 6 "float mulf(float, float); (int)mulf(1.5f, 4.0f);"
+assert 1 "int mix(int, double, int, float); mix(1, 2.5, 3, 4.0f) == 1284;"
+assert 1 "char retchar(void); retchar() == -3;"
+assert 7 "int add2(int, int); int x = add2(add2(1, 2), 4); x;"
+assert 8 "int add2(int, int); int (*fp)(int, int) = add2; fp(3, 5);"
+assert 8 "int add2(int, int); int (*fp)(int, int) = &add2; (*fp)(3, 5);"
+assert 2 "int sub2(int, int); int (*fp)(int, int); fp = sub2; fp(5, 3);"
+assert 1 "int add2(int, int); int (*fp)(int, int) = add2; fp == add2;"
+assert 9 "int add2(int, int); int apply(int (*f)(int, int), int, int); apply(add2, 4, 5);"
+assert 1 "int sub2(int, int); int apply(int (*)(int, int), int, int); apply(sub2, 4, 3);"
+assert 4 "int (*pick(int))(int, int); pick(1)(7, 3);"
+assert 10 "int (*pick(int))(int, int); pick(0)(7, 3);"
+assert 6 "int add2(int, int); int sub2(int, int); int (*fs[2])(int, int) = {add2, sub2}; fs[0](1, 2) + fs[1](5, 2);"
+assert 3 "typedef int F(int, int); F add2; F *p = add2; p(1, 2);"
+assert 8 "sizeof(int (*)(int));"
+assert 16 "int (*fs[2])(void); sizeof(fs);"
+assert 1 "struct S { int (*f)(int, int); } s; int add2(int, int); s.f = add2; s.f(3, -2);"
+assert 5 "add2(1, 4);"
+
+# PROTOTYPES AND ARGUMENT CONVERSIONS
+assert 3 "double addd(double, double); (int)addd(1, 2);"
+assert 6 "float mulf(float, float); (int)mulf(3, 2.0);"
+assert 2 "int add2(int, int); add2(1.9, 1.9);"
+assert 1 "int add2(int, int); char c = 1; add2(c, 256) == 257;"
+assert 3 "long add6(long, long, long, long, long, long); add6(1, 1, 1, 1, 1, -2);"
+assert 3 "int sprintf(char *, char *, ...); char buf[8]; char fmt[] = \"%.1f\"; sprintf(buf, fmt, 2.5f);"
+assert 53 "int sprintf(char *, char *, ...); char buf[8]; char fmt[] = \"%.1f\"; sprintf(buf, fmt, 2.5f); buf[2];"
+assert 4 "int sprintf(); char buf[8]; char fmt[] = \"%d\"; sprintf(buf, fmt, (char)-100);"
+assert 4 "long add8(long, long, long, long, long, long, long, long); add8(9, 1, 2, 3, 4, 5, 6, 8);"
+assert 20 "double addd10(double, double, double, double, double, double, double, double, double, double); (int)addd10(1, 1, 1, 1, 1, 1, 1, 1, 3, 4);"
+
+# FUNCTION DEFINITIONS AND VARIADIC FUNCTIONS
+assert 7 "int add(int a, int b) { return a + b; } add(3, 4);"
+assert 55 "int fib(int n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); } fib(10);"
+assert 3 "int f(void) { int x = 1; int y = 2; return x + y; } int x = 10; f() + x - 10;"
+assert 4 "double half(double x) { return x / 2; } (int)half(9.0);"
+assert 9 "float sq(float x) { return x * x; } (int)sq(3.0f);"
+assert 44 "char c(void) { return 300; } c();"
+assert 3 "void f(void) { return; } f(); 3;"
+assert 53 "long many(long a, long b, long c, long d, long e, long f, long g, long h) { return a - b + c - d + e - f + g * h; } many(1, 2, 3, 4, 5, 6, 7, 8);"
+assert 126 "double manyd(double a, double b, double c, double d, double e, double f, double g, double h, double i, double j) { return a + b + c + d + e + f + g + h + i * j; } (int)manyd(1, 2, 3, 4, 5, 6, 7, 8, 9, 10);"
+assert 42 "int mul(int a, int b) { return a * b; } int apply(int (*f)(int, int), int, int); apply(mul, 6, 7);"
+assert 53 "long sub8(long a, long b, long c, long d, long e, long f, long g, long h) { return a - b + c - d + e - f + g * h; } long call8(long (*)(long, long, long, long, long, long, long, long)); call8(sub8);"
+assert 103 "int g(void) { return __func__[0]; } g();"
+assert 10 "static inline int twice(int x) { return x * 2; } twice(5);"
+assert 12 "static int add2(int a, int b) { return a * b; } add2(3, 4);"
+assert 45 "int sum(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); int s = 0; int i = 0; do s = s + __builtin_va_arg(ap, int); while ((i = i + 1) < n); return s; } sum(9, 1, 2, 3, 4, 5, 6, 7, 8, 9);"
+assert 55 "double sumd(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); double s = 0; int i = 0; do s = s + __builtin_va_arg(ap, double); while ((i = i + 1) < n); return s; } (int)sumd(10, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0);"
+assert 28 "long vsum(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); long s = 0; do s = s + __builtin_va_arg(ap, long); while ((n = n - 1) > 0); return s; } long call_vsum(long (*)(int, ...)); call_vsum(vsum);"
+assert 55 "double vsumd(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); double s = 0; do s = s + __builtin_va_arg(ap, double); while ((n = n - 1) > 0); return s; } double call_vsumd(double (*)(int, ...)); (int)call_vsumd(vsumd);"
+assert 1 "int vmix(int n, ...) { __builtin_va_list ap; __builtin_va_start(ap, n); int a = __builtin_va_arg(ap, int); double b = __builtin_va_arg(ap, double); char *c = __builtin_va_arg(ap, char *); return a == 1 && b == 2.5 && c[0] == 'x'; } vmix(3, 1, 2.5, \"x\");"
+assert 49 $'int vsprintf(char *, char *, __builtin_va_list);\nint fmt(char *buf, char *f, ...) { __builtin_va_list ap; __builtin_va_start(ap, f); return vsprintf(buf, f, ap); }\nchar b[32]; fmt(b, "%d-%s-%.1f", 12, "ab", 2.5); b[0] + (b[6] == \'2\') - 1;'
+
+# STRUCT ARGUMENTS AND RETURN VALUES
+assert 100 "struct Small { char c; int i; }; int sum_small(struct Small); struct Small s = {'a', 3}; sum_small(s);"
+assert 7 "struct Pair { long a, b; }; long sum_pair(int, struct Pair); struct Pair p = {9, 4}; sum_pair(2, p);"
+assert 12 "struct Mixed { double d; int i; }; int sum_mixed(struct Mixed, double); struct Mixed m = {2.5, 2}; sum_mixed(m, 4);"
+assert 7 "struct Flt { float x, y, z; }; int sum_flt(struct Flt); struct Flt f = {1, 2, 3}; sum_flt(f);"
+assert 11 "struct Big { long a, b, c; }; long sum_big(struct Big); struct Big b = {1, 2, 5}; sum_big(b);"
+assert 6 "struct Odd { char c[3]; }; int sum_odd(struct Odd); struct Odd o = {{1, 2, 3}}; sum_odd(o);"
+assert 36 "struct Pair { long a, b; }; struct Big { long a, b, c; }; long sum_many(long, long, long, long, long, struct Pair, struct Big, int); struct Pair p = {10, 2}; struct Big q = {3, 4, 5}; sum_many(1, 2, 3, 4, 5, p, q, 1);"
+assert 5 "struct Small { char c; int i; }; struct Small make_small(int); struct Small s = make_small(5); s.i;"
+assert 97 "struct Small { char c; int i; }; struct Small make_small(int); make_small(5).c;"
+assert 3 "struct Pair { long a, b; }; struct Pair make_pair(long, long); make_pair(7, 4).a - make_pair(7, 4).b;"
+assert 9 "struct Mixed { double d; int i; }; struct Mixed make_mixed(double, int); struct Mixed m = make_mixed(1.5, 6); (int)(m.d * 2 + m.i);"
+assert 9 "struct Flt { float x, y, z; }; struct Flt make_flt(float); (int)(make_flt(1.5).z * 2);"
+assert 12 "struct Big { long a, b, c; }; struct Big make_big(long); make_big(3).a + make_big(3).b + make_big(3).c;"
+assert 6 "struct Odd { char c[3]; }; struct Odd make_odd(char); struct Odd o = make_odd(1); o.c[0] + o.c[1] + o.c[2];"
+assert 14 "struct Big { long a, b, c; }; struct Big make_big(long); long sum_big(struct Big); sum_big(make_big(2));"
+assert 8 "int add2(); add2(1, 2, 3, 4, 5, 6, 7) + 5;"
+assert 7 "struct Pair { long a, b; }; long diff(struct Pair p) { return p.a - p.b; } struct Pair p = {9, 2}; diff(p);"
+assert 12 "struct Big { long a, b, c; }; struct Big big(long a) { struct Big b = {a, a * 2, a * 3}; return b; } long sum(struct Big b) { return b.a + b.b + b.c; } sum(big(2));"
+assert 31 "struct Small { char c; int i; }; struct Pair { long a, b; }; struct Mixed { double d; int i; }; struct Flt { float x, y, z; }; struct Odd { char c[3]; }; long take(struct Small s, struct Pair p, struct Mixed m, struct Flt f, struct Odd o) { return s.c - 96 + p.a - p.b + (int)(m.d * 4) + m.i + (int)(f.x + f.y * f.z) + o.c[0] + o.c[1] + o.c[2]; } long call_structs(long (*)(struct Small, struct Pair, struct Mixed, struct Flt, struct Odd)); call_structs(take);"
+assert 36 "struct Pair { long a, b; }; struct Big { long a, b, c; }; long many(long a, long b, long c, long d, long e, struct Pair p, struct Big q, int g) { return a + b + c + d + e + p.a - p.b + q.a + q.b + q.c + g; } long call_many(long (*)(long, long, long, long, long, struct Pair, struct Big, int)); call_many(many);"
+assert 74 "struct Pair { long a, b; }; struct Pair pair(long a, long b) { struct Pair p = {a, b}; return p; } long call_make_pair(struct Pair (*)(long, long)); call_make_pair(pair);"
+assert 9 "struct Mixed { double d; int i; }; struct Mixed mixed(double d, int i) { struct Mixed m = {d, i}; return m; } int call_make_mixed(struct Mixed (*)(double, int)); call_make_mixed(mixed);"
+assert 9 "struct Flt { float x, y, z; }; struct Flt flt(float x) { struct Flt f = {x, x * 2, x * 3}; return f; } int call_make_flt(struct Flt (*)(float)); call_make_flt(flt);"
+assert 89 "struct Big { long a, b, c; }; struct Big big(long a) { struct Big b = {a, a + 1, a + 2}; return b; } long call_make_big(struct Big (*)(long)); call_make_big(big) - 256;"
+assert 123 "struct Odd { char c[3]; }; struct Odd odd(char c) { struct Odd o = {{c, c + 1, c + 2}}; return o; } int call_make_odd(struct Odd (*)(char)); call_make_odd(odd);"
+
+# _BOOL AND LOGICAL OPERATORS
+assert 1 "_Bool b = 2; b;"
+assert 0 "_Bool b = 0; b;"
+assert 1 "_Bool b = 256; b;"
+assert 1 "_Bool b = 0.5; b;"
+assert 0 "_Bool b = 0.0; b;"
+assert 1 "_Bool b = -1; b;"
+assert 1 "_Bool b; b = 4294967296; b;"
+assert 1 "int x; _Bool b = &x; b;"
+assert 1 "sizeof(_Bool);"
+assert 1 "(_Bool)3;"
+assert 2 "(_Bool)3 + (_Bool)-7;"
+assert 1 "_Bool b = 1; b + b == 2;"
+assert 1 "static _Bool b = 3; b;"
+assert 1 "static _Bool b = 0.25; b;"
+assert 1 "struct S { _Bool a; _Bool b; } s = {5, 0}; s.a + s.b;"
+assert 0 "!1;"
+assert 1 "!0;"
+assert 0 "!3.5;"
+assert 1 "!0.0;"
+assert 1 "int *p = 0; !p;"
+assert 1 "1 && 2;"
+assert 0 "1 && 0;"
+assert 0 "0 && 1;"
+assert 1 "0 || 3;"
+assert 0 "0 || 0;"
+assert 1 "0.5 && 2;"
+assert 3 "int x = 3; 0 && (x = 5); x;"
+assert 3 "int x = 3; 1 || (x = 5); x;"
+assert 5 "int x = 3; 1 && (x = 5); x;"
+assert 1 "0 || 0 || 1 && 2;"
+assert 1 "1 || 0 && 0;"
+assert 4 "sizeof(1 && 1);"
+
+# COMPOUND LITERALS AND ALIGNMENT
+assert 3 "struct P { int x; int y; }; (struct P){1, 2}.x + (struct P){1, 2}.y;"
+assert 2 "struct P { int x; int y; }; struct P *p = &(struct P){.y = 2}; p->y + p->x;"
+assert 7 "struct P { int x; int y; }; struct P p = (struct P){3, 4}; p.x + p.y;"
+assert 5 "struct P { int x; int y; }; (struct P){1, 2}.x = 5;"
+assert 2 "(int[]){1, 2, 3}[1];"
+assert 12 "sizeof((int[]){1, 2, 3});"
+assert 12 "sizeof (int[]){1, 2, 3};"
+assert 6 "int *p = (int[]){1, 2, 3}; p[0] + p[1] + p[2];"
+assert 4 "int x = (int){4}; x;"
+assert 9 "int *p = &(int){8}; *p = *p + 1; *p;"
+assert 3 "int add2(int, int); add2((int){1}, (int[2]){0, 2}[1]);"
+assert 1 "_Alignof(char);"
+assert 4 "_Alignof(int);"
+assert 8 "_Alignof(double);"
+assert 8 "_Alignof(int *);"
+assert 4 "_Alignof(int[3]);"
+assert 8 "struct S { char c; long l; }; _Alignof(struct S);"
+assert 16 "_Alignof(_Alignas(16) int);"
+assert 0 "_Alignas(16) char c; (long)&c - (long)&c / 16 * 16;"
+assert 0 "char a; _Alignas(8) char b; (long)&b - (long)&b / 8 * 8;"
+assert 0 "char a; _Alignas(long) char b; (long)&b - (long)&b / 8 * 8;"
+assert 0 "char a; char _Alignas(16) b[3]; (long)b - (long)b / 16 * 16;"
+assert 0 "static char a; static _Alignas(16) char b; (long)&b - (long)&b / 16 * 16;"
+assert 0 "char a; _Alignas(64) int b; (long)&b - (long)&b / 64 * 64;"
+assert 0 "_Alignas(32) char a; char b; _Alignas(128) long c[2]; (long)&a - (long)&a / 32 * 32 + (long)c - (long)c / 128 * 128;"
+assert 7 "char a; _Alignas(256) int b = 3; int add2(int, int); long *p = alloca(8); *p = 4; add2(b, *p);"
+assert 16 "struct S { char c; _Alignas(8) int x; }; sizeof(struct S);"
+assert 8 "struct S { char c; _Alignas(8) int x; } s; (char *)&s.x - (char *)&s;"
+assert 4 "_Alignas(16) int x; sizeof(x);"
+assert_error "<input>:2: アラインメントを型のアラインメント(4)より小さくすることはできません: 1" $'char c;\n_Alignas(1) int x;'
+assert_error "<input>:1: アラインメントは2の累乗でなければなりません: 3" "int _Alignas(3) x;"
+assert 0 "char c; _Alignas(4) _Alignas(8) int x; long _Alignas(8) l; (long)&x - (long)&x / 8 * 8;"
+
+# VARIABLE-LENGTH ARRAYS AND ALLOCA
+assert 3 "int n = 3; int a[n]; a[0] = 1; a[2] = 2; a[0] + a[2];"
+assert 12 "int n = 3; int a[n]; sizeof(a);"
+assert 40 "long n = 5; long a[n]; sizeof a;"
+assert 72 "int n = 3; int a[n][6]; sizeof a;"
+assert 24 "int n = 3; int a[n][6]; sizeof(a[1]);"
+assert 15 "int n = 4; int m = 2; int a[n][3]; a[m][1] = 15; a[2][1];"
+assert 84 "int n = 3; int m = 4; int a[n][m]; a[2][3] = 7; a[1][0] = 5; int *p = &a[0][0]; sizeof(a) + sizeof(a[1]) + p[11] + p[4] + (a[2] - a[0]);"
+assert 137 "int n = 2; int m = 3; int k = 4; long a[n][m][k]; a[1][2][3] = 9; long *p = &a[0][0][0]; p[23] + sizeof(a[1]) + sizeof(a[1][2]);"
+assert 34 "int n = 5; char a[3][n]; a[2][4] = 1; sizeof(a) + sizeof(a[0]) + (&a[2][4] - &a[0][0]);"
+assert 24 "int n = 2; int m = 3; sizeof(int[n][m]);"
+assert 20 "int n = 5; sizeof(int[n]);"
+assert 6 "int n = 2; n = n + 1; char c[n * 2]; sizeof(c);"
+assert 45 "int n = 10; int a[n]; int *p = a; int i = 0; do p[i] = i; while ((i = i + 1) < n); i = 0; int s = 0; do s = s + a[i]; while ((i = i + 1) < n); s;"
+assert 7 "int x = 7; int n = 4; int a[n]; int y = 3; a[3] = 1; x + a[3] - 1 + y - 3;"
+assert 1 "int n = 1; int a[n]; int b[n]; (long)a - (long)b >= 16;"
+assert 0 "int n = 3; double d[n]; (long)d - (long)d / 16 * 16;"
+assert 3 "int add2(int, int); int n = 2; add2(1, (int[2]){0, 2}[1] + (sizeof(int[n]) - 8));"
+assert 9 "int add2(int, int); int n = 3; add2(4, sizeof(char[n]) + (char *)alloca(16) - (char *)alloca(16) - 14);"
+assert 5 "char *p = alloca(8); p[0] = 2; p[7] = 3; p[0] + p[7];"
+assert 11 "int add2(int, int); add2(5, ((int *)alloca(4))[0] = 6);"
+assert 4 "int n = 2; int a[n]; a[1] = 4; int m = 8; int b[m]; b[7] = 0; a[1];"
+
+# BIT-FIELDS
+assert 4 "struct S { unsigned a : 3; unsigned b : 5; }; sizeof(struct S);"
+assert 1 "struct S { char a : 3; char b : 5; }; sizeof(struct S);"
+assert 4 "struct S { char c; int b : 4; }; sizeof(struct S);"
+assert 8 "struct S { int a : 20; int b : 20; }; sizeof(struct S);"
+assert 4 "struct S { int a : 3; int : 0; char c; }; (char *)&((struct S *)0)->c - (char *)0;"
+assert 2 "struct S { int : 3; char c; }; sizeof(struct S);"
+assert 1 "union U { int : 5; char c; }; sizeof(union U);"
+assert 16 "struct S { long a : 1; char c; }; sizeof(struct S) * 2;"
+assert 5 "struct S { unsigned a : 3; unsigned b : 5; } s; s.a = 5; s.b = 17; s.a;"
+assert 17 "struct S { unsigned a : 3; unsigned b : 5; } s; s.a = 5; s.b = 17; s.b;"
+assert 1 "struct S { unsigned a : 3; unsigned b : 5; } s; s.a = 5; s.b = 17; *(unsigned char *)&s == 5 + 17 * 8;"
+assert 1 "struct S { unsigned a : 3; } s; s.a = 9; s.a;"
+assert 1 "struct S { int a : 3; } s; s.a = 7; s.a == -1;"
+assert 1 "struct S { int a : 3; } s; s.a = -4; s.a == -4;"
+assert 1 "struct S { int a : 3; } s; (s.a = 5) == -3;"
+assert 1 "struct S { unsigned a : 3; } s; s.a = 0; s.a - 1 < 0;"
+assert 7 "struct S { int x; unsigned a : 3; int y; } s; s.x = -1; s.y = -1; s.a = 7; s.a;"
+assert 1 "struct S { int x : 4; unsigned a : 3; int y : 9; } s; s.x = -1; s.y = -1; s.a = 2; s.x + s.y == -2;"
+assert 6 "struct S { long a : 40; long b : 20; } s; s.a = 4; s.b = 2; s.a + s.b;"
+assert 1 "struct S { long a : 40; } s; s.a = -1; s.a == -1;"
+assert 1 "struct S { _Bool b : 1; } s; s.b = 5; s.b;"
+assert 14 "struct S { unsigned a : 3; unsigned b : 5; } s = {6, 8}; s.a + s.b;"
+assert 3 "struct S { int x : 4; int y : 4; } s = {.y = 3}; s.x + s.y;"
+assert 1 "struct S { int x : 2; int y : 6; } s = {-1, 31}; s.x == -1 && s.y == 31;"
+assert 1 "static struct S { int x : 2; unsigned y : 6; int z; } s = {-1, 65, 7}; s.x == -1 && s.y == 1 && s.z == 7;"
+assert 10 "struct S { char a : 4; char b : 4; } s; struct S *p = &s; p->a = 3; p->b = 7; p->a + p->b;"
+assert 2 "union U { int a : 3; int b; } u; u.b = 0; u.a = 2; u.b;"
+
+# PREPROCESSOR
+assert 3 $'#define A 3\nA;'
+assert 7 $'#define A 3\n#define B A + 4\nB;'
+assert 10 $'#define A (2 + 3)\nA * 2;'
+assert 4 $'#define A 4\nint x = A;\n#undef A\nint A = 1;\nx;'
+assert 2 $'#define A A\nint A = 2;\nA;'
+assert 5 $'#define A B\n#define B A\nint A = 5;\nA;'
+assert 8 $'#define TYPE long\nTYPE x = 6;\nsizeof(TYPE) + x - 6;'
+assert 4 $'#define long int\nlong x;\nsizeof(x);'
+assert 1 $'#\n#define A 1\n  # /* comment */\nA;'
+assert 3 $'int x = 1; // comment\n/* multi\nline */ x = x + 2; x;'
+assert 8 $'#define A \\\n  8\nA;'
+assert 2 $'#if 1\n2;\n#else\n3;\n#endif'
+assert 3 $'#if 0\n2;\n#else\n3;\n#endif'
+assert 4 $'#if 0\n2;\n#elif 1\n4;\n#else\n3;\n#endif'
+assert 5 $'#if 1\n5;\n#elif 1\n4;\n#endif'
+assert 6 $'#if 0\n#if 1\n2;\n#else\n3;\n#endif\n#else\n6;\n#endif'
+assert 7 $'#define A 2\n#if A == 2 && A * 3 > 5\n7;\n#endif'
+assert 8 $'#if UNDEFINED\n1;\n#else\n8;\n#endif'
+assert 9 $'#define A\n#ifdef A\n9;\n#endif'
+assert 10 $'#ifdef A\n1;\n#else\n10;\n#endif'
+assert 11 $'#ifndef A\n11;\n#endif'
+assert 12 $'#define A 0\n#if defined A && defined(A) && !defined B\n12;\n#endif'
+assert 13 $'#if 0\n#error never\n#unknown directive\n#endif\n13;'
+assert 14 $'#if 0\nthis won\'t "compile\n#endif\n14;'
+assert 15 $'#if 10 % 4 == 2 && (1 | 4) == 5 && ~0 == -1 && -1 > 0u\n15;\n#endif'
+assert 16 $'#if defined(X) && 10 / X > 1\n1;\n#else\n16;\n#endif'
+assert 2 $'#include "once.h"\n#include "once.h"\n#include "./once.h"\nonce;' -Itests/tmp/include
+assert 7 $'#pragma weak foo\n#line 7\n__LINE__;'
+assert_error "<input>:2: #error bad value: 'x'" $'int a;\n#error bad value: \'x\'\na;'
+assert 14 $'#define X 1\n#if X\n#define Y 14\n#endif\nY;'
+assert 15 $'#ifdef __X\n0;\n#elif defined _Y\n1;\n#else\n15;\n#endif'
+assert 3 $'#include "three.h"\nthree;' -Itests/tmp/include
+assert 3 $'#include <three.h>\n#include "three.h"\nTHREE;' -I tests/tmp/include
+assert 3 $'#define H <three.h>\n#include H\nTHREE;' -I tests/tmp/include
+assert 3 $'#define Q "three.h"\n#define INC(f) f\n#include INC(Q)\nthree;' -Itests/tmp/include
+assert 8 $'#define H <stdint.h>\n#include H\nsizeof(int64_t);'
+assert 11 $'#include "tests/tmp/include/sub/outer.h"\nOUTER;'
+assert 11 $'#include <sub/outer.h>\nOUTER;' -Itests/tmp/nonexistent -Itests/tmp/include
+
+# FUNCTION-LIKE MACROS
+assert 7 $'#define ADD(a, b) a + b\nADD(3, 4);'
+assert 10 $'#define ADD(a, b) a + b\n2 * ADD(3, 4);'
+assert 10 $'#define MUL(a, b) ((a) * (b))\nMUL(1 + 1, 2 + 3);'
+assert 3 $'#define F() 3\nF();'
+assert 5 $'#define F (x)\nint x = 5;\nF;'
+assert 6 $'#define F(x) x\nint F = 6;\nF;'
+assert 9 $'#define ADD(a, b) (a + b)\nADD(ADD(1, 2), ADD(2, 4));'
+assert 4 $'#define F(x) x\nint a[] = {1, 2}; F((a[1])) + F(2);'
+assert 6 $'#define SUM(...) sum(__VA_ARGS__)\n#define sum(a, b, c) a + b + c\nSUM(1, 2, 3);'
+assert 3 $'int add2();\n#define ADD(f, ...) f(1, ## __VA_ARGS__)\n#define one(x) x\nADD(add2, 2) + ADD(one) - one(1);'
+assert 3 $'#define FIRST(x, ...) x\nFIRST(3);'
+assert 4 $'#define S(x) #x\nchar s[] = S(a  + b);\nsizeof(s) - 1 - 1;'
+assert 34 $'#define S(x) #x\nchar s[] = S("q");\ns[0];'
+assert 12 $'#define CAT(a, b) a ## b\nint xy = 12;\nCAT(x, y);'
+assert 12 $'#define CAT(a, b) a ## b\nCAT(1, 2);'
+assert 5 $'#define CAT(a, b) a ## b\nint y = 5;\nCAT(, y);'
+assert 8 $'#define SH(a) 1 a ## < 3\nSH(<);'
+assert 2 $'#define f(x) x + f\nint f = 1;\nf(f);'
+assert 6 $'#define f(a) a*g\n#define g(a) f(a)\nint g = 3;\nf(2)(1);'
+assert 4 $'\n\n\n__LINE__;'
+assert 3 $'#define L __LINE__\n\nL;'
+assert 1 $'#if __STDC__ && __STDC_VERSION__ >= 201112\n1;\n#endif'
+assert 2 $'#ifdef __FILE__\nchar f[] = __FILE__; (f[1] == 105) + (sizeof(f) == 8);\n#endif'
+assert 13 $'#define APPLY(f, x) f(x)\n#define INC(x) x + 1\nAPPLY(INC, 12);'
+
+# PREPROCESSED OUTPUT
+assert_pp $'# 1 "<input>"\nint x = 1 + 2;' $'int x = 1 + 2;'
+assert_pp $'# 2 "<input>"\n(3)*2;' $'#define F(x) (x)*2\nF(3);'
+assert_pp $'# 1 "<input>"\na;\n\nb;' $'a;\n#if 1\nb;\n#endif'
+assert_pp $'# 2 "<input>"\n"a \\"x\\"";' $'#define S(x) #x\nS(a  "x");'
+assert_pp $'# 1 "<input>"\nchar c = \'\\n\' + \'a\' + 0x1Fu + 010;' $'char c = \'\\n\' + \'a\' + 0x1Fu + 010;'
+assert_pp $'# 4 "tests/tmp/include/three.h"\nint three = 3;\n# 2 "<input>"\nthree;' $'#include "three.h"\nthree;' -Itests/tmp/include
+
+# BUILT-IN HEADERS
+assert 8 $'#include <stddef.h>\nsize_t n = 3; sizeof(n);'
+assert 1 $'#include <stddef.h>\nint *p = NULL; p == 0;'
+assert 8 $'#include <stddef.h>\nstruct S { char c; int a; long b; };\noffsetof(struct S, b);'
+assert 8 $'#include <stddef.h>\nstruct S { char c; int a; long b; };\nstatic int o = offsetof(struct S, b); o;'
+assert 8 $'#include <stddef.h>\nstruct S { char c; int a; long b; };\nchar buf[offsetof(struct S, b)]; sizeof(buf);'
+assert 16 $'#include <stddef.h>\nstruct T { int x; struct { char c; int y[3]; } in; };\nenum { E = offsetof(struct T, in.y[2]) }; E;'
+assert 8 $'#include <stddef.h>\nptrdiff_t d = 1; sizeof(d) + sizeof(wchar_t) - 4;'
+assert 1 $'#include <stdbool.h>\nbool b = 2; b == true && sizeof(bool) == 1;'
+assert 0 $'#include <stdbool.h>\n#if __bool_true_false_are_defined\nfalse;\n#endif'
+assert 15 $'#include <stdint.h>\nsizeof(int8_t) + sizeof(int16_t) + sizeof(int32_t) + sizeof(int64_t);'
+assert 1 $'#include <stdint.h>\nuint8_t x = 255; x = x + 1; x == 0 && UINT8_MAX == 255;'
+assert 1 $'#include <stdint.h>\nint64_t x = INT64_MIN; x < 0 && x - 1 == INT64_MAX;'
+assert 1 $'#include <stdint.h>\nUINT64_MAX > 0 && SIZE_MAX == UINT64_MAX && INT32_MAX + INT32_MIN == -1;'
+assert 8 $'#include <stdint.h>\nsizeof(intptr_t) + sizeof(uintmax_t) - sizeof(UINT64_C(1));'
+assert 24 $'#include <stdarg.h>\nva_list ap; va_list aq; va_copy(aq, ap); va_end(aq); sizeof(ap);'
+assert 24 $'#include <stdarg.h>\nint vsprintf(char *, char *, va_list);\nsizeof(__gnuc_va_list);'
+assert 15 $'#include <stdarg.h>\nint sum(int n, ...) { va_list ap; va_start(ap, n); int s = 0; do s = s + va_arg(ap, int); while ((n = n - 1) > 0); va_end(ap); return s; }\nsum(5, 1, 2, 3, 4, 5);'
+assert 7 $'#include <stdarg.h>\ndouble second(int n, ...) { va_list ap, aq; va_start(ap, n); va_copy(aq, ap); va_arg(ap, double); double d = va_arg(aq, double) + va_arg(ap, double); va_end(ap); va_end(aq); return d; }\n(int)second(2, 2.5, 4.5);'
+assert 5 $'#include <stddef.h>\nNULL;' -Itests/tmp/include/override
+
+# LITERALS, CONDITIONAL OPERATOR AND GNU EXTENSIONS
+assert 31 "0x1f;"
+assert 15 "017;"
+assert 5 "0b101;"
+assert 97 "'a';"
+assert 10 "'\\n';"
+assert 1 "'\\377' == -1;"
+assert 127 "'\\x7f';"
+assert 8 "sizeof(1L);"
+assert 4 "sizeof(1U);"
+assert 8 "sizeof(4294967296);"
+assert 1 "1U - 2 > 0;"
+assert 8 "sizeof(1ULL);"
+assert 2 "1 ? 2 : 3;"
+assert 3 "0 ? 2 : 3;"
+assert 5 "int x = 0; 1 ? (x = 5) : (x = 6); x;"
+assert 4 "0 ? 1 : 0 ? 2 : 4;"
+assert 8 "sizeof(1 ? 1 : 2L);"
+assert 3 "int a[2] = {2, 3}; int *p = a; *(1 ? p + 1 : p);"
+assert 6 "char x[1 ? 6 : 2]; sizeof(x);"
+assert 4 "enum { E = 1 << 2 }; E;"
+assert 1 "enum { E = (unsigned long)-1 / 2 > 0 }; E;"
+assert 1 $'#include <stdint.h>\nstatic uint64_t s = UINT64_MAX / 2; s == 9223372036854775807;'
+assert 1 "static unsigned u = 4294967295u / 2 <= 0u - 1; u;"
+assert 1 "static unsigned long s = 1UL << 63 >> 63; s;"
+assert 0 "static unsigned u = 0xffffffffu + 1; u;"
+assert 1 "static long l = 1L << 63; l < 0;"
+assert 1 "static long m = 4611686018427387904L * 4; enum { E = 65536 * 65536 }; m == 0 && E == 0;"
+assert 2 "enum { E = (-8L >> 1) + 6 }; E;"
+assert 2 "int *restrict p; __restrict int x = 2; x;"
+assert 3 "__extension__ int x __attribute__((unused)) = 3; __extension__ x;"
+assert 8 "struct __attribute__((packed)) S { long a; } __attribute__((aligned(8))) s; sizeof(s);"
+assert 7 "int myabs(int) __asm__(\"abs\"); myabs(-7);"
+assert 5 "static inline int twice(int x) { return x * 2; } int twice(int); 5;"
+assert 4 "extern int ext1; extern int ext1; typedef int T; typedef int T; T x = 4; x;"
+assert 24 "__builtin_va_list ap; sizeof(ap);"
+assert 3 $'#include <stdio.h>\nchar b[8]; char f[] = "%d"; sprintf(b, f, 123);'
+assert 49 $'#include <stdio.h>\n#include <string.h>\nchar b[8]; char f[] = "%d"; sprintf(b, f, 123); strlen(b) + b[0] - 3;'
+assert 42 $'#include <stdlib.h>\nabs(-42);'
+assert 1 $'#include <ctype.h>\n#include <wchar.h>\n#include <math.h>\n#include <unistd.h>\n1;'
+assert 0 $'#include <stdio.h>\n#include <stdlib.h>\n#include <errno.h>\n#include <limits.h>\nINT_MAX == 2147483647 && EOF == -1 ? 0 : 1;'
+assert 0 $'#include <stdio.h>\nchar m[] = "%s\\n"; char s[] = "hello"; printf(m, s); fflush(stdout) + ferror(stdout);'
+assert 3 $'#include <stdio.h>\nprintf("hi\\n");'
+assert 0 $'#include <assert.h>\nassert(1); 0;'
+assert 67 $'#include <string.h>\nstrlen(__FILE__) + __FILE__[0];'
+assert 3 'sizeof("ab");'
+assert 98 '"abc"[1];'
+assert 100 'static char *p = "ab" "cd"; p[3];'
+assert 121 'char *q = "xyz"; struct { char *s; int n; } t = {"str", 3}; q[1];'
+assert 116 'struct { char *s; int n; } t = {"str", 3}; t.s[1];'
+
+# SEMANTIC ANALYSIS
+assert 3 "a = 1; int b = 2; a + b;"
+assert 5 "int x; *&x = 5; x;"
+assert 2 "struct { int a; } s, t; t.a = 2; s = t; s.a;"
+assert_error "<input>:1: 代入の左辺値が変数ではありません" "1 = 2;"
+assert_error "<input>:1: 識別子が宣言より前に使われています: a" "a = 300; char a; a;"
+assert_error "<input>:1: 代入の左辺値が変数ではありません" "int a; sizeof(1 = a);"
+assert_error "<input>:1: 代入の左辺値が変数ではありません" "struct S { int a; } g(); g().a = 1;"
+assert_error "<input>:2: 左辺値ではない値のアドレスは取得できません" $'int x;\n&(x + 1);'
+assert_error "<input>:2: 代入する値の型が変数の型と一致しません" $'int *p; double d;\nd = p;'
+assert_error "<input>:2: 1番目の引数の型が一致しません" $'int add2(int,int); int *p;\nadd2(p, 1);'
+assert_error "<input>:1: 代入する値の型が変数の型と一致しません" 'int *p; long l = p;'
+assert 1 'int *p = 0; char *q = (void *)0; _Bool b = ""; !p && !q && b;'
+assert 5 'sizeof(__func__);'
+assert_error "<input>:1: const修飾された値には代入できません" "const int c = 1; c = 2;"
+assert_error "<input>:1: const修飾されたメンバを持つ構造体・共用体には代入できません" "struct S {const int a; int b;} s, t; s = t;"
+assert 3 "struct S {const int a; int b;} t = {1, 2}; struct S s = t; s.a + s.b;"
+assert_error $'<input>:1: 代入の左辺値が変数ではありません\n<input>:3: ポインタではない値を参照外ししています' $'1 = 2;\nint x;\n*x;'
+assert_error "./tests/tmp/include/bad.h:2: 配列には代入できません" '#include "tests/tmp/include/bad.h"'
+
 echo "===test end==="